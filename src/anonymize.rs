@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{ConcatError, output_error};
+
+/// Stable mapping from real relative paths to pseudonyms such as
+/// `dir_01/file_03.rs`. Stored on disk keyed by pseudonym so answers that
+/// mention a pseudonym can be translated back.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PathMap {
+    /// Real path of every pseudonymous directory and file
+    paths: BTreeMap<String, String>,
+    #[serde(skip)]
+    pseudonyms: HashMap<String, String>,
+    #[serde(skip)]
+    dir_count: usize,
+    #[serde(skip)]
+    file_count: usize,
+}
+
+impl PathMap {
+    /// Loads a previously written mapping so pseudonyms stay the same across
+    /// runs. A missing file yields an empty mapping.
+    pub fn load(path: &Path) -> Result<Self, ConcatError> {
+        let mut map: Self = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| {
+                ConcatError::Config(format!("invalid path mapping {}: {}", path.display(), e))
+            })?,
+            Err(_) => Self::default(),
+        };
+
+        for (pseudonym, real) in &map.paths {
+            map.pseudonyms.insert(real.clone(), pseudonym.clone());
+            let name = pseudonym.rsplit('/').next().unwrap_or(pseudonym);
+            if let Some(n) = numbered(name, "dir_") {
+                map.dir_count = map.dir_count.max(n);
+            } else if let Some(n) = numbered(name, "file_") {
+                map.file_count = map.file_count.max(n);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConcatError> {
+        let json = serde_json::to_string_pretty(self).expect("path mapping serializes");
+        fs::write(path, json).map_err(output_error(path))
+    }
+
+    /// Returns the pseudonym for a relative file path, assigning new
+    /// pseudonyms to its directories and to the file as needed
+    pub fn anonymize(&mut self, rel_path: &Path) -> PathBuf {
+        let components: Vec<String> = rel_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let Some((file_name, dirs)) = components.split_last() else {
+            return rel_path.to_path_buf();
+        };
+
+        let mut real = String::new();
+        for dir in dirs {
+            if !real.is_empty() {
+                real.push('/');
+            }
+            real.push_str(dir);
+            self.assign(&real, |map| {
+                map.dir_count += 1;
+                format!("dir_{:02}", map.dir_count)
+            });
+        }
+
+        let ext = Path::new(file_name)
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        let real = rel_path.to_string_lossy().replace('\\', "/");
+        PathBuf::from(self.assign(&real, |map| {
+            map.file_count += 1;
+            format!("file_{:02}{}", map.file_count, ext)
+        }))
+    }
+
+    /// Looks up or creates the pseudonym (a full path) for a real path. New
+    /// names are nested under the pseudonym of the real path's parent.
+    fn assign(&mut self, real: &str, next_name: impl FnOnce(&mut Self) -> String) -> String {
+        if let Some(existing) = self.pseudonyms.get(real) {
+            return existing.clone();
+        }
+        let parent = real
+            .rsplit_once('/')
+            .and_then(|(parent, _)| self.pseudonyms.get(parent).cloned());
+        let name = next_name(self);
+        let pseudonym = match parent {
+            Some(parent) => format!("{}/{}", parent, name),
+            None => name,
+        };
+        self.pseudonyms.insert(real.to_string(), pseudonym.clone());
+        self.paths.insert(pseudonym.clone(), real.to_string());
+        pseudonym
+    }
+}
+
+/// Parses the number out of names like `dir_07` or `file_12.rs`
+fn numbered(name: &str, prefix: &str) -> Option<usize> {
+    let rest = name.strip_prefix(prefix)?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudonyms_nest_and_keep_extensions() {
+        let mut map = PathMap::default();
+        assert_eq!(
+            map.anonymize(Path::new("payments/ledger.rs")),
+            Path::new("dir_01/file_01.rs")
+        );
+        assert_eq!(
+            map.anonymize(Path::new("payments/audit.rs")),
+            Path::new("dir_01/file_02.rs")
+        );
+        assert_eq!(
+            map.anonymize(Path::new("payments/ledger.rs")),
+            Path::new("dir_01/file_01.rs")
+        );
+    }
+
+    #[test]
+    fn sections_naming_real_paths_are_rejected() {
+        for flag in [
+            "--front-matter",
+            "--summary-footer",
+            "--project-summary",
+            "--license-notice",
+            "--deps-appendix",
+            "--git-log-summary=5",
+            "--assets-inventory",
+        ] {
+            let argv = [
+                "md_concat",
+                "out.md",
+                "--no-config",
+                "--extensions=rs",
+                "--anonymize-paths",
+                flag,
+            ];
+            assert!(crate::parse_args(argv).is_err(), "{}", flag);
+        }
+    }
+}
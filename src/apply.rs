@@ -0,0 +1,209 @@
+use similar::TextDiff;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::directives;
+use crate::document;
+use crate::error::{ConcatError, output_error};
+use crate::output;
+
+/// A file whose section in the edited document differs from the working tree
+struct Change {
+    rel_path: PathBuf,
+    path: PathBuf,
+    /// Current content, or None for a file that does not exist yet
+    current: Option<String>,
+    edited: String,
+}
+
+/// Writes the sections of an edited document back to the files below `root`
+/// after showing a diff and asking for confirmation
+pub fn apply(input: &Path, root: &Path, dry_run: bool, yes: bool) -> Result<(), ConcatError> {
+    let text = fs::read_to_string(input).map_err(|source| ConcatError::InputFile {
+        path: input.to_path_buf(),
+        source,
+    })?;
+    let sections = document::parse(&text);
+    if sections.is_empty() {
+        return Err(ConcatError::NoFilesMatched);
+    }
+
+    let mut changes = Vec::new();
+    for (rel_path, edited) in document::resolve(&sections) {
+        let Some(safe_path) = document::safe_relative(&rel_path) else {
+            eprintln!(
+                "Warning: Skipping {}: path leaves the target directory",
+                rel_path.display()
+            );
+            continue;
+        };
+        let edited = match edited {
+            Ok(edited) => edited,
+            Err(reason) => {
+                eprintln!("Warning: Skipping {}: {}", rel_path.display(), reason);
+                continue;
+            }
+        };
+
+        let path = root.join(safe_path);
+        let current = fs::read_to_string(&path).ok();
+        if let Some(ref current) = current {
+            if document::same_content(current, &edited) {
+                continue;
+            }
+            // Regions left out by md_concat:begin-ignore comments are not in
+            // the section, so writing it back would delete them
+            let (kept, removed) = directives::strip_ignored(current);
+            if !removed.is_empty() {
+                if !document::same_content(&kept, &edited) {
+                    eprintln!(
+                        "Warning: Skipping {}: it has md_concat:begin-ignore regions \
+                         that are not in the document; edit it directly",
+                        rel_path.display()
+                    );
+                }
+                continue;
+            }
+        }
+        changes.push(Change {
+            rel_path: safe_path.to_path_buf(),
+            path,
+            current,
+            edited,
+        });
+    }
+
+    if changes.is_empty() {
+        status!("No changes to apply");
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+    for change in &changes {
+        let old = change.current.as_deref().unwrap_or("");
+        let name = change.rel_path.display().to_string();
+        let old_name = if change.current.is_some() {
+            name.as_str()
+        } else {
+            "/dev/null"
+        };
+        let diff = TextDiff::from_lines(old, &change.edited)
+            .unified_diff()
+            .header(old_name, &name)
+            .to_string();
+        write!(stdout, "{}", diff)?;
+    }
+    stdout.flush()?;
+    drop(stdout);
+
+    let created = changes.iter().filter(|c| c.current.is_none()).count();
+    let summary = format!(
+        "{} file(s) to update, {} to create",
+        changes.len() - created,
+        created
+    );
+    if dry_run {
+        status!("\n{} (dry run, nothing written)", summary);
+        return Ok(());
+    }
+    if !yes && !confirm(&format!("\n{}. Apply? [y/N] ", summary))? {
+        return Err(ConcatError::Cancelled);
+    }
+
+    for change in &changes {
+        if let Some(parent) = change.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).map_err(output_error(parent))?;
+        }
+        output::write_atomic(&change.path, |writer| {
+            writer.write_all(change.edited.as_bytes())?;
+            Ok(())
+        })?;
+    }
+    status!("Applied changes to {} file(s)", changes.len());
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal. Without a terminal the answer is no.
+pub fn confirm(prompt: &str) -> Result<bool, ConcatError> {
+    if !io::stdin().is_terminal() {
+        eprintln!("Not a terminal; pass --yes to confirm");
+        return Ok(false);
+    }
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIB: &str = "fn a() {}\n// md_concat:begin-ignore\nfn secret() {}\n// md_concat:end-ignore\nfn b() {}\n";
+
+    fn document(content: &str) -> String {
+        format!("## lib.rs\n\n```rust\n{}```\n", content)
+    }
+
+    fn apply_document(root: &Path, text: &str) -> Result<(), ConcatError> {
+        let input = root.join("out.md");
+        fs::write(&input, text).unwrap();
+        apply(&input, root, false, true)
+    }
+
+    #[test]
+    fn unedited_document_keeps_ignored_regions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), LIB).unwrap();
+
+        apply_document(dir.path(), &document("fn a() {}\nfn b() {}\n")).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("lib.rs")).unwrap(), LIB);
+    }
+
+    #[test]
+    fn edited_file_with_ignored_regions_is_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), LIB).unwrap();
+
+        apply_document(dir.path(), &document("fn a() {}\nfn c() {}\n")).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("lib.rs")).unwrap(), LIB);
+    }
+
+    #[test]
+    fn edited_sections_are_written_back() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), "fn a() {}\n").unwrap();
+
+        let text = format!(
+            "{}\n## new/mod.rs\n\n```rust\nfn n() {{}}\n```\n",
+            document("fn b() {}\n")
+        );
+        apply_document(dir.path(), &text).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("lib.rs")).unwrap(),
+            "fn b() {}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("new/mod.rs")).unwrap(),
+            "fn n() {}\n"
+        );
+    }
+
+    #[test]
+    fn sections_outside_the_root_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+
+        let text = "## ../escaped.rs\n\n```rust\nfn x() {}\n```\n";
+        assert!(matches!(
+            apply_document(&root, text),
+            Ok(()) | Err(ConcatError::NoFilesMatched)
+        ));
+        assert!(!dir.path().join("escaped.rs").exists());
+    }
+}
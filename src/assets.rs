@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::CliArgs;
+use crate::error::ConcatError;
+use crate::exclude::{ExcludeDirs, OnlyPaths};
+use crate::gitignore::{GitignoreManager, collect_files_with_gitignore};
+use crate::submodules::Submodules;
+
+/// Extensions of files whose contents never go into the document, by the type shown
+const ASSET_TYPES: &[(&str, &[&str])] = &[
+    (
+        "image",
+        &[
+            "png", "jpg", "jpeg", "gif", "bmp", "ico", "icns", "webp", "tif", "tiff", "psd",
+            "avif", "heic",
+        ],
+    ),
+    (
+        "archive",
+        &[
+            "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "jar", "war", "whl", "deb",
+            "rpm", "dmg", "iso",
+        ],
+    ),
+    (
+        "binary",
+        &[
+            "exe", "dll", "so", "dylib", "a", "o", "obj", "lib", "bin", "wasm", "class", "pyc",
+            "rlib",
+        ],
+    ),
+    ("font", &["ttf", "otf", "woff", "woff2", "eot"]),
+    (
+        "audio/video",
+        &[
+            "mp3", "wav", "ogg", "flac", "m4a", "mp4", "mov", "avi", "mkv", "webm",
+        ],
+    ),
+    (
+        "document",
+        &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt"],
+    ),
+    ("database", &["sqlite", "sqlite3", "db"]),
+];
+
+/// A file left out for being binary, listed by --assets-inventory
+pub struct Asset {
+    pub rel_path: PathBuf,
+    pub kind: &'static str,
+    pub size: u64,
+}
+
+/// The images, archives, binaries and other assets in the input directories,
+/// found with the same exclusions and gitignore rules as the files themselves.
+/// Extensions passed to --extensions are not assets.
+pub fn inventory(args: &CliArgs, roots: &[PathBuf]) -> Result<Vec<Asset>, ConcatError> {
+    let extensions: HashSet<String> = ASSET_TYPES
+        .iter()
+        .flat_map(|(_, extensions)| extensions.iter())
+        .filter(|ext| !args.extensions.iter().any(|included| included == *ext))
+        .map(|ext| ext.to_string())
+        .collect();
+    let gitignore_manager = if args.respect_gitignore {
+        GitignoreManager::discover_and_load(roots, &args.additional_gitignore_files)
+            .unwrap_or_default()
+    } else {
+        GitignoreManager::default()
+    };
+    let (found, _) = collect_files_with_gitignore(
+        roots,
+        &extensions,
+        &ExcludeDirs::new(&args.exclude_dirs)?,
+        &OnlyPaths::new(&args.only_paths),
+        &gitignore_manager,
+        args.root_label.applies(roots.len()),
+        &Submodules::discover(roots, args.submodules),
+    );
+    Ok(found
+        .into_iter()
+        .filter_map(|(rel_path, abs_path)| {
+            let ext = rel_path.extension()?.to_str()?.to_string();
+            let (kind, _) = ASSET_TYPES
+                .iter()
+                .find(|(_, extensions)| extensions.contains(&ext.as_str()))?;
+            Some(Asset {
+                size: fs::metadata(&abs_path).map_or(0, |metadata| metadata.len()),
+                rel_path,
+                kind,
+            })
+        })
+        .collect())
+}
+
+/// "Assets (not included)" section: a table of the assets with their types and
+/// sizes, so that a reader knows they exist (--assets-inventory)
+pub fn section(assets: &[Asset], heading_level: usize) -> String {
+    let mut section = format!("{} Assets (not included)\n\n", "#".repeat(heading_level));
+    let total: u64 = assets.iter().map(|asset| asset.size).sum();
+    let _ = writeln!(
+        section,
+        "{} file(s), {} in total, whose contents are left out.\n",
+        assets.len(),
+        human_size(total)
+    );
+    section.push_str("| File | Type | Size |\n|---|---|---|\n");
+    for asset in assets {
+        let _ = writeln!(
+            section,
+            "| `{}` | {} | {} |",
+            asset.rel_path.display(),
+            asset.kind,
+            human_size(asset.size)
+        );
+    }
+    section.push('\n');
+    section
+}
+
+/// "812 B", "14.2 KiB", "3.0 MiB"
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
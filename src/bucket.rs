@@ -0,0 +1,294 @@
+use crate::error::ConcatError;
+use crate::exclude::OnlyPaths;
+use crate::gitref::Snapshot;
+
+/// Downloads the objects below an `s3://bucket/prefix` or `gs://bucket/prefix`
+/// URL that have one of the `extensions` (and the .gitignore files) into a
+/// temporary directory, which then stands in for the input directory (--remote).
+/// With `only_paths`, only the objects below those sub-paths are listed and
+/// downloaded. Credentials and the region come from the usual environment
+/// variables of each provider.
+#[cfg(feature = "object-store")]
+pub fn snapshot(
+    url: &str,
+    extensions: &[String],
+    only_paths: &OnlyPaths,
+) -> Result<Snapshot, ConcatError> {
+    use object_store::ObjectStore;
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use std::fs;
+    use std::path::PathBuf;
+
+    let remote_error =
+        |e: object_store::Error| ConcatError::Config(format!("--remote {}: {}", url, e));
+    let (scheme, bucket, prefix) = parse_url(url)?;
+    let bucket_url = format!("{}://{}", scheme, bucket);
+    let store: Box<dyn ObjectStore> = match scheme {
+        "s3" => Box::new(
+            AmazonS3Builder::from_env()
+                .with_url(bucket_url)
+                .build()
+                .map_err(remote_error)?,
+        ),
+        _ => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(bucket_url)
+                .build()
+                .map_err(remote_error)?,
+        ),
+    };
+
+    let dir = tempfile::Builder::new()
+        .prefix("md_concat-")
+        .tempdir()
+        .map_err(|e| ConcatError::Config(format!("cannot create a snapshot: {}", e)))?;
+    // Named after the last part of the prefix, or the bucket, for --root-label
+    let name = prefix
+        .rsplit('/')
+        .find(|part| !part.is_empty())
+        .unwrap_or(bucket);
+    let root = dir.path().join(name);
+    fs::create_dir_all(&root)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let downloaded = runtime
+        .block_on(download(
+            store.as_ref(),
+            prefix,
+            &root,
+            extensions,
+            only_paths,
+        ))
+        .map_err(|e| match e {
+            DownloadError::Store(e) => remote_error(e),
+            DownloadError::Io(e) => ConcatError::Io(e),
+        })?;
+    status!("Downloaded {} object(s) from {}", downloaded, url);
+
+    Ok(Snapshot::from_dir(dir, vec![(PathBuf::from(url), root)]))
+}
+
+/// Failure of `download`, told apart so the store's errors can name the URL
+#[cfg(feature = "object-store")]
+#[derive(Debug)]
+enum DownloadError {
+    Store(object_store::Error),
+    Io(std::io::Error),
+}
+
+#[cfg(feature = "object-store")]
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Io(e)
+    }
+}
+
+/// Writes the wanted objects below `prefix` to `root`: those below each of
+/// `only_paths` (or all of them) with one of the `extensions`, and the
+/// .gitignore files among them and in the directories leading to them.
+/// Returns the number of objects written.
+#[cfg(feature = "object-store")]
+async fn download(
+    store: &dyn object_store::ObjectStore,
+    prefix: &str,
+    root: &std::path::Path,
+    extensions: &[String],
+    only_paths: &OnlyPaths,
+) -> Result<usize, DownloadError> {
+    use futures::TryStreamExt;
+    use object_store::path::Path as ObjectPath;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    let object_path = |rel_path: &Path| {
+        let parts: Vec<String> = std::iter::once(prefix.to_string())
+            .chain(
+                rel_path
+                    .iter()
+                    .map(|part| part.to_string_lossy().into_owned()),
+            )
+            .filter(|part| !part.is_empty())
+            .collect();
+        ObjectPath::from(parts.join("/"))
+    };
+    let sub_paths: Vec<&Path> = match only_paths.paths() {
+        [] => vec![Path::new("")],
+        paths => paths.iter().map(PathBuf::as_path).collect(),
+    };
+
+    let mut locations = BTreeSet::new();
+    for sub_path in &sub_paths {
+        let objects: Vec<_> = store
+            .list(Some(&object_path(sub_path)))
+            .try_collect()
+            .await
+            .map_err(DownloadError::Store)?;
+        // A sub-path naming a single object has nothing below it
+        if objects.is_empty() && !sub_path.as_os_str().is_empty() {
+            locations.insert(object_path(sub_path));
+        }
+        locations.extend(objects.into_iter().map(|object| object.location));
+        // The .gitignore files above the sub-path still apply to it
+        for dir in sub_path.ancestors().skip(1) {
+            locations.insert(object_path(&dir.join(".gitignore")));
+        }
+    }
+
+    let prefix = ObjectPath::from(prefix);
+    let mut downloaded = 0;
+    for location in locations {
+        let Some(rel_path) = location.prefix_match(&prefix) else {
+            continue;
+        };
+        let rel_path: PathBuf = rel_path.map(|part| part.as_ref().to_string()).collect();
+        let wanted = rel_path
+            .file_name()
+            .is_some_and(|name| name == ".gitignore")
+            || rel_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.iter().any(|wanted| wanted == ext));
+        if !wanted {
+            continue;
+        }
+        let object = match store.get(&location).await {
+            Ok(object) => object,
+            // Locations guessed rather than listed may not exist
+            Err(object_store::Error::NotFound { .. }) => continue,
+            Err(e) => return Err(DownloadError::Store(e)),
+        };
+        let last_modified = object.meta.last_modified;
+        let bytes = object.bytes().await.map_err(DownloadError::Store)?;
+        let path = root.join(&rel_path);
+        fs::create_dir_all(path.parent().unwrap_or(root))?;
+        fs::write(&path, &bytes)?;
+        fs::File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(last_modified.into())?;
+        downloaded += 1;
+    }
+    Ok(downloaded)
+}
+
+#[cfg(not(feature = "object-store"))]
+pub fn snapshot(
+    url: &str,
+    _extensions: &[String],
+    _only_paths: &OnlyPaths,
+) -> Result<Snapshot, ConcatError> {
+    parse_url(url)?;
+    Err(ConcatError::Config(
+        "--remote needs md_concat built with `--features object-store`".into(),
+    ))
+}
+
+/// Splits `s3://bucket/some/prefix` into its scheme, bucket and prefix
+fn parse_url(url: &str) -> Result<(&str, &str, &str), ConcatError> {
+    let unsupported = || {
+        ConcatError::Config(format!(
+            "--remote {}: expected s3://bucket/prefix or gs://bucket/prefix",
+            url
+        ))
+    };
+    let (scheme, rest) = url.split_once("://").ok_or_else(unsupported)?;
+    if !matches!(scheme, "s3" | "gs") {
+        return Err(unsupported());
+    }
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        return Err(unsupported());
+    }
+    Ok((scheme, bucket, prefix.trim_matches('/')))
+}
+
+#[cfg(all(test, feature = "object-store"))]
+mod tests {
+    use super::*;
+    use object_store::ObjectStore;
+    use object_store::memory::InMemory;
+    use object_store::path::Path as ObjectPath;
+
+    fn bucket() -> InMemory {
+        let store = InMemory::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for name in [
+            "repo/.gitignore",
+            "repo/src/.gitignore",
+            "repo/src/main.rs",
+            "repo/src/lib/mod.rs",
+            "repo/docs/guide.md",
+            "repo/assets/logo.png",
+            "repo/huge/data.rs",
+        ] {
+            runtime
+                .block_on(store.put(&ObjectPath::from(name), "x".into()))
+                .unwrap();
+        }
+        store
+    }
+
+    fn downloaded(only_paths: &[&str]) -> Vec<String> {
+        let store = bucket();
+        let dir = tempfile::tempdir().unwrap();
+        let only_paths: Vec<String> = only_paths.iter().map(|path| path.to_string()).collect();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let extensions = ["rs".to_string(), "md".to_string()];
+        let count = runtime
+            .block_on(download(
+                &store,
+                "repo",
+                dir.path(),
+                &extensions,
+                &OnlyPaths::new(&only_paths),
+            ))
+            .unwrap();
+        let mut files: Vec<String> = walkdir::WalkDir::new(dir.path())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let rel_path = entry.path().strip_prefix(dir.path()).unwrap();
+                rel_path.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        files.sort();
+        assert_eq!(count, files.len());
+        files
+    }
+
+    #[test]
+    fn without_only_paths_every_matching_object_is_downloaded() {
+        assert_eq!(
+            downloaded(&[]),
+            [
+                ".gitignore",
+                "docs/guide.md",
+                "huge/data.rs",
+                "src/.gitignore",
+                "src/lib/mod.rs",
+                "src/main.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn only_paths_limit_the_download() {
+        assert_eq!(
+            downloaded(&["src/lib"]),
+            [".gitignore", "src/.gitignore", "src/lib/mod.rs"]
+        );
+        assert_eq!(
+            downloaded(&["docs/guide.md"]),
+            [".gitignore", "docs/guide.md"]
+        );
+    }
+}
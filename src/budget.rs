@@ -0,0 +1,169 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::writer::FileStats;
+
+/// How files are chosen when the selection exceeds --max-tokens
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PackStrategy {
+    /// Drop the lowest-priority files, latest in the output first, until the rest fits
+    #[default]
+    Truncate,
+    /// Keep the files with the most priority weight per token, so many small important
+    /// files win over one large one
+    Greedy,
+}
+
+/// Removes files until the estimated total fits within `max_tokens`, as chosen
+/// by `strategy` from the priority weight of each file. The kept files stay in
+/// output order. Returns the pruned files.
+pub fn prune_to_budget(
+    files: &mut Vec<(PathBuf, PathBuf)>,
+    stats: &[FileStats],
+    weights: &[i64],
+    max_tokens: usize,
+    strategy: PackStrategy,
+) -> Vec<FileStats> {
+    let total: usize = stats.iter().map(FileStats::tokens).sum();
+    if total <= max_tokens {
+        return Vec::new();
+    }
+    let keep = match strategy {
+        PackStrategy::Truncate => truncate(weights, stats, total, max_tokens),
+        PackStrategy::Greedy => greedy(weights, stats, max_tokens),
+    };
+
+    let pruned = stats
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| !**keep)
+        .map(|(file, _)| file.clone())
+        .collect();
+    let mut index = 0;
+    files.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+    pruned
+}
+
+/// Files with the lowest priority weight are dropped first; among equal
+/// weights, files later in the output order go first
+fn truncate(
+    weights: &[i64],
+    stats: &[FileStats],
+    mut total: usize,
+    max_tokens: usize,
+) -> Vec<bool> {
+    let mut candidates: Vec<usize> = (0..weights.len()).collect();
+    candidates.sort_by_key(|&i| (weights[i], std::cmp::Reverse(i)));
+
+    let mut keep = vec![true; weights.len()];
+    for i in candidates {
+        if total <= max_tokens {
+            break;
+        }
+        keep[i] = false;
+        total -= stats[i].tokens();
+    }
+    keep
+}
+
+/// Greedy knapsack: files are taken by descending value per token while they
+/// fit, where a file's value is its priority weight shifted so the lowest
+/// weight in the selection is worth 1. With equal weights, the smallest files
+/// go first, which keeps as many files as possible.
+fn greedy(weights: &[i64], stats: &[FileStats], max_tokens: usize) -> Vec<bool> {
+    let lowest = weights.iter().copied().min().unwrap_or(0);
+    let density = |i: usize| (weights[i] - lowest + 1) as f64 / stats[i].tokens().max(1) as f64;
+
+    let mut candidates: Vec<usize> = (0..weights.len()).collect();
+    candidates.sort_by(|&a, &b| density(b).total_cmp(&density(a)).then(a.cmp(&b)));
+    fill(candidates, stats, max_tokens)
+}
+
+/// Takes the files in the order given while they fit; a file too large for
+/// the remaining budget is passed over for the smaller ones after it
+fn fill(
+    order: impl IntoIterator<Item = usize>,
+    stats: &[FileStats],
+    max_tokens: usize,
+) -> Vec<bool> {
+    let mut keep = vec![false; stats.len()];
+    let mut total = 0;
+    for i in order {
+        if total + stats[i].tokens() <= max_tokens {
+            keep[i] = true;
+            total += stats[i].tokens();
+        }
+    }
+    keep
+}
+
+/// Keeps the files that fit within `max_tokens` taken in output order, for a
+/// selection that is already ranked (--relevant-to). Returns the files left out.
+pub fn fill_in_order(
+    files: &mut Vec<(PathBuf, PathBuf)>,
+    stats: &[FileStats],
+    max_tokens: usize,
+) -> Vec<FileStats> {
+    let keep = fill(0..files.len(), stats, max_tokens);
+    let left_out = stats
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| !**keep)
+        .map(|(file, _)| file.clone())
+        .collect();
+    let mut index = 0;
+    files.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+    left_out
+}
+
+/// Keeps the files, in output order, up to the first one that would take the
+/// output past `max_bytes`; that file and all later ones are left out.
+/// Returns the files left out.
+pub fn truncate_to_bytes(
+    files: &mut Vec<(PathBuf, PathBuf)>,
+    stats: &[FileStats],
+    max_bytes: usize,
+) -> Vec<FileStats> {
+    let mut total = 0;
+    let fitting = stats
+        .iter()
+        .take_while(|file| {
+            total += file.bytes;
+            total <= max_bytes
+        })
+        .count();
+    files.truncate(fitting);
+    stats[fitting..].to_vec()
+}
+
+/// Parses a size in bytes with an optional unit: "500000", "512KiB", "10MB", "1.5GB".
+/// KB, MB and GB are powers of 1000; KiB, MiB and GiB powers of 1024.
+pub fn parse_size(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a size", text))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        other => return Err(format!("unknown size unit '{}'", other)),
+    };
+    Ok((number * multiplier as f64) as usize)
+}
@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::history;
+use crate::overrides;
+use crate::writer::WriteOptions;
+use crate::{CliArgs, TokenCounter};
+
+/// Cached facts about a single input file from a previous run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub sha256: String,
+    pub char_count: usize,
+    pub word_count: usize,
+}
+
+/// On-disk cache used to detect whether a run would produce the same output
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RunCache {
+    /// Fingerprint of the options that produced the cached output
+    pub options: String,
+    /// Entries keyed by the path shown for each included file, which stays the
+    /// same when --at-ref or --remote read it from a new temporary copy
+    pub files: BTreeMap<PathBuf, CacheEntry>,
+}
+
+/// Result of comparing the current file set against the cache
+pub struct CacheCheck {
+    /// The refreshed cache to store after a successful run
+    pub cache: RunCache,
+    /// Whether anything differs from the previous run
+    pub changed: bool,
+    /// Number of files that had to be re-read
+    pub reread: usize,
+}
+
+impl RunCache {
+    /// Loads a cache file, returning an empty cache if it is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache file
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Compares the given files against this cache, re-reading only files whose
+    /// size or modification time changed since the cached run
+    pub fn check(&self, options: &str, files: &[(PathBuf, PathBuf)]) -> CacheCheck {
+        let mut cache = RunCache {
+            options: options.to_string(),
+            files: BTreeMap::new(),
+        };
+        let mut changed = self.options != options || self.files.len() != files.len();
+        let mut reread = 0;
+
+        for (rel_path, abs_path) in files {
+            let previous = self.files.get(rel_path);
+            let metadata = match fs::metadata(abs_path) {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    changed = true;
+                    continue;
+                }
+            };
+            let (mtime_secs, mtime_nanos) = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| (d.as_secs(), d.subsec_nanos()))
+                .unwrap_or((0, 0));
+
+            if let Some(previous) = previous
+                && previous.size == metadata.len()
+                && previous.mtime_secs == mtime_secs
+                && previous.mtime_nanos == mtime_nanos
+            {
+                cache.files.insert(rel_path.clone(), previous.clone());
+                continue;
+            }
+
+            reread += 1;
+            let bytes = match fs::read(abs_path) {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    changed = true;
+                    continue;
+                }
+            };
+            let mut counter = TokenCounter::new();
+            counter.add_text(&String::from_utf8_lossy(&bytes));
+            let entry = CacheEntry {
+                size: metadata.len(),
+                mtime_secs,
+                mtime_nanos,
+                sha256: sha256_hex(&bytes),
+                char_count: counter.char_count,
+                word_count: counter.word_count,
+            };
+
+            if previous.is_none_or(|p| p.sha256 != entry.sha256) {
+                changed = true;
+            }
+            cache.files.insert(rel_path.clone(), entry);
+        }
+
+        CacheCheck {
+            cache,
+            changed,
+            reread,
+        }
+    }
+}
+
+/// Fingerprint of everything besides the contents of the included files that
+/// shapes the output: the options, the order of the files, and what is read
+/// from outside the selection (prepended and appended files, templates,
+/// redaction rules, .mdconcat.toml overrides, manifests, git history). The temporary copies made for
+/// --at-ref, --remote and --stdin-section are left out, as their paths differ
+/// on every run.
+pub fn fingerprint(args: &CliArgs, options: &WriteOptions, files: &[(PathBuf, PathBuf)]) -> String {
+    let mut args = args.clone();
+    args.snapshot = None;
+    args.stdin_file = None;
+    let mut text = format!("{:?}\n", args);
+
+    for (rel_path, _) in files {
+        text.push_str(&format!("{}\n", rel_path.display()));
+    }
+    // Prepended and appended files, the license notice, project summary and
+    // git log summary, and the appendices as they are rendered
+    for rendered in [
+        &options.preamble,
+        &options.epilogue,
+        &options.deps_appendix,
+        &options.assets_inventory,
+    ] {
+        text.push_str(&sha256_hex(rendered.as_bytes()));
+        text.push('\n');
+    }
+    let read_files = [
+        &args.header_template,
+        &args.document_template,
+        &args.redact_rules,
+    ];
+    for path in read_files.into_iter().flatten() {
+        let hash =
+            fs::read(path).map_or_else(|_| "missing".to_string(), |bytes| sha256_hex(&bytes));
+        text.push_str(&format!("{} {}\n", path.display(), hash));
+    }
+    // The .mdconcat.toml overrides as they apply to each file
+    let overrides = overrides::for_files(files, &options.override_roots);
+    text.push_str(&format!("{:?}\n", overrides.map_err(|e| e.to_string())));
+    if args.file_history.is_some() || args.weight_recency {
+        for root in &options.override_roots {
+            let commit = history::commit_id(root, args.revision()).unwrap_or_default();
+            text.push_str(&format!("{} {}\n", root.display(), commit));
+        }
+    }
+    sha256_hex(text.as_bytes())
+}
+
+/// Returns the lowercase hex SHA-256 digest of the given bytes
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(dir: &Path, names: &[&str]) -> Vec<(PathBuf, PathBuf)> {
+        names
+            .iter()
+            .map(|name| (PathBuf::from(name), dir.join(name)))
+            .collect()
+    }
+
+    fn fingerprint_of(argv: &[&str], files: &[(PathBuf, PathBuf)]) -> String {
+        let args = crate::parse_args(
+            ["md_concat", "out.md", "--no-config", "--extensions=rs"]
+                .iter()
+                .chain(argv),
+        )
+        .unwrap();
+        let options = WriteOptions::from_args(&args).unwrap();
+        fingerprint(&args, &options, files)
+    }
+
+    #[test]
+    fn unchanged_files_are_not_reread() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        let files = files(dir.path(), &["a.rs"]);
+
+        let first = RunCache::default().check("options", &files);
+        assert!(first.changed);
+        assert_eq!(first.reread, 1);
+
+        let second = first.cache.check("options", &files);
+        assert!(!second.changed);
+        assert_eq!(second.reread, 0);
+    }
+
+    #[test]
+    fn edited_added_and_removed_files_are_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        fs::write(dir.path().join("b.rs"), "fn b() {}\n").unwrap();
+        let cache = RunCache::default()
+            .check("options", &files(dir.path(), &["a.rs"]))
+            .cache;
+
+        assert!(
+            cache
+                .check("other options", &files(dir.path(), &["a.rs"]))
+                .changed
+        );
+        assert!(
+            cache
+                .check("options", &files(dir.path(), &["a.rs", "b.rs"]))
+                .changed
+        );
+        assert!(cache.check("options", &files(dir.path(), &[])).changed);
+
+        fs::write(dir.path().join("a.rs"), "fn a() { changed() }\n").unwrap();
+        let check = cache.check("options", &files(dir.path(), &["a.rs"]));
+        assert!(check.changed);
+        assert_eq!(check.reread, 1);
+    }
+
+    #[test]
+    fn entries_follow_the_shown_path_rather_than_the_copy_read() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        fs::write(first.path().join("a.rs"), "fn a() {}\n").unwrap();
+        fs::write(second.path().join("a.rs"), "fn a() {}\n").unwrap();
+
+        let cache = RunCache::default()
+            .check("options", &files(first.path(), &["a.rs"]))
+            .cache;
+        assert!(
+            !cache
+                .check("options", &files(second.path(), &["a.rs"]))
+                .changed
+        );
+    }
+
+    #[test]
+    fn fingerprint_covers_files_read_besides_the_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let intro = dir.path().join("intro.md");
+        fs::write(&intro, "Old intro\n").unwrap();
+        let prepend = format!("--prepend-file={}", intro.display());
+
+        let before = fingerprint_of(&[&prepend], &[]);
+        assert_eq!(before, fingerprint_of(&[&prepend], &[]));
+        fs::write(&intro, "New intro\n").unwrap();
+        assert_ne!(before, fingerprint_of(&[&prepend], &[]));
+    }
+
+    #[test]
+    fn fingerprint_covers_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("heading.tera");
+        fs::write(&template, "### {{ path }}\n").unwrap();
+        let template_arg = format!("--header-template={}", template.display());
+
+        let before = fingerprint_of(&[&template_arg], &[]);
+        fs::write(&template, "#### {{ path }}\n").unwrap();
+        assert_ne!(before, fingerprint_of(&[&template_arg], &[]));
+    }
+
+    #[test]
+    fn fingerprint_covers_directory_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("fixtures")).unwrap();
+        fs::write(dir.path().join("fixtures/a.rs"), "fn a() {}\n").unwrap();
+        let input_dirs = format!("--input-dirs={}", dir.path().display());
+        let files = files(dir.path(), &["fixtures/a.rs"]);
+
+        let before = fingerprint_of(&[&input_dirs], &files);
+        fs::write(
+            dir.path().join("fixtures").join(overrides::OVERRIDES_FILE),
+            "max_lines_per_file = 10\n",
+        )
+        .unwrap();
+        assert_ne!(before, fingerprint_of(&[&input_dirs], &files));
+    }
+
+    #[test]
+    fn fingerprint_covers_the_order_of_the_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_ne!(
+            fingerprint_of(&[], &files(dir.path(), &["a.rs", "b.rs"])),
+            fingerprint_of(&[], &files(dir.path(), &["b.rs", "a.rs"]))
+        );
+    }
+}
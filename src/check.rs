@@ -0,0 +1,54 @@
+use std::io;
+use std::path::Path;
+
+use crate::error::ConcatError;
+use crate::writer::write_document;
+use crate::{PER_FILE_STRATEGY, TokenCounter};
+
+/// Runs the configured collection without writing anything and fails if the
+/// document would exceed `max_tokens`, for use as a CI gate
+pub fn check(
+    max_tokens: usize,
+    profile: Option<&str>,
+    config: Option<&Path>,
+) -> Result<(), ConcatError> {
+    let mut argv = vec!["md_concat".to_string(), "--dry-run".to_string()];
+    if let Some(profile) = profile {
+        argv.push(format!("--profile={}", profile));
+    }
+    if let Some(config) = config {
+        argv.push(format!("--config={}", config.display()));
+    }
+    let args = crate::parse_args(argv)?;
+    if args.extensions.is_empty() && args.files_from.is_none() && args.from_manifest.is_none() {
+        return Err(ConcatError::Config(
+            "check needs extensions from a configuration file".into(),
+        ));
+    }
+
+    let (found_files, write_options) = crate::prepare(&args)?;
+    let mut token_counter = TokenCounter::new();
+    let stats = write_document(
+        &mut io::sink(),
+        &found_files,
+        &mut token_counter,
+        &write_options,
+    )?;
+    crate::print_file_table(&stats);
+    status!("\n{}", token_counter.get_token_estimates());
+
+    let tokens = PER_FILE_STRATEGY.estimate(token_counter.char_count);
+    if tokens > max_tokens {
+        return Err(ConcatError::OverBudget {
+            tokens,
+            budget: max_tokens,
+        });
+    }
+    status!(
+        "\nWithin budget: ~{} of {} tokens ({} estimate)",
+        tokens,
+        max_tokens,
+        PER_FILE_STRATEGY.name()
+    );
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::ops::Range;
+use std::path::Path;
+use tree_sitter::Node;
+
+use crate::symbols;
+
+/// Removes the comments of a Rust, Python, JavaScript, TypeScript or Go file
+/// (--strip-comments), doc comments included. Lines left holding only a
+/// comment disappear entirely. Other files, and files that fail to parse, are
+/// returned unchanged.
+pub fn strip(rel_path: &Path, text: &str) -> String {
+    let Some(tree) = symbols::syntax_tree(rel_path, text) else {
+        return text.to_string();
+    };
+    if tree.root_node().has_error() {
+        return text.to_string();
+    }
+    let mut ranges = Vec::new();
+    collect(tree.root_node(), text, &mut ranges);
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut position = 0;
+    for range in ranges {
+        if range.start >= position {
+            stripped.push_str(&text[position..range.start]);
+            position = range.end;
+        }
+    }
+    stripped.push_str(&text[position..]);
+    stripped
+}
+
+/// Byte ranges of the comments below `node`, in source order. A comment alone
+/// on its lines takes its indentation and line break with it; a trailing one
+/// takes the spaces before it.
+fn collect(node: Node, text: &str, ranges: &mut Vec<Range<usize>>) {
+    if node.kind().contains("comment") {
+        let bytes = text.as_bytes();
+        let (start, node_end) = (node.start_byte(), node.end_byte());
+        // Some grammars end line comments after their line break
+        let ends_line = text[..node_end].ends_with('\n');
+        let mut end = node_end;
+        if !ends_line {
+            while end < bytes.len() && matches!(bytes[end], b' ' | b'\t' | b'\r') {
+                end += 1;
+            }
+        }
+        let at_line_end = ends_line || end == bytes.len() || bytes[end] == b'\n';
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        if at_line_end && text[line_start..start].trim().is_empty() {
+            if !ends_line && end < bytes.len() {
+                end += 1;
+            }
+            ranges.push(line_start..end);
+        } else if at_line_end {
+            let before = text[..start].trim_end_matches([' ', '\t']).len();
+            ranges.push(before..node_end - usize::from(ends_line));
+        } else {
+            ranges.push(start..node_end);
+        }
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, text, ranges);
+    }
+}
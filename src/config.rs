@@ -0,0 +1,707 @@
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::CliArgs;
+use crate::budget::PackStrategy;
+use crate::gitignore::RootLabel;
+use crate::ordering::SortOrder;
+use crate::paths::PathStyle;
+use crate::presets::Preset;
+use crate::report::Report;
+use crate::split::SplitBy;
+use crate::submodules::SubmoduleMode;
+use crate::testcode::TestMode;
+use crate::writer::{FenceStyle, HeaderField, InputEncoding, MarkdownMode};
+
+/// Name of the project-level configuration file
+pub const CONFIG_FILE_NAME: &str = "md_concat.toml";
+
+/// Settings read from a configuration file. Every field mirrors a command-line
+/// option; values given on the command line take precedence.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input_dirs: Option<Vec<PathBuf>>,
+    pub extensions: Option<Vec<String>>,
+    pub exclude_dirs: Option<Vec<String>>,
+    /// Whether to respect .gitignore files
+    pub gitignore: Option<bool>,
+    pub additional_gitignore: Option<Vec<PathBuf>>,
+    pub sort: Option<SortOrder>,
+    pub reverse: Option<bool>,
+    pub max_tokens: Option<usize>,
+    pub pin: Option<Vec<PathBuf>>,
+    pub encoding: Option<InputEncoding>,
+    pub line_numbers: Option<bool>,
+    pub header_fields: Option<Vec<HeaderField>>,
+    pub header_template: Option<PathBuf>,
+    pub document_template: Option<PathBuf>,
+    /// Fence languages keyed by extension
+    pub lang_map: Option<BTreeMap<String, String>>,
+    pub prepend_file: Option<Vec<PathBuf>>,
+    pub append_file: Option<Vec<PathBuf>>,
+    pub cache_stable: Option<bool>,
+    pub redact_secrets: Option<bool>,
+    pub redact_rules: Option<PathBuf>,
+    pub sanitize: Option<bool>,
+    pub dedupe_content: Option<bool>,
+    pub dedupe_similar: Option<f64>,
+    pub manifest: Option<bool>,
+    pub root_label: Option<RootLabel>,
+    pub workspace_member: Option<Vec<String>>,
+    pub report: Option<Vec<Report>>,
+    pub report_depth: Option<u16>,
+    pub front_matter: Option<bool>,
+    pub deterministic: Option<bool>,
+    pub native_separators: Option<bool>,
+    pub max_files: Option<usize>,
+    pub max_input_bytes: Option<u64>,
+    pub split_tokens: Option<usize>,
+    pub tests: Option<TestMode>,
+    pub submodules: Option<SubmoduleMode>,
+    pub symbol_index: Option<bool>,
+    pub extract_symbols: Option<Vec<String>>,
+    pub public_api: Option<bool>,
+    pub md: Option<MarkdownMode>,
+    pub heading_level: Option<u8>,
+    pub fence: Option<FenceStyle>,
+    pub path_style: Option<PathStyle>,
+    pub strip_prefix: Option<String>,
+    pub only_paths: Option<Vec<String>>,
+    pub max_output_bytes: Option<usize>,
+    pub summary_footer: Option<bool>,
+    pub annotate_tokens: Option<bool>,
+    pub warn_file_tokens: Option<usize>,
+    pub pack: Option<PackStrategy>,
+    pub relevant_to: Option<String>,
+    pub semantic_query: Option<String>,
+    pub seed: Option<Vec<PathBuf>>,
+    pub expand_imports: Option<usize>,
+    pub slice_from: Option<String>,
+    pub weight_recency: Option<bool>,
+    pub git_log_summary: Option<usize>,
+    pub file_history: Option<usize>,
+    pub at_ref: Option<String>,
+    pub remote: Option<String>,
+    pub split_by: Option<SplitBy>,
+    pub directives: Option<bool>,
+    pub strip_comments: Option<bool>,
+    pub max_lines_per_file: Option<usize>,
+    pub project_summary: Option<bool>,
+    pub deps_appendix: Option<bool>,
+    pub license_notice: Option<bool>,
+    pub assets_inventory: Option<bool>,
+    pub debug_ignore: Option<bool>,
+    /// Built-in preset providing defaults underneath this file's values
+    pub preset: Option<Preset>,
+    /// Weighted glob rules used for ordering and budget pruning
+    pub priority: Vec<PriorityRule>,
+    /// Named option sets selected with --profile
+    pub profiles: BTreeMap<String, Config>,
+}
+
+/// Assigns a weight to every file whose relative path matches `glob`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PriorityRule {
+    pub glob: String,
+    pub weight: i64,
+}
+
+impl Config {
+    /// Reads and parses a configuration file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let config: Self = toml::from_str(&text)?;
+        Ok(config.relative_to(path.parent().unwrap_or(Path::new(""))))
+    }
+
+    /// Resolves the relative file and directory options of the configuration and
+    /// its profiles against `base`, the directory of the file they were read from.
+    /// `pin` and `seed` name files inside the input and stay as they are.
+    fn relative_to(mut self, base: &Path) -> Self {
+        let join_all = |paths: Vec<PathBuf>| paths.into_iter().map(|p| base.join(p)).collect();
+        self.input_dirs = self.input_dirs.map(join_all);
+        self.additional_gitignore = self.additional_gitignore.map(join_all);
+        self.prepend_file = self.prepend_file.map(join_all);
+        self.append_file = self.append_file.map(join_all);
+        self.header_template = self.header_template.map(|p| base.join(p));
+        self.document_template = self.document_template.map(|p| base.join(p));
+        self.redact_rules = self.redact_rules.map(|p| base.join(p));
+        self.profiles = self
+            .profiles
+            .into_iter()
+            .map(|(name, profile)| (name, profile.relative_to(base)))
+            .collect();
+        self
+    }
+
+    /// Loads the user configuration and then the project configuration (either
+    /// `explicit` or `./md_concat.toml`), with project values taking precedence.
+    /// Returns the merged configuration and the files it was read from.
+    pub fn load_layers(explicit: Option<&Path>) -> Result<(Self, Vec<PathBuf>), Box<dyn Error>> {
+        let mut config = Self::default();
+        let mut sources = Vec::new();
+
+        if let Some(user_path) = user_config_path().filter(|p| p.is_file()) {
+            config = config.overlay(Self::load(&user_path)?);
+            sources.push(user_path);
+        }
+
+        let project_path = match explicit {
+            Some(path) => Some(path.to_path_buf()),
+            None => Some(PathBuf::from(CONFIG_FILE_NAME)).filter(|p| p.is_file()),
+        };
+        if let Some(project_path) = project_path {
+            config = config.overlay(Self::load(&project_path)?);
+            sources.push(project_path);
+        }
+
+        Ok((config, sources))
+    }
+
+    /// Applies the named profile and the preset (from the command line, or else
+    /// from the configuration). A command-line preset overrides configuration
+    /// values, while a configured preset only fills in what the files leave unset.
+    pub fn resolve(
+        mut self,
+        profile: Option<&str>,
+        cli_preset: Option<Preset>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if let Some(name) = profile {
+            let selected = self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown profile '{}'", name))?;
+            self = self.overlay(selected);
+        }
+
+        Ok(match (cli_preset, self.preset) {
+            (Some(preset), _) => self.overlay(preset.config()),
+            (None, Some(preset)) => preset.config().overlay(self),
+            (None, None) => self,
+        })
+    }
+
+    /// Returns `self` with every value set in `other` replacing its own
+    fn overlay(self, other: Self) -> Self {
+        let mut priority = self.priority;
+        priority.extend(other.priority);
+        let mut profiles = self.profiles;
+        profiles.extend(other.profiles);
+        Self {
+            input_dirs: other.input_dirs.or(self.input_dirs),
+            extensions: other.extensions.or(self.extensions),
+            exclude_dirs: other.exclude_dirs.or(self.exclude_dirs),
+            gitignore: other.gitignore.or(self.gitignore),
+            additional_gitignore: other.additional_gitignore.or(self.additional_gitignore),
+            sort: other.sort.or(self.sort),
+            reverse: other.reverse.or(self.reverse),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            pin: other.pin.or(self.pin),
+            encoding: other.encoding.or(self.encoding),
+            line_numbers: other.line_numbers.or(self.line_numbers),
+            header_fields: other.header_fields.or(self.header_fields),
+            header_template: other.header_template.or(self.header_template),
+            document_template: other.document_template.or(self.document_template),
+            lang_map: other.lang_map.or(self.lang_map),
+            prepend_file: other.prepend_file.or(self.prepend_file),
+            append_file: other.append_file.or(self.append_file),
+            cache_stable: other.cache_stable.or(self.cache_stable),
+            redact_secrets: other.redact_secrets.or(self.redact_secrets),
+            redact_rules: other.redact_rules.or(self.redact_rules),
+            sanitize: other.sanitize.or(self.sanitize),
+            dedupe_content: other.dedupe_content.or(self.dedupe_content),
+            dedupe_similar: other.dedupe_similar.or(self.dedupe_similar),
+            manifest: other.manifest.or(self.manifest),
+            root_label: other.root_label.or(self.root_label),
+            workspace_member: other.workspace_member.or(self.workspace_member),
+            report: other.report.or(self.report),
+            report_depth: other.report_depth.or(self.report_depth),
+            front_matter: other.front_matter.or(self.front_matter),
+            deterministic: other.deterministic.or(self.deterministic),
+            native_separators: other.native_separators.or(self.native_separators),
+            max_files: other.max_files.or(self.max_files),
+            max_input_bytes: other.max_input_bytes.or(self.max_input_bytes),
+            split_tokens: other.split_tokens.or(self.split_tokens),
+            tests: other.tests.or(self.tests),
+            submodules: other.submodules.or(self.submodules),
+            symbol_index: other.symbol_index.or(self.symbol_index),
+            extract_symbols: other.extract_symbols.or(self.extract_symbols),
+            public_api: other.public_api.or(self.public_api),
+            md: other.md.or(self.md),
+            heading_level: other.heading_level.or(self.heading_level),
+            fence: other.fence.or(self.fence),
+            path_style: other.path_style.or(self.path_style),
+            strip_prefix: other.strip_prefix.or(self.strip_prefix),
+            only_paths: other.only_paths.or(self.only_paths),
+            max_output_bytes: other.max_output_bytes.or(self.max_output_bytes),
+            summary_footer: other.summary_footer.or(self.summary_footer),
+            annotate_tokens: other.annotate_tokens.or(self.annotate_tokens),
+            warn_file_tokens: other.warn_file_tokens.or(self.warn_file_tokens),
+            pack: other.pack.or(self.pack),
+            relevant_to: other.relevant_to.or(self.relevant_to),
+            semantic_query: other.semantic_query.or(self.semantic_query),
+            seed: other.seed.or(self.seed),
+            expand_imports: other.expand_imports.or(self.expand_imports),
+            slice_from: other.slice_from.or(self.slice_from),
+            weight_recency: other.weight_recency.or(self.weight_recency),
+            git_log_summary: other.git_log_summary.or(self.git_log_summary),
+            file_history: other.file_history.or(self.file_history),
+            at_ref: other.at_ref.or(self.at_ref),
+            remote: other.remote.or(self.remote),
+            split_by: other.split_by.or(self.split_by),
+            directives: other.directives.or(self.directives),
+            strip_comments: other.strip_comments.or(self.strip_comments),
+            max_lines_per_file: other.max_lines_per_file.or(self.max_lines_per_file),
+            project_summary: other.project_summary.or(self.project_summary),
+            deps_appendix: other.deps_appendix.or(self.deps_appendix),
+            license_notice: other.license_notice.or(self.license_notice),
+            assets_inventory: other.assets_inventory.or(self.assets_inventory),
+            debug_ignore: other.debug_ignore.or(self.debug_ignore),
+            preset: other.preset.or(self.preset),
+            priority,
+            profiles,
+        }
+    }
+
+    /// Fills in every option that was not given on the command line. Returns
+    /// the ids of the options it set, with the value for flags, so they can be
+    /// checked against the other options like command-line arguments.
+    pub fn apply_to(
+        self,
+        args: &mut CliArgs,
+        matches: &ArgMatches,
+    ) -> Vec<(&'static str, Option<bool>)> {
+        let mut applied = Vec::new();
+        let mut unset = |id: &'static str, flag: Option<bool>| {
+            let unset = matches.value_source(id) != Some(ValueSource::CommandLine);
+            if unset {
+                applied.push((id, flag));
+            }
+            unset
+        };
+
+        if let Some(value) = self.input_dirs.filter(|_| unset("input_dirs", None)) {
+            args.input_dirs = value;
+        }
+        if let Some(value) = self.extensions.filter(|_| unset("extensions", None)) {
+            args.extensions = value;
+        }
+        if let Some(value) = self.exclude_dirs.filter(|_| unset("exclude_dirs", None)) {
+            args.exclude_dirs = value;
+        }
+        if let Some(value) = self
+            .gitignore
+            .filter(|&value| unset("respect_gitignore", Some(value)))
+        {
+            args.respect_gitignore = value;
+        }
+        if let Some(value) = self
+            .additional_gitignore
+            .filter(|_| unset("additional_gitignore_files", None))
+        {
+            args.additional_gitignore_files = value;
+        }
+        if let Some(value) = self.sort.filter(|_| unset("sort", None)) {
+            args.sort = Some(value);
+        }
+        if let Some(value) = self.reverse.filter(|&value| unset("reverse", Some(value))) {
+            args.reverse = value;
+        }
+        if let Some(value) = self.max_tokens.filter(|_| unset("max_tokens", None)) {
+            args.max_tokens = Some(value);
+        }
+        if let Some(value) = self.pin.filter(|_| unset("pin", None)) {
+            args.pin = value;
+        }
+        if let Some(value) = self.encoding.filter(|_| unset("encoding", None)) {
+            args.encoding = value;
+        }
+        if let Some(value) = self
+            .line_numbers
+            .filter(|&value| unset("line_numbers", Some(value)))
+        {
+            args.line_numbers = value;
+        }
+        if let Some(value) = self.header_fields.filter(|_| unset("header_fields", None)) {
+            args.header_fields = value;
+        }
+        if let Some(value) = self
+            .header_template
+            .filter(|_| unset("header_template", None))
+        {
+            args.header_template = Some(value);
+        }
+        if let Some(value) = self
+            .document_template
+            .filter(|_| unset("document_template", None))
+        {
+            args.document_template = Some(value);
+        }
+        if let Some(value) = self.lang_map.filter(|_| unset("lang_map", None)) {
+            args.lang_map = value.into_iter().collect();
+        }
+        if let Some(value) = self.prepend_file.filter(|_| unset("prepend_file", None)) {
+            args.prepend_file = value;
+        }
+        if let Some(value) = self.append_file.filter(|_| unset("append_file", None)) {
+            args.append_file = value;
+        }
+        if let Some(value) = self
+            .cache_stable
+            .filter(|&value| unset("cache_stable", Some(value)))
+        {
+            args.cache_stable = value;
+        }
+        if let Some(value) = self
+            .redact_secrets
+            .filter(|&value| unset("redact_secrets", Some(value)))
+        {
+            args.redact_secrets = value;
+        }
+        if let Some(value) = self.redact_rules.filter(|_| unset("redact_rules", None)) {
+            args.redact_rules = Some(value);
+        }
+        if let Some(value) = self
+            .sanitize
+            .filter(|&value| unset("sanitize", Some(value)))
+        {
+            args.sanitize = value;
+        }
+        if let Some(value) = self
+            .dedupe_content
+            .filter(|&value| unset("dedupe_content", Some(value)))
+        {
+            args.dedupe_content = value;
+        }
+        if let Some(value) = self
+            .dedupe_similar
+            .filter(|_| unset("dedupe_similar", None))
+        {
+            args.dedupe_similar = Some(value);
+        }
+        if let Some(value) = self
+            .manifest
+            .filter(|&value| unset("manifest", Some(value)))
+        {
+            args.manifest = value;
+        }
+        if let Some(value) = self.root_label.filter(|_| unset("root_label", None)) {
+            args.root_label = value;
+        }
+        if let Some(value) = self
+            .workspace_member
+            .filter(|_| unset("workspace_member", None))
+        {
+            args.workspace_member = value;
+        }
+        if let Some(value) = self.report.filter(|_| unset("report", None)) {
+            args.report = value;
+        }
+        if let Some(value) = self.report_depth.filter(|_| unset("report_depth", None)) {
+            args.report_depth = value;
+        }
+        if let Some(value) = self
+            .front_matter
+            .filter(|&value| unset("front_matter", Some(value)))
+        {
+            args.front_matter = value;
+        }
+        if let Some(value) = self
+            .deterministic
+            .filter(|&value| unset("deterministic", Some(value)))
+        {
+            args.deterministic = value;
+        }
+        if let Some(value) = self
+            .native_separators
+            .filter(|&value| unset("native_separators", Some(value)))
+        {
+            args.native_separators = value;
+        }
+        if let Some(value) = self.max_files.filter(|_| unset("max_files", None)) {
+            args.max_files = value;
+        }
+        if let Some(value) = self
+            .max_input_bytes
+            .filter(|_| unset("max_input_bytes", None))
+        {
+            args.max_input_bytes = value;
+        }
+        if let Some(value) = self.split_tokens.filter(|_| unset("split_tokens", None)) {
+            args.split_tokens = Some(value);
+        }
+        if let Some(value) = self.tests.filter(|_| unset("tests", None)) {
+            args.tests = Some(value);
+        }
+        if let Some(value) = self.submodules.filter(|_| unset("submodules", None)) {
+            args.submodules = value;
+        }
+        if let Some(value) = self
+            .symbol_index
+            .filter(|&value| unset("symbol_index", Some(value)))
+        {
+            args.symbol_index = value;
+        }
+        if let Some(value) = self
+            .extract_symbols
+            .filter(|_| unset("extract_symbols", None))
+        {
+            args.extract_symbols = value;
+        }
+        if let Some(value) = self
+            .public_api
+            .filter(|&value| unset("public_api", Some(value)))
+        {
+            args.public_api = value;
+        }
+        if let Some(value) = self.md.filter(|_| unset("md", None)) {
+            args.md = value;
+        }
+        if let Some(value) = self.heading_level.filter(|_| unset("heading_level", None)) {
+            args.heading_level = value;
+        }
+        if let Some(value) = self.fence.filter(|_| unset("fence", None)) {
+            args.fence = value;
+        }
+        if let Some(value) = self.path_style.filter(|_| unset("path_style", None)) {
+            args.path_style = value;
+        }
+        if let Some(value) = self.strip_prefix.filter(|_| unset("strip_prefix", None)) {
+            args.strip_prefix = Some(value);
+        }
+        if let Some(value) = self.only_paths.filter(|_| unset("only_paths", None)) {
+            args.only_paths = value;
+        }
+        if let Some(value) = self
+            .max_output_bytes
+            .filter(|_| unset("max_output_bytes", None))
+        {
+            args.max_output_bytes = Some(value);
+        }
+        if let Some(value) = self
+            .summary_footer
+            .filter(|&value| unset("summary_footer", Some(value)))
+        {
+            args.summary_footer = value;
+        }
+        if let Some(value) = self
+            .annotate_tokens
+            .filter(|&value| unset("annotate_tokens", Some(value)))
+        {
+            args.annotate_tokens = value;
+        }
+        if let Some(value) = self
+            .warn_file_tokens
+            .filter(|_| unset("warn_file_tokens", None))
+        {
+            args.warn_file_tokens = Some(value);
+        }
+        if let Some(value) = self.pack.filter(|_| unset("pack", None)) {
+            args.pack = value;
+        }
+        if let Some(value) = self.relevant_to.filter(|_| unset("relevant_to", None)) {
+            args.relevant_to = Some(value);
+        }
+        if let Some(value) = self
+            .semantic_query
+            .filter(|_| unset("semantic_query", None))
+        {
+            args.semantic_query = Some(value);
+        }
+        if let Some(value) = self.seed.filter(|_| unset("seed", None)) {
+            args.seed = value;
+        }
+        if let Some(value) = self
+            .expand_imports
+            .filter(|_| unset("expand_imports", None))
+        {
+            args.expand_imports = Some(value);
+        }
+        if let Some(value) = self.slice_from.filter(|_| unset("slice_from", None)) {
+            args.slice_from = Some(value);
+        }
+        if let Some(value) = self
+            .weight_recency
+            .filter(|&value| unset("weight_recency", Some(value)))
+        {
+            args.weight_recency = value;
+        }
+        if let Some(value) = self
+            .git_log_summary
+            .filter(|_| unset("git_log_summary", None))
+        {
+            args.git_log_summary = Some(value);
+        }
+        if let Some(value) = self.file_history.filter(|_| unset("file_history", None)) {
+            args.file_history = Some(value);
+        }
+        if let Some(value) = self.at_ref.filter(|_| unset("at_ref", None)) {
+            args.at_ref = Some(value);
+        }
+        if let Some(value) = self.remote.filter(|_| unset("remote", None)) {
+            args.remote = Some(value);
+        }
+        if let Some(value) = self.split_by.filter(|_| unset("split_by", None)) {
+            args.split_by = Some(value);
+        }
+        if let Some(value) = self
+            .directives
+            .filter(|&value| unset("directives", Some(value)))
+        {
+            args.directives = value;
+        }
+        if let Some(value) = self
+            .strip_comments
+            .filter(|&value| unset("strip_comments", Some(value)))
+        {
+            args.strip_comments = value;
+        }
+        if let Some(value) = self
+            .max_lines_per_file
+            .filter(|_| unset("max_lines_per_file", None))
+        {
+            args.max_lines_per_file = Some(value);
+        }
+        if let Some(value) = self
+            .project_summary
+            .filter(|&value| unset("project_summary", Some(value)))
+        {
+            args.project_summary = value;
+        }
+        if let Some(value) = self
+            .deps_appendix
+            .filter(|&value| unset("deps_appendix", Some(value)))
+        {
+            args.deps_appendix = value;
+        }
+        if let Some(value) = self
+            .license_notice
+            .filter(|&value| unset("license_notice", Some(value)))
+        {
+            args.license_notice = value;
+        }
+        if let Some(value) = self
+            .assets_inventory
+            .filter(|&value| unset("assets_inventory", Some(value)))
+        {
+            args.assets_inventory = value;
+        }
+        if let Some(value) = self
+            .debug_ignore
+            .filter(|&value| unset("debug_ignore", Some(value)))
+        {
+            args.debug_ignore = value;
+        }
+        args.priority = self.priority;
+        applied
+    }
+}
+
+/// Location of the per-user configuration file
+fn user_config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("md_concat").join("config.toml"))
+}
+
+/// Compiled priority rules
+#[derive(Default)]
+pub struct Priorities {
+    rules: Vec<(GlobMatcher, i64)>,
+}
+
+impl Priorities {
+    pub fn new(rules: &[PriorityRule]) -> Result<Self, Box<dyn Error>> {
+        let rules = rules
+            .iter()
+            .map(|rule| Ok((Glob::new(&rule.glob)?.compile_matcher(), rule.weight)))
+            .collect::<Result<_, globset::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Weight of a relative path: the highest weight among matching rules, or 0
+    pub fn weight(&self, rel_path: &Path) -> i64 {
+        self.rules
+            .iter()
+            .filter(|(matcher, _)| matcher.is_match(rel_path))
+            .map(|(_, weight)| *weight)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_paths_against_the_config_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &path,
+            "input_dirs = [\"src\"]\npin = [\"lib.rs\"]\nredact_rules = \"/etc/rules.toml\"\n\n\
+             [profiles.docs]\nprepend_file = [\"intro.md\"]\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.input_dirs, Some(vec![dir.path().join("src")]));
+        assert_eq!(config.pin, Some(vec![PathBuf::from("lib.rs")]));
+        assert_eq!(config.redact_rules, Some(PathBuf::from("/etc/rules.toml")));
+        assert_eq!(
+            config.profiles["docs"].prepend_file,
+            Some(vec![dir.path().join("intro.md")])
+        );
+    }
+
+    #[test]
+    fn checks_config_values_against_command_line_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "extensions = [\"rs\"]\nsplit_tokens = 1000\n").unwrap();
+        let config = format!("--config={}", path.display());
+
+        assert!(crate::parse_args(["md_concat", "out.md", &config]).is_ok());
+        let err = crate::parse_args(["md_concat", "out.md", &config, "--cache"]).unwrap_err();
+        assert!(err.to_string().contains("--split-tokens"), "{}", err);
+    }
+
+    #[test]
+    fn checks_requirements_of_config_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "extensions = [\"rs\"]\nexpand_imports = 2\n").unwrap();
+        let config = format!("--config={}", path.display());
+
+        assert!(crate::parse_args(["md_concat", "out.md", &config]).is_err());
+        assert!(crate::parse_args(["md_concat", "out.md", &config, "--seed=lib.rs"]).is_ok());
+    }
+
+    #[test]
+    fn ignores_disabled_config_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "extensions = [\"rs\"]\nsummary_footer = false\n").unwrap();
+        let config = format!("--config={}", path.display());
+
+        let args = ["md_concat", "out.md", &config, "--split-tokens=1000"];
+        assert!(crate::parse_args(args).is_ok());
+    }
+}
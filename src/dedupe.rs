@@ -0,0 +1,119 @@
+use similar::TextDiff;
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Number of hash functions in a MinHash signature
+const SIGNATURE_LEN: usize = 64;
+/// Words per shingle
+const SHINGLE_WORDS: usize = 4;
+
+/// A file kept in full that later files may be collapsed against
+struct Canonical {
+    rel_path: PathBuf,
+    signature: [u64; SIGNATURE_LEN],
+    text: String,
+}
+
+/// A later file found to be nearly identical to an earlier one
+pub struct NearDuplicate {
+    pub original: PathBuf,
+    /// Estimated Jaccard similarity of the two files' shingles
+    pub similarity: f64,
+    /// Unified diff from the original to this file
+    pub diff: String,
+}
+
+/// Detects near-duplicate files with MinHash signatures over word shingles
+pub struct SimilarityIndex {
+    threshold: f64,
+    canonical: Vec<Canonical>,
+}
+
+impl SimilarityIndex {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            canonical: Vec::new(),
+        }
+    }
+
+    /// Compares a file against the files seen so far. Returns the closest
+    /// match at or above the threshold, provided the diff is smaller than the
+    /// file itself; otherwise remembers the file as a canonical copy.
+    pub fn check(&mut self, rel_path: &Path, text: &str) -> Option<NearDuplicate> {
+        let signature = signature(text);
+        let best = self
+            .canonical
+            .iter()
+            .map(|canonical| {
+                (
+                    canonical,
+                    estimate_similarity(&canonical.signature, &signature),
+                )
+            })
+            .filter(|(_, similarity)| *similarity >= self.threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((canonical, similarity)) = best {
+            let original = canonical.rel_path.display().to_string();
+            let diff = TextDiff::from_lines(canonical.text.as_str(), text)
+                .unified_diff()
+                .context_radius(2)
+                .header(&original, &rel_path.display().to_string())
+                .to_string();
+            if diff.len() < text.len() {
+                return Some(NearDuplicate {
+                    original: canonical.rel_path.clone(),
+                    similarity,
+                    diff,
+                });
+            }
+        }
+
+        self.canonical.push(Canonical {
+            rel_path: rel_path.to_path_buf(),
+            signature,
+            text: text.to_string(),
+        });
+        None
+    }
+}
+
+/// Parses a similarity threshold between 0 and 1
+pub fn parse_threshold(text: &str) -> Result<f64, String> {
+    let value: f64 = text
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", text))?;
+    if value > 0.0 && value <= 1.0 {
+        Ok(value)
+    } else {
+        Err("must be greater than 0 and at most 1".to_string())
+    }
+}
+
+fn signature(text: &str) -> [u64; SIGNATURE_LEN] {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let shingles: HashSet<&[&str]> = if words.len() < SHINGLE_WORDS {
+        HashSet::from([words.as_slice()])
+    } else {
+        words.windows(SHINGLE_WORDS).collect()
+    };
+
+    let mut signature = [u64::MAX; SIGNATURE_LEN];
+    for shingle in shingles {
+        for (seed, slot) in signature.iter_mut().enumerate() {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            shingle.hash(&mut hasher);
+            *slot = (*slot).min(hasher.finish());
+        }
+    }
+    signature
+}
+
+/// The fraction of equal signature slots estimates the Jaccard similarity
+fn estimate_similarity(a: &[u64; SIGNATURE_LEN], b: &[u64; SIGNATURE_LEN]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / SIGNATURE_LEN as f64
+}
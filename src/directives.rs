@@ -0,0 +1,45 @@
+/// Comment markers a directive may follow
+const COMMENT_MARKERS: [&str; 7] = ["//", "#", "/*", "--", "<!--", ";", "%"];
+
+/// The directive named in a comment line, such as `ignore-file` for
+/// `// md_concat:ignore-file` or `<!-- md_concat:begin-ignore -->`
+fn directive(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let rest = COMMENT_MARKERS
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))?;
+    let rest = rest
+        .trim_start_matches(['/', '*', '!', '#', '-', ';', '%'])
+        .trim_start();
+    let name = rest.strip_prefix("md_concat:")?;
+    name.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .next()
+}
+
+/// Whether a file asks to be left out with a `md_concat:ignore-file` comment
+pub fn ignores_file(text: &str) -> bool {
+    text.lines()
+        .any(|line| directive(line) == Some("ignore-file"))
+}
+
+/// Removes the regions between `md_concat:begin-ignore` and `md_concat:end-ignore`
+/// comments, including the comment lines. A region left open runs to the end
+/// of the file. Returns the remaining text and the 1-based numbers of the lines removed.
+pub fn strip_ignored(text: &str) -> (String, Vec<usize>) {
+    let mut kept = String::with_capacity(text.len());
+    let mut removed = Vec::new();
+    let mut ignoring = false;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        match directive(line) {
+            Some("begin-ignore") => ignoring = true,
+            Some("end-ignore") if ignoring => ignoring = false,
+            _ if !ignoring => {
+                kept.push_str(line);
+                continue;
+            }
+            _ => {}
+        }
+        removed.push(index + 1);
+    }
+    (kept, removed)
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// The body of one `## path` section of a generated document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    /// The file content from the fenced block
+    Content(String),
+    /// A --dedupe-content stub pointing at an earlier section
+    Identical(PathBuf),
+    /// A --dedupe-similar unified diff against an earlier section
+    Diff { original: PathBuf, diff: String },
+    /// A placeholder for a file that could not be read
+    Unreadable(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub path: PathBuf,
+    pub body: Body,
+}
+
+/// Parses a document in the default layout (`## path` headings followed by a
+/// fenced code block) back into its sections. Documents written with another
+/// `--heading-level` or `--fence` are recognised too.
+pub fn parse(text: &str) -> Vec<Section> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let prefix = format!("{} ", "#".repeat(section_level(&lines)));
+    let mut sections = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some(path) = lines[i].trim_end().strip_prefix(prefix.as_str()) else {
+            i += 1;
+            continue;
+        };
+        let path = PathBuf::from(heading_path(path.trim()));
+        i += 1;
+
+        // Skip metadata up to the fence, noting deduplication stubs on the way
+        let mut stub = None;
+        while i < lines.len() && fence_len(lines[i]).is_none() {
+            let line = lines[i].trim_end();
+            if line.starts_with(prefix.as_str()) {
+                break;
+            }
+            if let Some(original) = quoted_path(line, "Identical to `") {
+                stub = Some(Body::Identical(original));
+            } else if let Some(original) = quoted_path(line, "Nearly identical to `") {
+                stub = Some(Body::Diff {
+                    original,
+                    diff: String::new(),
+                });
+            }
+            i += 1;
+        }
+
+        let stub = match stub {
+            Some(Body::Identical(original)) => {
+                sections.push(Section {
+                    path,
+                    body: Body::Identical(original),
+                });
+                continue;
+            }
+            other => other,
+        };
+        let Some(fence) = lines.get(i).and_then(|line| fence_len(line)) else {
+            continue;
+        };
+        i += 1;
+
+        let mut content = String::new();
+        while i < lines.len() && !is_closing_fence(lines[i], fence) {
+            content.push_str(lines[i]);
+            i += 1;
+        }
+        i += 1;
+
+        let body = match stub {
+            Some(Body::Diff { original, .. }) => Body::Diff {
+                original,
+                diff: content,
+            },
+            _ => match content.strip_prefix("\nError: ") {
+                Some(reason) if !reason.trim_end().contains('\n') => {
+                    Body::Unreadable(reason.trim_end().to_string())
+                }
+                _ => Body::Content(content),
+            },
+        };
+        sections.push(Section { path, body });
+    }
+
+    sections
+}
+
+/// Resolves every section to its full content: stubs take the content of the
+/// section they reference and diffs are applied to it. Sections that cannot be
+/// resolved are returned as errors alongside their path.
+pub fn resolve(sections: &[Section]) -> Vec<(PathBuf, Result<String, String>)> {
+    let mut contents: HashMap<&Path, String> = HashMap::new();
+    let mut resolved = Vec::with_capacity(sections.len());
+
+    for section in sections {
+        let content = match &section.body {
+            Body::Content(content) => Ok(content.clone()),
+            Body::Identical(original) => contents
+                .get(original.as_path())
+                .cloned()
+                .ok_or_else(|| format!("refers to missing section {}", original.display())),
+            Body::Diff { original, diff } => contents
+                .get(original.as_path())
+                .ok_or_else(|| format!("refers to missing section {}", original.display()))
+                .and_then(|base| {
+                    apply_unified_diff(base, diff).ok_or_else(|| {
+                        format!("diff against {} does not apply", original.display())
+                    })
+                }),
+            Body::Unreadable(reason) => Err(format!("was unreadable when generated: {}", reason)),
+        };
+        if let Ok(ref content) = content {
+            contents.insert(&section.path, content.clone());
+        }
+        resolved.push((section.path.clone(), content));
+    }
+
+    resolved
+}
+
+/// Whether a section's content matches a file's content. The writer adds a
+/// final newline to files lacking one, which is not a difference.
+pub fn same_content(file: &str, section: &str) -> bool {
+    file == section || section.strip_suffix('\n') == Some(file) && !file.ends_with('\n')
+}
+
+/// Returns the path if it stays inside the directory it is joined to
+pub fn safe_relative(path: &Path) -> Option<&Path> {
+    let safe = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    (safe && !path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Heading level of the file sections: that of the last heading followed by a
+/// code fence before any other heading (2 if there is none). The last one, as
+/// recaps and prepended text come before the files.
+fn section_level(lines: &[&str]) -> usize {
+    let heading_level = |line: &str| {
+        let hashes = line.chars().take_while(|&c| c == '#').count();
+        ((1..=6).contains(&hashes) && line[hashes..].starts_with(' ')).then_some(hashes)
+    };
+    for (i, line) in lines.iter().enumerate().rev() {
+        let Some(level) = heading_level(line) else {
+            continue;
+        };
+        let next = lines[i + 1..]
+            .iter()
+            .find(|line| heading_level(line).is_some() || fence_len(line).is_some());
+        if next.is_some_and(|line| fence_len(line).is_some()) {
+            return level;
+        }
+    }
+    2
+}
+
+/// Character and length of the code fence the line opens, if it opens one
+fn fence_len(line: &str) -> Option<(char, usize)> {
+    let c = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let count = line.chars().take_while(|&other| other == c).count();
+    (count >= 3).then_some((c, count))
+}
+
+/// A closing fence has at least as many of the opening fence's characters and nothing else
+fn is_closing_fence(line: &str, (c, open_len): (char, usize)) -> bool {
+    let line = line.trim_end();
+    line.len() >= open_len && line.chars().all(|other| other == c)
+}
+
+/// Heading text without the `(≈1,245 tokens)` note of --annotate-tokens or
+/// the `(⚠ ≈12,345 tokens)` mark of --warn-file-tokens
+fn heading_path(heading: &str) -> &str {
+    match heading.rsplit_once(" (") {
+        Some((path, note))
+            if (note.starts_with('≈') || note.starts_with("⚠ ≈")) && note.ends_with(" tokens)") =>
+        {
+            path
+        }
+        _ => heading,
+    }
+}
+
+fn quoted_path(line: &str, prefix: &str) -> Option<PathBuf> {
+    let rest = line.strip_prefix(prefix)?;
+    rest.split_once('`').map(|(path, _)| PathBuf::from(path))
+}
+
+/// Applies a unified diff to the text it was generated from
+fn apply_unified_diff(original: &str, diff: &str) -> Option<String> {
+    let old_lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(original.len());
+    let mut next_old = 0;
+    // Whether the previous diff line ended up in the output
+    let mut last_kept = false;
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("--- ") || line.starts_with("+++ ") {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("@@ -") {
+            // "@@ -start,len +start,len @@": copy untouched lines up to the hunk
+            let start: usize = header.split([',', ' ']).next()?.parse().ok()?;
+            let hunk_start = start.saturating_sub(1).max(next_old);
+            for old in old_lines.get(next_old..hunk_start)? {
+                output.push_str(old);
+            }
+            next_old = hunk_start;
+        } else if line.starts_with('\\') {
+            // "\ No newline at end of file" applies to the line before it
+            if last_kept && output.ends_with('\n') {
+                output.pop();
+            }
+            continue;
+        } else if let Some(added) = line.strip_prefix('+') {
+            output.push_str(added);
+        } else if let Some(removed) = line.strip_prefix('-') {
+            if old_lines.get(next_old)?.trim_end_matches('\n') != removed.trim_end_matches('\n') {
+                return None;
+            }
+            next_old += 1;
+        } else if let Some(context) = line.strip_prefix(' ') {
+            let old = old_lines.get(next_old)?;
+            if old.trim_end_matches('\n') != context.trim_end_matches('\n') {
+                return None;
+            }
+            output.push_str(old);
+            next_old += 1;
+        }
+        last_kept = !line.starts_with('-');
+    }
+
+    for old in old_lines.get(next_old..)? {
+        output.push_str(old);
+    }
+    Some(output)
+}
@@ -0,0 +1,103 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use thiserror::Error;
+
+/// Errors that end a run, each mapped to a distinct process exit code so that
+/// scripts wrapping md_concat can react without parsing stderr
+#[derive(Debug, Error)]
+pub enum ConcatError {
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Input directory '{path}' is not accessible: {source}")]
+    InputDir { path: PathBuf, source: io::Error },
+
+    #[error("Cannot read '{path}': {source}")]
+    InputFile { path: PathBuf, source: io::Error },
+
+    #[error("No files matched the selection")]
+    NoFilesMatched,
+
+    #[error(
+        "Output file '{0}' already exists (use --force to overwrite or --backup to keep a copy)"
+    )]
+    OutputExists(PathBuf),
+
+    #[error("Cannot write output '{path}': {source}")]
+    OutputNotWritable { path: PathBuf, source: io::Error },
+
+    #[error("Token budget of {budget} exceeded: dropped {dropped} file(s) to fit")]
+    BudgetExceeded { budget: usize, dropped: usize },
+
+    #[error("Document of ~{tokens} tokens exceeds the budget of {budget}")]
+    OverBudget { tokens: usize, budget: usize },
+
+    #[error("Output of {bytes} bytes exceeds --max-output-bytes {limit}")]
+    OutputTooLarge { bytes: usize, limit: usize },
+
+    #[error("{0} file(s) could not be read")]
+    UnreadableFiles(usize),
+
+    #[error("Cannot read {path}: {reason}")]
+    Unreadable { path: PathBuf, reason: String },
+
+    #[error("{0} file(s) differ from the snapshot")]
+    Drift(usize),
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error(
+        "{count} files selected, more than --max-files {limit}; without a terminal to confirm, pass --yes or raise --max-files"
+    )]
+    TooManyFiles { count: usize, limit: usize },
+
+    #[error(
+        "{bytes} bytes selected, more than --max-input-bytes {limit}; without a terminal to confirm, pass --yes or raise --max-input-bytes"
+    )]
+    SelectionTooLarge { bytes: u64, limit: u64 },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl ConcatError {
+    /// Process exit code for this error. 2 is left to command-line usage errors.
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            ConcatError::Config(_)
+            | ConcatError::InputDir { .. }
+            | ConcatError::InputFile { .. }
+            | ConcatError::Cancelled
+            | ConcatError::TooManyFiles { .. }
+            | ConcatError::SelectionTooLarge { .. }
+            | ConcatError::Io(_) => 1,
+            ConcatError::NoFilesMatched => 3,
+            ConcatError::OutputExists(_) | ConcatError::OutputNotWritable { .. } => 4,
+            ConcatError::BudgetExceeded { .. }
+            | ConcatError::OverBudget { .. }
+            | ConcatError::OutputTooLarge { .. } => 5,
+            ConcatError::UnreadableFiles(_) | ConcatError::Unreadable { .. } => 6,
+            ConcatError::Drift(_) => 7,
+        })
+    }
+}
+
+impl ConcatError {
+    /// Whether a long-running mode such as --watch should keep going after this error
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            ConcatError::NoFilesMatched
+                | ConcatError::BudgetExceeded { .. }
+                | ConcatError::UnreadableFiles(_)
+        )
+    }
+}
+
+/// Shorthand for attaching the output path to a write failure
+pub fn output_error(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> ConcatError {
+    let path = path.into();
+    move |source| ConcatError::OutputNotWritable { path, source }
+}
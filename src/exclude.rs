@@ -0,0 +1,103 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConcatError;
+use crate::paths;
+
+/// Directories left out of the walk, given as `--exclude-dirs` entries. A plain
+/// name (`target`) matches a directory of that name anywhere; a name glob
+/// (`build-*`) matches directory names; an entry with a `/` (`src/generated`,
+/// `**/generated`) is a glob matched against the path from the input directory.
+#[derive(Debug, Default)]
+pub struct ExcludeDirs {
+    names: HashSet<String>,
+    name_globs: GlobSet,
+    path_globs: GlobSet,
+}
+
+impl ExcludeDirs {
+    pub fn new(entries: &[String]) -> Result<Self, ConcatError> {
+        let mut names = HashSet::new();
+        let mut name_globs = GlobSetBuilder::new();
+        let mut path_globs = GlobSetBuilder::new();
+        for entry in entries {
+            let entry = entry.trim_start_matches("./").trim_end_matches('/');
+            if entry.is_empty() {
+                continue;
+            }
+            if entry.contains('/') {
+                let glob = GlobBuilder::new(entry)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(invalid)?;
+                path_globs.add(glob);
+            } else if entry.contains(['*', '?', '[', '{']) {
+                name_globs.add(Glob::new(entry).map_err(invalid)?);
+            } else {
+                names.insert(entry.to_string());
+            }
+        }
+        Ok(Self {
+            names,
+            name_globs: name_globs.build().map_err(invalid)?,
+            path_globs: path_globs.build().map_err(invalid)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.name_globs.is_empty() && self.path_globs.is_empty()
+    }
+
+    /// Whether the directory at `rel_dir` (relative to its input directory) is excluded
+    pub fn matches(&self, rel_dir: &Path) -> bool {
+        let name = rel_dir.file_name().unwrap_or_default();
+        self.names.contains(name.to_string_lossy().as_ref())
+            || self.name_globs.is_match(name)
+            || self.path_globs.is_match(paths::portable(rel_dir))
+    }
+}
+
+/// Sub-paths of each input directory the walk is restricted to (`--only-paths`).
+/// Directories leading to them are walked without collecting their files.
+#[derive(Debug, Default)]
+pub struct OnlyPaths {
+    paths: Vec<PathBuf>,
+}
+
+impl OnlyPaths {
+    pub fn new(entries: &[String]) -> Self {
+        let paths = entries
+            .iter()
+            .map(|entry| entry.trim_start_matches("./").trim_end_matches('/'))
+            .filter(|entry| !entry.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        Self { paths }
+    }
+
+    /// The sub-paths, relative to the input directory; empty without --only-paths
+    #[cfg(feature = "object-store")]
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Whether the walk has to enter the directory at `rel_dir`: it is one of
+    /// the sub-paths, lies below one, or leads to one
+    pub fn enters(&self, rel_dir: &Path) -> bool {
+        self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|path| rel_dir.starts_with(path) || path.starts_with(rel_dir))
+    }
+
+    /// Whether the file at `rel_path` is one of the sub-paths or lies below one
+    pub fn contains(&self, rel_path: &Path) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|path| rel_path.starts_with(path))
+    }
+}
+
+fn invalid(e: globset::Error) -> ConcatError {
+    ConcatError::Config(format!("invalid --exclude-dirs entry: {}", e))
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use crate::config::Priorities;
+use crate::error::ConcatError;
+use crate::exclude::{ExcludeDirs, OnlyPaths};
+use crate::gitignore::GitignoreManager;
+use crate::submodules::Submodules;
+use crate::writer::WriteOptions;
+use crate::{CliArgs, QUIET};
+
+/// Prints why a path is or isn't part of the output of a run with the given
+/// options: the input directory it falls under, each directory rule on the way
+/// down, the extension and gitignore checks, and finally what the full
+/// selection decides
+pub fn explain(path: &Path, options: &[String]) -> Result<(), ConcatError> {
+    let argv = ["md_concat", "--dry-run"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(options.iter().cloned());
+    let args = crate::parse_args(argv)?;
+    if args.extensions.is_empty() && args.files_from.is_none() && args.from_manifest.is_none() {
+        return Err(ConcatError::Config(
+            "explain needs extensions (after `--` or from a configuration file)".into(),
+        ));
+    }
+    let args = &args;
+
+    let canonical = fs::canonicalize(path).map_err(|source| ConcatError::InputFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    status!("{}", path.display());
+
+    if let Some(ref list) = args.files_from {
+        status!("  files are listed in {} (--files-from)", list.display());
+        return conclude(args, &canonical, None);
+    }
+    if let Some(ref manifest) = args.from_manifest {
+        status!(
+            "  files are recorded in {} (--from-manifest)",
+            manifest.display()
+        );
+        return conclude(args, &canonical, None);
+    }
+
+    let input_dirs: Vec<PathBuf> = args
+        .input_dirs
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .collect();
+    let Some(root) = input_dirs
+        .iter()
+        .filter(|dir| canonical.starts_with(dir))
+        .max_by_key(|dir| dir.components().count())
+    else {
+        return excluded("outside every input directory (--input-dirs)");
+    };
+    status!("  input directory {}", root.display());
+
+    let gitignore = if args.respect_gitignore {
+        GitignoreManager::discover_and_load(&input_dirs, &args.additional_gitignore_files)
+            .unwrap_or_default()
+    } else {
+        status!("  gitignore files are not consulted (--no-gitignore)");
+        GitignoreManager::new()
+    };
+    let exclude_dirs = ExcludeDirs::new(&args.exclude_dirs)?;
+    let only_paths = OnlyPaths::new(&args.only_paths);
+    let submodules = Submodules::discover(&input_dirs, args.submodules);
+
+    // Directories between the input directory and the file, outermost first
+    let mut dirs: Vec<&Path> = canonical
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root) && dir != root)
+        .collect();
+    dirs.reverse();
+    for dir in dirs {
+        let shown = dir.strip_prefix(root).unwrap_or(dir).display();
+        if exclude_dirs.matches(dir.strip_prefix(root).unwrap_or(dir)) {
+            return excluded(&format!(
+                "directory {}/ is excluded (--exclude-dirs)",
+                shown
+            ));
+        }
+        if !only_paths.enters(dir.strip_prefix(root).unwrap_or(dir)) {
+            return excluded(&format!("directory {}/ is outside --only-paths", shown));
+        }
+        if submodules.skips_dir(dir) {
+            return excluded(&format!(
+                "directory {}/ is a git submodule (--submodules)",
+                shown
+            ));
+        }
+        if let Some(rule) = gitignore.ignoring_rule(dir, dir) {
+            return excluded(&format!("directory {}/ is ignored by {}", shown, rule));
+        }
+        status!("  directory {}/: walked", shown);
+    }
+
+    let extension = canonical
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+    if !args.extensions.iter().any(|wanted| wanted == extension) {
+        return excluded(&format!(
+            "extension \"{}\" is not in --extensions ({})",
+            extension,
+            args.extensions.join(",")
+        ));
+    }
+    status!("  extension \"{}\": listed in --extensions", extension);
+
+    let rel_path = canonical.strip_prefix(root).unwrap_or(&canonical);
+    if !only_paths.contains(rel_path) {
+        return excluded("outside --only-paths");
+    }
+    if let Some(rule) = gitignore.ignoring_rule(&canonical, rel_path) {
+        return excluded(&format!("ignored by {}", rule));
+    }
+    if args.respect_gitignore {
+        status!("  gitignore: no rule ignores it");
+    }
+
+    conclude(args, &canonical, Some(rel_path))
+}
+
+/// Runs the whole selection to account for the filters applied after the walk
+/// (workspace members, tests, symbols, budget, ...)
+fn conclude(args: &CliArgs, canonical: &Path, rel_path: Option<&Path>) -> Result<(), ConcatError> {
+    let write_options = WriteOptions::from_args(args)?;
+    let priorities = Priorities::new(&args.priority)
+        .map_err(|e| ConcatError::Config(format!("invalid priority glob: {}", e)))?;
+    let quiet = QUIET.swap(true, Ordering::Relaxed);
+    let selection = crate::select_files(args, &write_options, &priorities);
+    QUIET.store(quiet, Ordering::Relaxed);
+
+    let selection = match selection {
+        Ok(selection) => selection,
+        Err(ConcatError::NoFilesMatched) => return excluded("no files are selected at all"),
+        Err(e) => return Err(e),
+    };
+    if let Some(position) = selection
+        .files
+        .iter()
+        .position(|(_, abs_path)| abs_path == canonical)
+    {
+        status!(
+            "=> included as {} (file {} of {})",
+            selection.files[position].0.display(),
+            position + 1,
+            selection.files.len()
+        );
+        return Ok(());
+    }
+    let reason = selection
+        .skipped
+        .iter()
+        .find(|skipped| rel_path.is_some_and(|rel| skipped.path.ends_with(rel)))
+        .map(|skipped| skipped.reason.clone());
+    match reason {
+        Some(reason) => excluded(&reason),
+        None => excluded("not selected"),
+    }
+}
+
+fn excluded(reason: &str) -> Result<(), ConcatError> {
+    status!("=> excluded: {}", reason);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Copies everything piped on stdin to a temporary file, which is written as
+/// its own section (--stdin-section) and removed when dropped
+pub fn read_stdin_section() -> io::Result<tempfile::TempPath> {
+    let mut file = tempfile::Builder::new()
+        .prefix("md_concat-stdin-")
+        .tempfile()?;
+    io::copy(&mut io::stdin().lock(), &mut file)?;
+    Ok(file.into_temp_path())
+}
+
+/// Reads an explicit list of files from a file or stdin ("-") and resolves each
+/// entry to a (relative, canonical) path pair, keeping the given order.
+///
+/// Relative entries are resolved against `root`. In newline-separated lists,
+/// blank lines and lines starting with `#` are ignored so manifests can be commented.
+pub fn read_file_list(
+    source: &Path,
+    root: &Path,
+    nul_separated: bool,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut text = String::new();
+    if source == Path::new("-") {
+        io::stdin().read_to_string(&mut text)?;
+    } else {
+        text = fs::read_to_string(source)?;
+    }
+
+    let separator = if nul_separated { '\0' } else { '\n' };
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+
+    let canonical_root = fs::canonicalize(root)?;
+
+    for entry in text.split(separator) {
+        let entry = if nul_separated { entry } else { entry.trim() };
+        if entry.is_empty() || (!nul_separated && entry.starts_with('#')) {
+            continue;
+        }
+
+        let path = root.join(entry);
+        let canonical_path = match fs::canonicalize(&path) {
+            Ok(p) if p.is_file() => p,
+            Ok(_) => {
+                eprintln!("Warning: Not a file, skipping: {}", entry);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Warning: Could not access listed file {}: {}", entry, e);
+                continue;
+            }
+        };
+
+        let rel_path = match canonical_path.strip_prefix(&canonical_root) {
+            Ok(rel_path) => rel_path.to_path_buf(),
+            Err(_) => display_path(Path::new(entry)),
+        };
+
+        if seen.insert(canonical_path.clone()) {
+            files.push((rel_path, canonical_path));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Drops leading "./" components so listed paths read like collected ones
+fn display_path(path: &Path) -> PathBuf {
+    path.components()
+        .skip_while(|c| matches!(c, Component::CurDir))
+        .collect()
+}
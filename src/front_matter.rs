@@ -0,0 +1,97 @@
+use chrono::{SecondsFormat, Utc};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::paths;
+use crate::writer::FileStats;
+use crate::{CliArgs, TokenCountStrategy};
+
+/// YAML block describing how a document was generated, written at its start so
+/// archived snapshots are self-describing
+pub fn front_matter(
+    args: &CliArgs,
+    stats: &[FileStats],
+    stripped_prefix: Option<&Path>,
+    char_count: usize,
+) -> String {
+    let mut yaml = String::from("---\n");
+    // A timestamp would change the first line of every run
+    if !args.cache_stable && !args.deterministic {
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let _ = writeln!(yaml, "generated: {}", now);
+    }
+    let _ = writeln!(
+        yaml,
+        "generator: {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    match args.files_from {
+        Some(ref list) => {
+            let list = paths::shown(list, args.deterministic);
+            let _ = writeln!(yaml, "files_from: {}", quote_path(&list));
+        }
+        None if let Some(ref manifest) = args.from_manifest => {
+            let manifest = paths::shown(manifest, args.deterministic);
+            let _ = writeln!(yaml, "from_manifest: {}", quote_path(&manifest));
+        }
+        None => {
+            let _ = writeln!(yaml, "roots:");
+            for root in &args.input_dirs {
+                let root = paths::shown(root, args.deterministic);
+                let _ = writeln!(yaml, "  - {}", quote_path(&root));
+            }
+        }
+    }
+    if let Some(prefix) = stripped_prefix {
+        // Paths below are relative to this directory
+        let _ = writeln!(yaml, "path_prefix: {}", quote_path(prefix));
+    }
+    let _ = writeln!(yaml, "filters:");
+    list(&mut yaml, "extensions", &args.extensions);
+    let exclude_dirs: Vec<String> = args
+        .exclude_dirs
+        .iter()
+        .filter(|dir| !dir.is_empty())
+        .cloned()
+        .collect();
+    list(&mut yaml, "exclude_dirs", &exclude_dirs);
+    list(&mut yaml, "workspace_members", &args.workspace_member);
+    let _ = writeln!(yaml, "  gitignore: {}", args.respect_gitignore);
+    if let Some(max_tokens) = args.max_tokens {
+        let _ = writeln!(yaml, "  max_tokens: {}", max_tokens);
+    }
+
+    let included = stats.iter().filter(|file| file.char_count > 0).count();
+    let _ = writeln!(yaml, "files: {}", included);
+    let _ = writeln!(yaml, "tokens:");
+    for (key, strategy) in [
+        ("conservative", TokenCountStrategy::Conservative),
+        ("claude", TokenCountStrategy::Claude),
+        ("gpt", TokenCountStrategy::Gpt),
+        ("word_based", TokenCountStrategy::WordBased),
+    ] {
+        let _ = writeln!(yaml, "  {}: {}", key, strategy.estimate(char_count));
+    }
+    yaml.push_str("---\n\n");
+    yaml
+}
+
+/// Writes a flow sequence of double-quoted strings, which is valid YAML
+fn list(yaml: &mut String, key: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+    let quoted: Vec<String> = values.iter().map(|value| quote(value)).collect();
+    let _ = writeln!(yaml, "  {}: [{}]", key, quoted.join(", "));
+}
+
+fn quote_path(path: &Path) -> String {
+    quote(&path.display().to_string())
+}
+
+/// JSON string escaping is a subset of YAML's double-quoted style
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("strings serialize")
+}
@@ -1,8 +1,99 @@
+use clap::ValueEnum;
+use ignore::Match;
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::error::Error;
+use std::ffi::OsStr;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+
+use crate::exclude::{ExcludeDirs, OnlyPaths};
+use crate::report::Skipped;
+use crate::submodules::Submodules;
+
+/// When headings are prefixed with the name of the input directory they came from
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RootLabel {
+    /// Only when more than one input directory is given
+    #[default]
+    Auto,
+    /// Always, even for a single input directory
+    Always,
+    /// Never; paths are relative to their own input directory
+    Never,
+}
+
+impl RootLabel {
+    pub fn applies(self, root_count: usize) -> bool {
+        match self {
+            RootLabel::Auto => root_count > 1,
+            RootLabel::Always => true,
+            RootLabel::Never => false,
+        }
+    }
+}
+
+/// Labels of the input directories: the name of each, preceded by as many parent
+/// directories as it takes to tell apart those with the same name (`a/src` and
+/// `b/src`). A directory given twice gets a numeric suffix (`src-2`).
+pub fn root_labels(input_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let names: Vec<Vec<&OsStr>> = input_dirs
+        .iter()
+        .map(|dir| {
+            dir.components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    let label = |index: usize, depth: usize| -> PathBuf {
+        let names = &names[index];
+        names[names.len().saturating_sub(depth)..].iter().collect()
+    };
+
+    let mut depths = vec![1; input_dirs.len()];
+    let mut labels: Vec<PathBuf> = (0..input_dirs.len()).map(|i| label(i, 1)).collect();
+    loop {
+        let mut grown = false;
+        for index in 0..labels.len() {
+            // Shared with a different directory; the same one given twice is numbered below
+            let shared = (0..labels.len()).any(|other| {
+                labels[other] == labels[index] && input_dirs[other] != input_dirs[index]
+            });
+            if shared && depths[index] < names[index].len() {
+                depths[index] += 1;
+                grown = true;
+            }
+        }
+        if !grown {
+            break;
+        }
+        labels = (0..input_dirs.len()).map(|i| label(i, depths[i])).collect();
+    }
+
+    labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| {
+            match labels[..index]
+                .iter()
+                .filter(|earlier| *earlier == label)
+                .count()
+            {
+                0 => label.clone(),
+                earlier => {
+                    let mut name = label.clone().into_os_string();
+                    name.push(format!("-{}", earlier + 1));
+                    PathBuf::from(name)
+                }
+            }
+        })
+        .collect()
+}
 
 /// Manages gitignore files and provides filtering functionality
 pub struct GitignoreManager {
@@ -10,6 +101,10 @@ pub struct GitignoreManager {
     ignores: HashMap<PathBuf, Gitignore>,
     /// Global gitignore patterns that apply to all files
     global_ignore: Option<Gitignore>,
+    /// The gitignore files loaded, for --debug-ignore
+    files: Vec<PathBuf>,
+    /// Log each match with the rule that decided it (--debug-ignore)
+    debug: bool,
 }
 
 impl GitignoreManager {
@@ -18,6 +113,8 @@ impl GitignoreManager {
         Self {
             ignores: HashMap::new(),
             global_ignore: None,
+            files: Vec::new(),
+            debug: false,
         }
     }
 
@@ -34,15 +131,16 @@ impl GitignoreManager {
         for input_dir in input_dirs {
             if let Ok(canonical_dir) = fs::canonicalize(input_dir) {
                 manager.discover_gitignore_files_recursive(&canonical_dir, &mut gitignore_files)?;
+                discover_parent_gitignore_files(&canonical_dir, &mut gitignore_files);
             }
         }
 
         // Add any additional gitignore files specified by user
         for gitignore_file in additional_gitignore_files {
-            if let Ok(canonical_file) = fs::canonicalize(gitignore_file) {
-                if let Some(parent_dir) = canonical_file.parent() {
-                    gitignore_files.insert(parent_dir.to_path_buf(), canonical_file);
-                }
+            if let Ok(canonical_file) = fs::canonicalize(gitignore_file)
+                && let Some(parent_dir) = canonical_file.parent()
+            {
+                gitignore_files.insert(parent_dir.to_path_buf(), canonical_file);
             }
         }
 
@@ -51,6 +149,7 @@ impl GitignoreManager {
             match manager.build_gitignore_for_directory(&dir_path, &gitignore_path) {
                 Ok(gitignore) => {
                     manager.ignores.insert(dir_path, gitignore);
+                    manager.files.push(gitignore_path);
                 }
                 Err(e) => {
                     eprintln!(
@@ -65,6 +164,20 @@ impl GitignoreManager {
         Ok(manager)
     }
 
+    /// Logs to stderr the gitignore files loaded and, from now on, every path a
+    /// rule ignores or re-includes, with the pattern and the file and line it
+    /// comes from (--debug-ignore)
+    pub fn enable_debug(&mut self) {
+        self.debug = true;
+        self.files.sort();
+        if self.files.is_empty() {
+            eprintln!("[debug-ignore] no gitignore files found");
+        }
+        for path in &self.files {
+            eprintln!("[debug-ignore] loaded {}", path.display());
+        }
+    }
+
     /// Recursively discovers gitignore files in a directory
     fn discover_gitignore_files_recursive(
         &self,
@@ -87,10 +200,11 @@ impl GitignoreManager {
                 let path = entry.path();
                 if path.is_dir() {
                     // Don't recurse into hidden directories except .git
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        if name.starts_with('.') && name != ".git" {
-                            continue;
-                        }
+                    if let Some(name) = path.file_name().and_then(|n| n.to_str())
+                        && name.starts_with('.')
+                        && name != ".git"
+                    {
+                        continue;
                     }
                     self.discover_gitignore_files_recursive(&path, gitignore_files)?;
                 }
@@ -115,50 +229,101 @@ impl GitignoreManager {
         Ok(builder.build()?)
     }
 
-    /// Checks if a file should be ignored based on all applicable gitignore rules
-    pub fn should_ignore(&self, file_path: &Path, relative_path: &Path) -> bool {
+    /// Checks if a file should be ignored based on all applicable gitignore rules,
+    /// describing the rule that ignores it along with the file it comes from
+    pub fn ignoring_rule(&self, file_path: &Path, relative_path: &Path) -> Option<String> {
         // Check global ignore first
-        if let Some(ref global_ignore) = self.global_ignore {
-            if global_ignore
-                .matched(relative_path, file_path.is_dir())
-                .is_ignore()
-            {
-                return true;
-            }
+        if let Some(ref global_ignore) = self.global_ignore
+            && let Match::Ignore(glob) = global_ignore.matched(relative_path, file_path.is_dir())
+        {
+            self.log_match(file_path, "ignored", glob);
+            return Some(describe_rule(glob));
         }
 
-        // Check directory-specific ignores
-        // We need to find the most specific gitignore that applies to this file
-        let mut best_match_dir: Option<&Path> = None;
-        let mut best_match_depth = 0;
-
-        for dir_path in self.ignores.keys() {
-            if file_path.starts_with(dir_path) {
-                let depth = dir_path.components().count();
-                if depth > best_match_depth {
-                    best_match_depth = depth;
-                    best_match_dir = Some(dir_path);
+        // Check directory-specific ignores like git does: the gitignore closest
+        // to the file decides, and the ones further up only if it has no opinion
+        let mut applicable: Vec<(&PathBuf, &Gitignore)> = self
+            .ignores
+            .iter()
+            .filter(|(dir_path, _)| file_path.starts_with(dir_path) && file_path != *dir_path)
+            .collect();
+        applicable.sort_by_key(|(dir_path, _)| std::cmp::Reverse(dir_path.components().count()));
+
+        for (dir_path, gitignore) in applicable {
+            // Calculate relative path from the gitignore directory
+            let Ok(rel_from_gitignore) = file_path.strip_prefix(dir_path) else {
+                continue;
+            };
+            // A file inside an ignored directory is ignored too, which matters for
+            // directories the walk starts in rather than descends into
+            match gitignore.matched_path_or_any_parents(rel_from_gitignore, file_path.is_dir()) {
+                Match::Ignore(glob) => {
+                    self.log_match(file_path, "ignored", glob);
+                    return Some(describe_rule(glob));
                 }
+                Match::Whitelist(glob) => {
+                    self.log_match(file_path, "re-included", glob);
+                    return None;
+                }
+                Match::None => {}
             }
         }
 
-        if let Some(matching_dir) = best_match_dir {
-            if let Some(gitignore) = self.ignores.get(matching_dir) {
-                // Calculate relative path from the gitignore directory
-                if let Ok(rel_from_gitignore) = file_path.strip_prefix(matching_dir) {
-                    return gitignore
-                        .matched(rel_from_gitignore, file_path.is_dir())
-                        .is_ignore();
-                }
-            }
+        None
+    }
+
+    fn log_match(&self, path: &Path, verdict: &str, glob: &ignore::gitignore::Glob) {
+        if !self.debug {
+            return;
         }
+        let line = glob.from().and_then(|source| {
+            let text = fs::read_to_string(source).ok()?;
+            let index = text
+                .lines()
+                .position(|line| line.trim() == glob.original())?;
+            Some(index + 1)
+        });
+        eprintln!(
+            "[debug-ignore] {}{} {} by `{}` in {}{}",
+            path.display(),
+            if path.is_dir() { "/" } else { "" },
+            verdict,
+            glob.original(),
+            glob.from()
+                .map_or_else(|| "(global)".into(), |source| source.display().to_string()),
+            line.map(|line| format!(":{}", line)).unwrap_or_default()
+        );
+    }
+}
 
-        false
+/// Adds the gitignore files of the directories above `dir` up to the root of
+/// the git repository containing it, which git applies as well. Nothing is
+/// added outside a repository.
+fn discover_parent_gitignore_files(dir: &Path, gitignore_files: &mut HashMap<PathBuf, PathBuf>) {
+    if dir.join(".git").exists() {
+        return;
+    }
+    let Some(repo_root) = dir.ancestors().skip(1).find(|a| a.join(".git").exists()) else {
+        return;
+    };
+    for ancestor in dir.ancestors().skip(1) {
+        let gitignore_path = ancestor.join(".gitignore");
+        if gitignore_path.is_file() {
+            gitignore_files
+                .entry(ancestor.to_path_buf())
+                .or_insert(gitignore_path);
+        }
+        if ancestor == repo_root {
+            break;
+        }
     }
+}
 
-    /// Checks if a directory should be ignored (for early pruning during traversal)
-    pub fn should_ignore_directory(&self, dir_path: &Path) -> bool {
-        self.should_ignore(dir_path, dir_path)
+/// "target/ in /repo/.gitignore"
+fn describe_rule(glob: &ignore::gitignore::Glob) -> String {
+    match glob.from() {
+        Some(source) => format!("{} in {}", glob.original(), source.display()),
+        None => glob.original().to_string(),
     }
 }
 
@@ -168,24 +333,30 @@ impl Default for GitignoreManager {
     }
 }
 
-/// Collects files with gitignore filtering applied
+/// Collects files with gitignore filtering applied, along with the files and
+/// directories that were left out
 pub fn collect_files_with_gitignore(
     input_dirs: &[PathBuf],
     extensions: &std::collections::HashSet<String>,
-    exclude_dirs: &std::collections::HashSet<String>,
+    exclude_dirs: &ExcludeDirs,
+    only_paths: &OnlyPaths,
     gitignore_manager: &GitignoreManager,
-    respect_gitignore: bool,
-) -> Vec<(PathBuf, PathBuf)> {
+    label_roots: bool,
+    submodules: &Submodules,
+) -> (Vec<(PathBuf, PathBuf)>, Vec<Skipped>) {
     use std::collections::HashSet;
     use walkdir::WalkDir;
 
     let mut found_files = Vec::new();
+    let mut skipped = Vec::new();
     let mut processed_files = HashSet::new();
 
-    for input_dir in input_dirs {
-        let walker = WalkDir::new(input_dir).follow_links(false);
+    let labels = root_labels(input_dirs);
+    for (input_dir, label) in input_dirs.iter().zip(labels) {
+        let label = if label_roots { label } else { PathBuf::new() };
+        let mut walker = WalkDir::new(input_dir).follow_links(false).into_iter();
 
-        for entry in walker {
+        while let Some(entry) = walker.next() {
             let entry = match entry {
                 Ok(entry) => entry,
                 Err(e) => {
@@ -195,55 +366,80 @@ pub fn collect_files_with_gitignore(
             };
 
             let path = entry.path();
+            let shown = || label.join(path.strip_prefix(input_dir).unwrap_or(path));
 
-            // Skip if this is a directory and it's in exclude_dirs
+            // Skip (and don't descend into) directories in exclude_dirs
             if entry.file_type().is_dir() {
-                if let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) {
-                    if exclude_dirs.contains(dir_name) {
-                        continue;
-                    }
+                if let Ok(rel_dir) = path.strip_prefix(input_dir)
+                    && !rel_dir.as_os_str().is_empty()
+                    && exclude_dirs.matches(rel_dir)
+                {
+                    skipped.push(Skipped::dir(shown(), "excluded directory (--exclude-dirs)"));
+                    walker.skip_current_dir();
+                    continue;
+                }
+
+                if let Ok(rel_dir) = path.strip_prefix(input_dir)
+                    && !only_paths.enters(rel_dir)
+                {
+                    skipped.push(Skipped::dir(shown(), "outside --only-paths"));
+                    walker.skip_current_dir();
+                    continue;
+                }
+
+                if submodules.skips_dir(path) {
+                    skipped.push(Skipped::dir(shown(), "git submodule (--submodules)"));
+                    walker.skip_current_dir();
+                    continue;
                 }
 
                 // If respecting gitignore, check if directory should be ignored
-                if respect_gitignore && gitignore_manager.should_ignore_directory(path) {
+                if let Some(rule) = gitignore_manager.ignoring_rule(path, path) {
+                    skipped.push(Skipped::dir(shown(), format!("ignored by {}", rule)));
+                    walker.skip_current_dir();
                     continue;
                 }
             }
 
-            if entry.file_type().is_file() {
-                if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                    if extensions.contains(ext) {
-                        // Get canonical path for deduplication
-                        let canonical_file_path = match std::fs::canonicalize(path) {
-                            Ok(p) => p,
-                            Err(_) => path.to_path_buf(),
-                        };
-
-                        // Check if we've already processed this file
-                        if processed_files.contains(&canonical_file_path) {
-                            continue;
-                        }
-
-                        // Apply gitignore filtering if enabled
-                        if respect_gitignore {
-                            if let Ok(rel_path) = path.strip_prefix(input_dir) {
-                                if gitignore_manager.should_ignore(path, rel_path) {
-                                    continue;
-                                }
-                            }
-                        }
-
-                        // Add to results
-                        if let Ok(rel_path) = path.strip_prefix(input_dir) {
-                            found_files.push((rel_path.to_path_buf(), canonical_file_path.clone()));
-                            processed_files.insert(canonical_file_path);
-                        } else {
-                            eprintln!(
-                                "Warning: Could not get relative path for {}",
-                                path.display()
-                            );
-                        }
-                    }
+            if entry.file_type().is_file()
+                && let Some(ext) = path.extension().and_then(|e| e.to_str())
+                && extensions.contains(ext)
+                && path
+                    .strip_prefix(input_dir)
+                    .is_ok_and(|rel_path| only_paths.contains(rel_path))
+            {
+                // Get canonical path for deduplication
+                let canonical_file_path = match std::fs::canonicalize(path) {
+                    Ok(p) => p,
+                    Err(_) => path.to_path_buf(),
+                };
+
+                // Check if we've already processed this file
+                if processed_files.contains(&canonical_file_path) {
+                    skipped.push(Skipped::new(
+                        shown(),
+                        "already included from another input directory",
+                    ));
+                    continue;
+                }
+
+                // Apply gitignore filtering (the manager is empty when disabled)
+                if let Ok(rel_path) = path.strip_prefix(input_dir)
+                    && let Some(rule) = gitignore_manager.ignoring_rule(path, rel_path)
+                {
+                    skipped.push(Skipped::new(shown(), format!("ignored by {}", rule)));
+                    continue;
+                }
+
+                // Add to results
+                if let Ok(rel_path) = path.strip_prefix(input_dir) {
+                    found_files.push((label.join(rel_path), canonical_file_path.clone()));
+                    processed_files.insert(canonical_file_path);
+                } else {
+                    eprintln!(
+                        "Warning: Could not get relative path for {}",
+                        path.display()
+                    );
                 }
             }
         }
@@ -251,5 +447,67 @@ pub fn collect_files_with_gitignore(
 
     // Sort by relative path for consistent output
     found_files.sort_by(|a, b| a.0.cmp(&b.0));
-    found_files
+    (found_files, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_labels_tell_apart_directories_with_the_same_name() {
+        let labels = root_labels(&[
+            PathBuf::from("/work/backend/src"),
+            PathBuf::from("/work/frontend/src"),
+            PathBuf::from("/work/docs"),
+        ]);
+        assert_eq!(
+            labels,
+            [
+                PathBuf::from("backend/src"),
+                PathBuf::from("frontend/src"),
+                PathBuf::from("docs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn root_labels_number_identical_directories() {
+        let labels = root_labels(&[PathBuf::from("/work/src"), PathBuf::from("/work/src")]);
+        assert_eq!(labels, [PathBuf::from("src"), PathBuf::from("src-2")]);
+    }
+
+    #[test]
+    fn headings_of_roots_with_the_same_name_do_not_collide() {
+        let dir = tempfile::tempdir().unwrap();
+        let roots: Vec<PathBuf> = ["a/src", "b/src"]
+            .iter()
+            .map(|root| {
+                let root = dir.path().join(root);
+                fs::create_dir_all(&root).unwrap();
+                fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+                root
+            })
+            .collect();
+        let extensions = ["rs".to_string()].into_iter().collect();
+
+        let (found, _) = collect_files_with_gitignore(
+            &roots,
+            &extensions,
+            &ExcludeDirs::new(&[]).unwrap(),
+            &OnlyPaths::new(&[]),
+            &GitignoreManager::default(),
+            true,
+            &Submodules::default(),
+        );
+        let mut headings: Vec<PathBuf> = found.into_iter().map(|(rel_path, _)| rel_path).collect();
+        headings.sort();
+        assert_eq!(
+            headings,
+            [
+                PathBuf::from("a/src/main.rs"),
+                PathBuf::from("b/src/main.rs")
+            ]
+        );
+    }
 }
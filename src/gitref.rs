@@ -0,0 +1,246 @@
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tempfile::TempDir;
+
+use crate::error::ConcatError;
+use crate::overrides::OVERRIDES_FILE;
+use crate::{license, project, workspace};
+
+/// The files of a git tree, written out to a temporary directory so that a run
+/// reads them instead of the working tree (--at-ref). The directory mirrors the
+/// repository and holds a `.git` file pointing at it, so the git history of
+/// each file is still found. Objects downloaded from a bucket (--remote) are
+/// kept the same way.
+#[derive(Debug)]
+pub struct Snapshot {
+    /// Removed when the snapshot is dropped
+    _dir: TempDir,
+    /// Each input directory as given, with its copy in the snapshot
+    roots: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Snapshot {
+    /// Writes out the files of `reference` below each input directory that
+    /// have one of the `extensions`, along with the other files a run reads
+    /// (see [`is_side_file`]). An input directory may also be a bare repository.
+    pub fn new(
+        input_dirs: &[PathBuf],
+        reference: &str,
+        extensions: &[String],
+    ) -> Result<Self, ConcatError> {
+        let git_error = |e: git2::Error| {
+            ConcatError::Config(format!("--at-ref {}: {}", reference, e.message()))
+        };
+        let dir = tempfile::Builder::new()
+            .prefix("md_concat-")
+            .tempdir()
+            .map_err(|e| ConcatError::Config(format!("cannot create a snapshot: {}", e)))?;
+        let extensions: HashSet<&str> = extensions.iter().map(String::as_str).collect();
+
+        // Repositories written out so far, by work tree, with their copies
+        let mut written: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let mut roots = Vec::new();
+        for input_dir in input_dirs {
+            let (work_tree, subpath) = locate(input_dir)?;
+            let copy = match written.iter().find(|(tree, _)| *tree == work_tree) {
+                Some((_, copy)) => copy.clone(),
+                None => {
+                    // A bare `project.git` is shown as `project`
+                    let name = work_tree
+                        .file_stem()
+                        .filter(|_| work_tree.extension().is_some_and(|ext| ext == "git"))
+                        .or(work_tree.file_name())
+                        .unwrap_or("repository".as_ref());
+                    let copy = dir.path().join(written.len().to_string()).join(name);
+                    status!(
+                        "Reading {} at {} from the git object database",
+                        work_tree.display(),
+                        reference
+                    );
+                    let repo = Repository::open(&work_tree).map_err(git_error)?;
+                    write_tree(&repo, reference, &copy, &extensions).map_err(git_error)?;
+                    fs::write(
+                        copy.join(".git"),
+                        format!("gitdir: {}\n", repo.path().display()),
+                    )?;
+                    written.push((work_tree, copy.clone()));
+                    copy
+                }
+            };
+            let root = copy.join(&subpath);
+            if !root.is_dir() {
+                return Err(ConcatError::Config(format!(
+                    "--at-ref {}: {} does not exist at that revision",
+                    reference,
+                    input_dir.display()
+                )));
+            }
+            roots.push((input_dir.clone(), root));
+        }
+        Ok(Self { _dir: dir, roots })
+    }
+
+    /// A snapshot of files already written below `dir`, with the copy of each input directory
+    #[cfg(feature = "object-store")]
+    pub fn from_dir(dir: TempDir, roots: Vec<(PathBuf, PathBuf)>) -> Self {
+        Self { _dir: dir, roots }
+    }
+
+    /// The copy of an input directory in the snapshot
+    pub fn root_for(&self, input_dir: &Path) -> PathBuf {
+        self.roots
+            .iter()
+            .find(|(dir, _)| dir == input_dir)
+            .map_or_else(|| input_dir.to_path_buf(), |(_, root)| root.clone())
+    }
+}
+
+/// The work tree holding `input_dir` and the directory's path inside it, or a
+/// bare repository given as the input directory itself. The directory does not
+/// need to exist in the working tree, only at the revision.
+fn locate(input_dir: &Path) -> Result<(PathBuf, PathBuf), ConcatError> {
+    let absolute = std::path::absolute(input_dir)?;
+    let existing = absolute
+        .ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or(Path::new("/"));
+    let not_in_repository = |e: git2::Error| {
+        ConcatError::Config(format!(
+            "--at-ref: {} is not in a git repository: {}",
+            input_dir.display(),
+            e.message()
+        ))
+    };
+    let repo = Repository::discover(existing).map_err(not_in_repository)?;
+    let Some(work_tree) = repo.workdir() else {
+        // Read straight from the object database, from the top of the tree
+        return Ok((repo.path().canonicalize()?, PathBuf::new()));
+    };
+    let work_tree = work_tree.canonicalize()?;
+    let existing = existing.canonicalize()?;
+    let rest = absolute.strip_prefix(&existing).unwrap_or(Path::new(""));
+    let subpath = existing
+        .strip_prefix(&work_tree)
+        .unwrap_or(Path::new(""))
+        .join(rest);
+    Ok((work_tree, crate::imports::normalize(&subpath)))
+}
+
+/// Whether `dir` is a bare repository, whose files can only be read with --at-ref
+pub fn is_bare_repository(dir: &Path) -> bool {
+    Repository::open_bare(dir).is_ok_and(|repo| repo.is_bare())
+}
+
+/// Writes the matching blobs of the tree at `reference` below `dest`, with the
+/// commit time as their modification time
+fn write_tree(
+    repo: &Repository,
+    reference: &str,
+    dest: &Path,
+    extensions: &HashSet<&str>,
+) -> Result<(), git2::Error> {
+    let commit = repo.revparse_single(reference)?.peel_to_commit()?;
+    let tree = commit.tree()?;
+    let mtime = UNIX_EPOCH + Duration::from_secs(commit.time().seconds().max(0) as u64);
+    fs::create_dir_all(dest).map_err(io_error)?;
+
+    let mut result = Ok(());
+    let walked = tree.walk(TreeWalkMode::PreOrder, |parent, entry| {
+        let Some(name) = entry.name() else {
+            return TreeWalkResult::Skip;
+        };
+        // Symbolic links (0o120000) and submodules are not written out
+        if entry.kind() != Some(ObjectType::Blob) || entry.filemode() == 0o120000 {
+            return TreeWalkResult::Ok;
+        }
+        let wanted = is_side_file(&Path::new(parent).join(name))
+            || Path::new(name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.contains(ext));
+        if !wanted {
+            return TreeWalkResult::Ok;
+        }
+        let path = dest.join(parent).join(name);
+        let written = entry
+            .to_object(repo)
+            .and_then(|object| object.peel_to_blob())
+            .and_then(|blob| {
+                fs::create_dir_all(path.parent().unwrap_or(dest)).map_err(io_error)?;
+                fs::write(&path, blob.content()).map_err(io_error)?;
+                fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|file| file.set_modified(mtime))
+                    .map_err(io_error)
+            });
+        match written {
+            Ok(()) => TreeWalkResult::Ok,
+            Err(e) => {
+                result = Err(e);
+                TreeWalkResult::Abort
+            }
+        }
+    });
+    result.and(walked)
+}
+
+/// Files read besides the selected sources, kept whatever their extension:
+/// .gitignore and .gitmodules files, `.mdconcat.toml` overrides, package
+/// manifests and workspace root files (--project-summary, --deps-appendix,
+/// --workspace-member) and license files (--license-notice)
+fn is_side_file(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default();
+    name == ".gitignore"
+        || name == ".gitmodules"
+        || name == OVERRIDES_FILE
+        || project::MANIFESTS.iter().any(|manifest| name == *manifest)
+        || workspace::is_root_file(path)
+        || license::is_license_file(path)
+}
+
+fn io_error(e: std::io::Error) -> git2::Error {
+    git2::Error::from_str(&e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_keeps_manifests_and_licenses() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        for (path, text) in [
+            ("src/lib.rs", "pub fn a() {}\n"),
+            ("src/notes.txt", "notes\n"),
+            ("Cargo.toml", "[package]\nname = \"a\"\n"),
+            ("LICENSE", "MIT License\n"),
+            (".mdconcat.toml", "line_numbers = true\n"),
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("a", "a@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "a", &tree, &[])
+            .unwrap();
+
+        let input = dir.path().to_path_buf();
+        let snapshot =
+            Snapshot::new(std::slice::from_ref(&input), "HEAD", &["rs".to_string()]).unwrap();
+        let root = snapshot.root_for(&input);
+        for kept in ["src/lib.rs", "Cargo.toml", "LICENSE", ".mdconcat.toml"] {
+            assert!(root.join(kept).is_file(), "{}", kept);
+        }
+        assert!(!root.join("src/notes.txt").exists());
+    }
+}
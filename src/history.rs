@@ -0,0 +1,275 @@
+use chrono::DateTime;
+use git2::{DiffOptions, Repository, Sort};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConcatError;
+use crate::paths;
+
+/// Commits looked at for --weight-recency, newest first
+const RECENCY_COMMITS: usize = 1000;
+/// Age in days at which a change counts half as much as one made today
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+/// Priority weight added to the most often and recently changed file
+const RECENCY_WEIGHT: f64 = 10.0;
+
+/// Priority weight bonus of each file for --weight-recency, from 0 for files
+/// that did not change lately (or are not in a git repository) to 10 for the
+/// most often and recently changed one
+pub fn recency_weights(files: &[(PathBuf, PathBuf)], revision: &str) -> Vec<i64> {
+    let scores = recency_scores(files, revision);
+    let hottest = scores.iter().copied().fold(0.0, f64::max);
+    scores
+        .into_iter()
+        .map(|score| {
+            if hottest > 0.0 {
+                (score / hottest * RECENCY_WEIGHT).round() as i64
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// How often and how recently each file changed in its git history: every
+/// commit touching a file adds 1, halved for each 30 days of the commit's age.
+/// Files outside a git repository score 0.
+fn recency_scores(files: &[(PathBuf, PathBuf)], revision: &str) -> Vec<f64> {
+    let locations = locate(files);
+    let mut repositories: HashMap<&Path, HashMap<PathBuf, f64>> = HashMap::new();
+    locations
+        .iter()
+        .map(|location| {
+            let Some((work_tree, path)) = location else {
+                return 0.0;
+            };
+            repositories
+                .entry(work_tree)
+                .or_insert_with(|| change_scores(work_tree, revision).unwrap_or_default())
+                .get(path)
+                .copied()
+                .unwrap_or(0.0)
+        })
+        .collect()
+}
+
+/// The work tree of the git repository holding each file and the file's path
+/// relative to it, or None for files outside a repository
+fn locate(files: &[(PathBuf, PathBuf)]) -> Vec<Option<(PathBuf, PathBuf)>> {
+    let mut work_trees: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+    files
+        .iter()
+        .map(|(_, abs_path)| {
+            let abs_path = fs::canonicalize(abs_path).ok()?;
+            let dir = abs_path.parent()?.to_path_buf();
+            // The directory holding `.git`, which is also the root of an --at-ref snapshot
+            let work_tree = work_trees
+                .entry(dir.clone())
+                .or_insert_with(|| paths::repository_root(&dir))
+                .clone()?;
+            let path = abs_path.strip_prefix(&work_tree).ok()?.to_path_buf();
+            Some((work_tree, path))
+        })
+        .collect()
+}
+
+/// Commits looked at for --file-history, newest first
+const HISTORY_COMMITS: usize = 5000;
+
+/// The last `count` commits that changed each file, newest first, as
+/// "date `hash` subject" lines (--file-history). Files outside a git repository,
+/// or not changed in the last 5000 commits, have none.
+pub fn file_histories(
+    files: &[(PathBuf, PathBuf)],
+    count: usize,
+    revision: &str,
+) -> Vec<Vec<String>> {
+    let locations = locate(files);
+    let mut wanted: HashMap<&Path, HashMap<&Path, Vec<String>>> = HashMap::new();
+    for (work_tree, path) in locations.iter().flatten() {
+        wanted
+            .entry(work_tree)
+            .or_default()
+            .insert(path, Vec::new());
+    }
+    for (work_tree, histories) in &mut wanted {
+        if let Err(e) = collect_histories(work_tree, histories, count, revision) {
+            status!(
+                "Could not read the git history of {}: {}",
+                work_tree.display(),
+                e.message()
+            );
+        }
+    }
+    locations
+        .iter()
+        .map(|location| {
+            location
+                .as_ref()
+                .and_then(|(work_tree, path)| wanted.get(work_tree.as_path())?.get(path.as_path()))
+                .cloned()
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn collect_histories(
+    work_tree: &Path,
+    histories: &mut HashMap<&Path, Vec<String>>,
+    count: usize,
+    revision: &str,
+) -> Result<(), git2::Error> {
+    let repo = Repository::open(work_tree)?;
+    let mut walk = repo.revwalk()?;
+    walk.push(repo.revparse_single(revision)?.peel_to_commit()?.id())?;
+    walk.set_sorting(Sort::TIME)?;
+    let mut incomplete = histories.len();
+    for oid in walk.take(HISTORY_COMMITS) {
+        if incomplete == 0 {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        for path in changed_paths(&repo, &commit)? {
+            if let Some(history) = histories.get_mut(path.as_path())
+                && history.len() < count
+            {
+                history.push(format!(
+                    "{} `{}` {}",
+                    date(&commit),
+                    short_hash(&commit),
+                    commit.summary().unwrap_or("")
+                ));
+                if history.len() == count {
+                    incomplete -= 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn date(commit: &git2::Commit) -> String {
+    DateTime::from_timestamp(commit.time().seconds(), 0)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn short_hash(commit: &git2::Commit) -> String {
+    commit.id().to_string()[..7].to_string()
+}
+
+/// Files listed per commit in the --git-log-summary section
+const LOG_FILES_SHOWN: usize = 10;
+
+/// Section listing the last `count` commits up to `revision` of the repository
+/// containing `dir`, newest first, with their date, author, subject and the files they touched
+/// (--git-log-summary). It has no code block, so it is never mistaken for a
+/// file section.
+pub fn log_section(
+    dir: &Path,
+    revision: &str,
+    count: usize,
+    heading_level: usize,
+) -> Result<String, ConcatError> {
+    let git_error = |e: git2::Error| {
+        ConcatError::Config(format!(
+            "--git-log-summary: {}: {}",
+            dir.display(),
+            e.message()
+        ))
+    };
+    let repo = Repository::discover(dir).map_err(git_error)?;
+    let mut section = format!("{} Recent Commits\n\n", "#".repeat(heading_level));
+    if repo
+        .head()
+        .is_err_and(|e| e.code() == git2::ErrorCode::UnbornBranch)
+    {
+        section.push_str("No commits yet.\n\n");
+        return Ok(section);
+    }
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    let start = repo
+        .revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(git_error)?;
+    walk.push(start.id()).map_err(git_error)?;
+    walk.set_sorting(Sort::TIME).map_err(git_error)?;
+
+    for oid in walk.take(count) {
+        let commit = repo
+            .find_commit(oid.map_err(git_error)?)
+            .map_err(git_error)?;
+        let _ = writeln!(
+            section,
+            "- `{}` {} {}: {}",
+            short_hash(&commit),
+            date(&commit),
+            commit.author().name().unwrap_or("unknown"),
+            commit.summary().unwrap_or("")
+        );
+        let paths = changed_paths(&repo, &commit).map_err(git_error)?;
+        if !paths.is_empty() {
+            let mut files: Vec<String> = paths
+                .iter()
+                .take(LOG_FILES_SHOWN)
+                .map(|path| format!("`{}`", path.display()))
+                .collect();
+            if paths.len() > LOG_FILES_SHOWN {
+                files.push(format!("and {} more", paths.len() - LOG_FILES_SHOWN));
+            }
+            let _ = writeln!(section, "  - {}", files.join(", "));
+        }
+    }
+    section.push('\n');
+    Ok(section)
+}
+
+/// Id of the commit `revision` resolves to in the repository containing `dir`,
+/// or None outside a repository
+pub fn commit_id(dir: &Path, revision: &str) -> Option<String> {
+    let repo = Repository::discover(dir).ok()?;
+    let commit = repo.revparse_single(revision).ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Recency-weighted change counts of the files touched by the last commits up
+/// to `revision` of the repository at `work_tree`
+fn change_scores(work_tree: &Path, revision: &str) -> Result<HashMap<PathBuf, f64>, git2::Error> {
+    let repo = Repository::open(work_tree)?;
+    let mut walk = repo.revwalk()?;
+    walk.push(repo.revparse_single(revision)?.peel_to_commit()?.id())?;
+    walk.set_sorting(Sort::TIME)?;
+    let now = chrono::Utc::now().timestamp();
+
+    let mut scores = HashMap::new();
+    for oid in walk.take(RECENCY_COMMITS) {
+        let commit = repo.find_commit(oid?)?;
+        let age_days = (now - commit.time().seconds()).max(0) as f64 / 86_400.0;
+        let weight = 0.5f64.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+        for path in changed_paths(&repo, &commit)? {
+            *scores.entry(path).or_insert(0.0) += weight;
+        }
+    }
+    Ok(scores)
+}
+
+/// Paths, relative to the work tree, that a commit changed compared to its
+/// first parent (or that it added, for a root commit)
+fn changed_paths(repo: &Repository, commit: &git2::Commit) -> Result<Vec<PathBuf>, git2::Error> {
+    let tree = commit.tree()?;
+    let parent = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_tree(
+        parent.as_ref(),
+        Some(&tree),
+        Some(DiffOptions::new().skip_binary_check(true)),
+    )?;
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().map(Path::to_path_buf))
+        .collect())
+}
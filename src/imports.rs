@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Finds, for every file, the indices of the other selected files it imports
+/// or includes. Parsing is line-based and deliberately lightweight: it covers
+/// Rust `mod`/`use crate::`, C/C++ `#include "..."`, Python `import`/`from`,
+/// JavaScript/TypeScript relative `import`/`require`, and Go package imports.
+pub fn dependencies(files: &[(PathBuf, PathBuf)]) -> Vec<Vec<usize>> {
+    let index: HashMap<PathBuf, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(i, (rel_path, _))| (normalize(rel_path), i))
+        .collect();
+    let resolver = Resolver { files, index };
+
+    files
+        .iter()
+        .enumerate()
+        .map(|(i, (rel_path, abs_path))| {
+            let content = match fs::read(abs_path) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Err(_) => return Vec::new(),
+            };
+            let mut deps = resolver.resolve(&normalize(rel_path), &content);
+            deps.retain(|&dep| dep != i);
+            deps.sort_unstable();
+            deps.dedup();
+            deps
+        })
+        .collect()
+}
+
+/// Returns which files are reachable from the `seeds` (indices into `files`)
+/// by following imports at most `hops` times; the seeds themselves are hop 0
+pub fn reachable(files: &[(PathBuf, PathBuf)], seeds: &[usize], hops: usize) -> Vec<bool> {
+    let deps = dependencies(files);
+    let mut reached = vec![false; files.len()];
+    let mut frontier: Vec<usize> = seeds.to_vec();
+    for &seed in seeds {
+        reached[seed] = true;
+    }
+    for _ in 0..hops {
+        let mut next = Vec::new();
+        for dep in frontier.iter().flat_map(|&i| &deps[i]) {
+            if !reached[*dep] {
+                reached[*dep] = true;
+                next.push(*dep);
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    reached
+}
+
+struct Resolver<'a> {
+    files: &'a [(PathBuf, PathBuf)],
+    index: HashMap<PathBuf, usize>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&self, rel_path: &Path, content: &str) -> Vec<usize> {
+        let ext = rel_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match ext {
+            "rs" => self.rust(rel_path, content),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => self.c(rel_path, content),
+            "py" => self.python(rel_path, content),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" | "vue" | "svelte" => {
+                self.javascript(rel_path, content)
+            }
+            "go" => self.go(rel_path, content),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Looks up the first candidate path that is part of the selection
+    fn first(&self, candidates: impl IntoIterator<Item = PathBuf>) -> Option<usize> {
+        candidates
+            .into_iter()
+            .find_map(|candidate| self.index.get(&normalize(&candidate)).copied())
+    }
+
+    /// Finds a selected file whose path ends with the given relative path
+    fn by_suffix(&self, suffix: &Path) -> Option<usize> {
+        self.files
+            .iter()
+            .position(|(rel_path, _)| normalize(rel_path).ends_with(suffix))
+    }
+
+    fn rust(&self, rel_path: &Path, content: &str) -> Vec<usize> {
+        let dir = parent(rel_path);
+        let stem = rel_path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let module_dir = if matches!(stem, "main" | "lib" | "mod") {
+            dir.clone()
+        } else {
+            dir.join(stem)
+        };
+        let crate_root = self.rust_crate_root(&dir);
+        let module_file = |base: &Path, name: &str| {
+            self.first([
+                base.join(format!("{}.rs", name)),
+                base.join(name).join("mod.rs"),
+            ])
+        };
+
+        let mut deps = Vec::new();
+        for line in content.lines() {
+            let line = strip_visibility(line.trim());
+            if let Some(rest) = line.strip_prefix("mod ") {
+                if let Some(name) = rest.strip_suffix(';') {
+                    deps.extend(module_file(&module_dir, name.trim()));
+                }
+            } else if let Some(rest) = line.strip_prefix("use crate::") {
+                let rest = rest.trim_end_matches(';');
+                let names: Vec<&str> = match rest.strip_prefix('{') {
+                    Some(group) => group.trim_end_matches('}').split(',').collect(),
+                    None => vec![rest],
+                };
+                for name in names {
+                    let first = name.trim().split("::").next().unwrap_or("");
+                    if !first.is_empty() {
+                        deps.extend(module_file(&crate_root, first));
+                    }
+                }
+            }
+        }
+        deps
+    }
+
+    /// The crate root is the nearest ancestor directory holding main.rs or lib.rs
+    fn rust_crate_root(&self, dir: &Path) -> PathBuf {
+        dir.ancestors()
+            .find(|ancestor| {
+                self.index.contains_key(&ancestor.join("main.rs"))
+                    || self.index.contains_key(&ancestor.join("lib.rs"))
+            })
+            .unwrap_or(dir)
+            .to_path_buf()
+    }
+
+    fn c(&self, rel_path: &Path, content: &str) -> Vec<usize> {
+        let dir = parent(rel_path);
+        content
+            .lines()
+            .filter_map(|line| {
+                let rest = line.trim().strip_prefix('#')?.trim_start();
+                let include = quoted(rest.strip_prefix("include")?)?;
+                self.first([dir.join(include)])
+                    .or_else(|| self.by_suffix(&normalize(Path::new(include))))
+            })
+            .collect()
+    }
+
+    fn python(&self, rel_path: &Path, content: &str) -> Vec<usize> {
+        let dir = parent(rel_path);
+        let mut deps = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            let (module, names) = if let Some(rest) = line.strip_prefix("from ") {
+                let Some((module, names)) = rest.split_once(" import ") else {
+                    continue;
+                };
+                (module.trim(), names.trim_matches(|c| c == '(' || c == ')'))
+            } else if let Some(rest) = line.strip_prefix("import ") {
+                for module in rest.split(',') {
+                    let module = module.split(" as ").next().unwrap_or("").trim();
+                    deps.extend(self.python_module(&dir, module, None));
+                }
+                continue;
+            } else {
+                continue;
+            };
+
+            let module_dep = self.python_module(&dir, module, None);
+            deps.extend(module_dep);
+            // `from pkg import sub` may name a submodule rather than an attribute
+            for name in names.split(',') {
+                let name = name.split(" as ").next().unwrap_or("").trim();
+                if !name.is_empty() && name != "*" {
+                    deps.extend(self.python_module(&dir, module, Some(name)));
+                }
+            }
+        }
+        deps
+    }
+
+    fn python_module(&self, dir: &Path, module: &str, name: Option<&str>) -> Option<usize> {
+        let dots = module.chars().take_while(|&c| c == '.').count();
+        let mut path: PathBuf = module[dots..]
+            .split('.')
+            .filter(|part| !part.is_empty())
+            .collect();
+        if let Some(name) = name {
+            path.push(name);
+        }
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+
+        let candidates = |base: PathBuf| [base.with_extension("py"), base.join("__init__.py")];
+        if dots > 0 {
+            let mut base = dir.to_path_buf();
+            for _ in 1..dots {
+                base.pop();
+            }
+            self.first(candidates(base.join(&path)))
+        } else {
+            candidates(path)
+                .iter()
+                .find_map(|candidate| self.by_suffix(candidate))
+        }
+    }
+
+    fn javascript(&self, rel_path: &Path, content: &str) -> Vec<usize> {
+        const EXTENSIONS: [&str; 8] = ["ts", "tsx", "js", "jsx", "mjs", "cjs", "vue", "svelte"];
+        let dir = parent(rel_path);
+        let mut deps = Vec::new();
+
+        for line in content.lines() {
+            let specifiers = ["from ", "import ", "require(", "import("]
+                .iter()
+                .filter_map(|keyword| line.find(keyword).map(|i| &line[i + keyword.len()..]))
+                .filter_map(|rest| quoted(rest.trim_start()));
+
+            for specifier in specifiers {
+                if !specifier.starts_with('.') {
+                    continue;
+                }
+                let base = dir.join(specifier);
+                let mut candidates = vec![base.clone()];
+                for ext in EXTENSIONS {
+                    let mut with_ext = base.as_os_str().to_os_string();
+                    with_ext.push(".");
+                    with_ext.push(ext);
+                    candidates.push(PathBuf::from(with_ext));
+                    candidates.push(base.join(format!("index.{}", ext)));
+                }
+                deps.extend(self.first(candidates));
+            }
+        }
+        deps
+    }
+
+    fn go(&self, _rel_path: &Path, content: &str) -> Vec<usize> {
+        let mut deps = Vec::new();
+        let mut in_block = false;
+
+        for line in content.lines() {
+            let line = line.trim();
+            let import = if in_block {
+                if line.starts_with(')') {
+                    in_block = false;
+                    continue;
+                }
+                line
+            } else if let Some(rest) = line.strip_prefix("import") {
+                let rest = rest.trim_start();
+                if rest.starts_with('(') {
+                    in_block = true;
+                    continue;
+                }
+                rest
+            } else {
+                continue;
+            };
+
+            let Some(import) = import.find('"').and_then(|i| quoted(&import[i..])) else {
+                continue;
+            };
+            // A package is every selected file whose directory the import path ends with
+            let import = Path::new(import);
+            deps.extend(
+                self.files
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, (rel_path, _))| {
+                        let dir = parent(&normalize(rel_path));
+                        (!dir.as_os_str().is_empty() && import.ends_with(&dir)).then_some(i)
+                    }),
+            );
+        }
+        deps
+    }
+}
+
+/// Removes `pub`, `pub(crate)` and similar visibility prefixes
+fn strip_visibility(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix("pub") else {
+        return line;
+    };
+    if !rest.starts_with(|c: char| c == '(' || c.is_whitespace()) {
+        return line;
+    }
+    let rest = match rest.strip_prefix('(') {
+        Some(inner) => inner.split_once(')').map_or(rest, |(_, after)| after),
+        None => rest,
+    };
+    rest.trim_start()
+}
+
+/// Returns the contents of a leading '...' or "..." string literal
+fn quoted(text: &str) -> Option<&str> {
+    let quote = text
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let rest = &text[1..];
+    rest.find(quote).map(|end| &rest[..end])
+}
+
+fn parent(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Lexically resolves "." and ".." components
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
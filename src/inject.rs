@@ -0,0 +1,45 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::error::{ConcatError, output_error};
+
+/// Marker that opens the generated region in an existing document
+pub const BEGIN_MARKER: &str = "<!-- md_concat:begin -->";
+/// Marker that closes the generated region in an existing document
+pub const END_MARKER: &str = "<!-- md_concat:end -->";
+
+/// Replaces everything between the begin and end markers of `target` with `body`,
+/// leaving the surrounding hand-written content intact
+pub fn inject_into(target: &Path, body: &[u8]) -> Result<(), ConcatError> {
+    let document = fs::read_to_string(target).map_err(output_error(target))?;
+    let body = String::from_utf8_lossy(body);
+
+    let missing = |marker: &str| {
+        output_error(target)(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("document does not contain the {} marker", marker),
+        ))
+    };
+
+    let begin = document
+        .find(BEGIN_MARKER)
+        .ok_or_else(|| missing(BEGIN_MARKER))?
+        + BEGIN_MARKER.len();
+    let end = document[begin..]
+        .find(END_MARKER)
+        .ok_or_else(|| missing(END_MARKER))?
+        + begin;
+
+    let mut updated = String::with_capacity(document.len() + body.len());
+    updated.push_str(&document[..begin]);
+    updated.push_str("\n\n");
+    updated.push_str(&body);
+    updated.push_str(&document[end..]);
+
+    crate::output::write_atomic(target, |writer| {
+        writer
+            .write_all(updated.as_bytes())
+            .map_err(output_error(target))
+    })
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Extension to fence language mapping: user overrides layered over the built-in table
+#[derive(Debug, Clone, Default)]
+pub struct LanguageMap {
+    overrides: HashMap<String, String>,
+}
+
+impl LanguageMap {
+    pub fn new(overrides: &[(String, String)]) -> Self {
+        Self {
+            overrides: overrides
+                .iter()
+                .map(|(ext, language)| (normalize_extension(ext), language.clone()))
+                .collect(),
+        }
+    }
+
+    /// Returns the code fence language for a file: a user override for its
+    /// extension, a well-known name for common file names and extensions,
+    /// otherwise the raw extension
+    pub fn language_for<'a>(&'a self, rel_path: &'a Path) -> &'a str {
+        let ext = rel_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if let Some(language) = self.overrides.get(&normalize_extension(ext)) {
+            return language;
+        }
+
+        let file_name = rel_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if let Some(language) = by_file_name(file_name) {
+            return language;
+        }
+        by_extension(&ext.to_ascii_lowercase()).unwrap_or(ext)
+    }
+
+    /// Like language_for, but for extensions shared by several languages
+    /// (`.h`, `.m`, `.pl`) the content decides, unless --lang-map names one
+    pub fn language_for_content<'a>(&'a self, rel_path: &'a Path, text: &str) -> &'a str {
+        let ext = rel_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let ext = normalize_extension(ext);
+        if self.overrides.contains_key(&ext) {
+            return self.language_for(rel_path);
+        }
+        classify(&ext, text).unwrap_or_else(|| self.language_for(rel_path))
+    }
+}
+
+/// Tells apart the languages sharing an ambiguous extension from telltale
+/// lines near the top of the file. None if the extension is not ambiguous.
+fn classify(ext: &str, text: &str) -> Option<&'static str> {
+    let lines = || {
+        text.lines()
+            .take(200)
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+    };
+    Some(match ext {
+        "h" => {
+            let cpp = lines().any(|line| {
+                line.starts_with("class ")
+                    || line.starts_with("namespace ")
+                    || line.starts_with("template <")
+                    || line.starts_with("template<")
+                    || line.starts_with("#include <iostream>")
+                    || line.contains("std::")
+                    || line == "public:"
+                    || line == "private:"
+            });
+            let objectivec = lines().any(|line| {
+                line.starts_with("@interface")
+                    || line.starts_with("@protocol")
+                    || line.starts_with("#import ")
+            });
+            if objectivec {
+                "objectivec"
+            } else if cpp {
+                "cpp"
+            } else {
+                "c"
+            }
+        }
+        "m" => {
+            let objectivec = lines().any(|line| {
+                line.starts_with("@interface")
+                    || line.starts_with("@implementation")
+                    || line.starts_with("@protocol")
+                    || line.starts_with("#import ")
+                    || line.starts_with("#include ")
+            });
+            let matlab = lines().any(|line| {
+                line.starts_with('%')
+                    || line.starts_with("function ")
+                    || line.starts_with("classdef ")
+                    || line == "end"
+            });
+            if matlab && !objectivec {
+                "matlab"
+            } else {
+                "objectivec"
+            }
+        }
+        "pl" => {
+            let perl = lines().any(|line| {
+                line.starts_with("#!") && line.contains("perl")
+                    || line.starts_with("use strict")
+                    || line.starts_with("use warnings")
+                    || line.starts_with("my ")
+                    || line.starts_with("sub ")
+                    || line.starts_with("package ")
+            });
+            let prolog = lines().any(|line| {
+                line.starts_with(":-")
+                    || line.starts_with('%')
+                    || (line.contains(":-") && !line.starts_with('#'))
+            });
+            if prolog && !perl { "prolog" } else { "perl" }
+        }
+        _ => return None,
+    })
+}
+
+/// Parses an `ext=language` pair given to --lang-map
+pub fn parse_mapping(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((ext, language)) if !ext.trim().is_empty() && !language.trim().is_empty() => {
+            Ok((ext.trim().to_string(), language.trim().to_string()))
+        }
+        _ => Err(format!("expected EXT=LANGUAGE, got '{}'", text)),
+    }
+}
+
+fn normalize_extension(ext: &str) -> String {
+    ext.trim_start_matches('.').to_ascii_lowercase()
+}
+
+fn by_file_name(file_name: &str) -> Option<&'static str> {
+    Some(match file_name {
+        "Dockerfile" | "Containerfile" => "dockerfile",
+        "Makefile" | "GNUmakefile" | "makefile" => "makefile",
+        "CMakeLists.txt" => "cmake",
+        "Cargo.lock" | "Pipfile" => "toml",
+        "Gemfile" | "Rakefile" => "ruby",
+        "Jenkinsfile" => "groovy",
+        ".bashrc" | ".bash_profile" | ".profile" => "bash",
+        ".zshrc" => "zsh",
+        _ => return None,
+    })
+}
+
+fn by_extension(ext: &str) -> Option<&'static str> {
+    Some(match ext {
+        // Systems languages
+        "rs" => "rust",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "c++" | "hh" | "hpp" | "hxx" | "h++" | "ipp" | "inl" => "cpp",
+        "m" => "objectivec",
+        "mm" => "objectivecpp",
+        "go" => "go",
+        "zig" => "zig",
+        "d" => "d",
+        "nim" => "nim",
+        "v" | "sv" | "svh" => "verilog",
+        "vhd" | "vhdl" => "vhdl",
+        "asm" | "s" => "asm",
+
+        // JVM and .NET
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" | "sc" => "scala",
+        "groovy" | "gradle" => "groovy",
+        "clj" | "cljs" | "cljc" | "edn" => "clojure",
+        "cs" | "csx" => "csharp",
+        "fs" | "fsi" | "fsx" => "fsharp",
+        "vb" => "vbnet",
+
+        // Scripting languages
+        "py" | "pyi" | "pyw" => "python",
+        "rb" | "rake" | "gemspec" => "ruby",
+        "php" | "phtml" => "php",
+        "pl" | "pm" => "perl",
+        "lua" => "lua",
+        "r" => "r",
+        "jl" => "julia",
+        "ex" | "exs" => "elixir",
+        "erl" | "hrl" => "erlang",
+        "hs" | "lhs" => "haskell",
+        "ml" | "mli" => "ocaml",
+        "elm" => "elm",
+        "dart" => "dart",
+        "swift" => "swift",
+        "tcl" => "tcl",
+
+        // Shells
+        "sh" | "bash" => "bash",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "ps1" | "psm1" | "psd1" => "powershell",
+        "bat" | "cmd" => "batch",
+
+        // Web
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "astro" => "astro",
+        "html" | "htm" | "xhtml" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "sass" => "sass",
+        "less" => "less",
+
+        // Data and configuration
+        "json" | "jsonc" | "json5" => "json",
+        "yml" | "yaml" => "yaml",
+        "toml" => "toml",
+        "xml" | "xsd" | "xsl" | "xslt" | "svg" | "plist" | "csproj" | "fsproj" | "vbproj" => "xml",
+        "ini" | "cfg" | "conf" => "ini",
+        "properties" => "properties",
+        "env" => "dotenv",
+        "csv" => "csv",
+        "sql" => "sql",
+        "graphql" | "gql" => "graphql",
+        "proto" => "protobuf",
+        "tf" | "tfvars" | "hcl" => "hcl",
+        "nix" => "nix",
+        "dockerfile" => "dockerfile",
+        "mk" | "mak" => "makefile",
+        "cmake" => "cmake",
+
+        // Documents
+        "md" | "markdown" | "mdx" => "markdown",
+        "rst" => "rst",
+        "tex" | "sty" | "cls" => "latex",
+        "txt" | "text" => "text",
+        "diff" | "patch" => "diff",
+        _ => return None,
+    })
+}
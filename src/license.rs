@@ -0,0 +1,276 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::gitignore::root_labels;
+use crate::project::Package;
+
+/// How a license text is recognized, after collapsing whitespace and lowercasing
+struct Known {
+    id: &'static str,
+    /// Phrases that must all occur: in the title (the first 300 characters) for
+    /// licenses that name other licenses in their body, anywhere otherwise
+    phrases: &'static [&'static str],
+    in_title: bool,
+    /// Phrases that must not occur
+    excluding: &'static [&'static str],
+}
+
+/// Known licenses; earlier entries win, so the more specific ones come first
+const LICENSES: &[Known] = &[
+    Known {
+        id: "AGPL-3.0",
+        phrases: &["gnu affero general public license", "version 3"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "LGPL-3.0",
+        phrases: &["gnu lesser general public license", "version 3"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "LGPL-2.1",
+        phrases: &["gnu lesser general public license", "version 2.1"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "GPL-3.0",
+        phrases: &["gnu general public license", "version 3"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "GPL-2.0",
+        phrases: &["gnu general public license", "version 2"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "Apache-2.0",
+        phrases: &["apache license", "version 2.0"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "MPL-2.0",
+        phrases: &["mozilla public license", "2.0"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "EPL-2.0",
+        phrases: &["eclipse public license", "2.0"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "BSL-1.0",
+        phrases: &["boost software license - version 1.0"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "CC0-1.0",
+        phrases: &["cc0 1.0 universal"],
+        in_title: true,
+        excluding: &[],
+    },
+    Known {
+        id: "Unlicense",
+        phrases: &["free and unencumbered software released into the public domain"],
+        in_title: false,
+        excluding: &[],
+    },
+    Known {
+        id: "MIT",
+        phrases: &[
+            "permission is hereby granted, free of charge",
+            "the above copyright notice and this permission notice shall be included",
+        ],
+        in_title: false,
+        excluding: &[],
+    },
+    Known {
+        id: "ISC",
+        phrases: &[
+            "permission to use, copy, modify, and",
+            "copyright notice and this permission notice appear in all copies",
+        ],
+        in_title: false,
+        excluding: &[],
+    },
+    Known {
+        id: "0BSD",
+        phrases: &[
+            "permission to use, copy, modify, and/or distribute this software for any purpose",
+        ],
+        in_title: false,
+        excluding: &[],
+    },
+    Known {
+        id: "BSD-3-Clause",
+        phrases: &[
+            "redistribution and use in source and binary forms",
+            "neither the name",
+        ],
+        in_title: false,
+        excluding: &[],
+    },
+    Known {
+        id: "BSD-2-Clause",
+        phrases: &["redistribution and use in source and binary forms"],
+        in_title: false,
+        excluding: &["neither the name"],
+    },
+];
+
+/// A license file found at the top of an input directory
+#[derive(Debug)]
+struct LicenseFile {
+    /// Path as shown, prefixed with the input directory's name when there are several
+    path: PathBuf,
+    /// SPDX identifier, from an `SPDX-License-Identifier:` line or the text itself
+    spdx: Option<String>,
+    /// `Copyright ...` lines of the file
+    copyrights: Vec<String>,
+}
+
+/// Notice naming the licenses of the project, from the LICENSE, LICENCE,
+/// COPYING and UNLICENSE files at the top of each input directory (matched to
+/// SPDX identifiers) and the license the manifests declare (--license-notice).
+/// None if no license was found.
+pub fn notice(roots: &[PathBuf], packages: &[Package]) -> Option<String> {
+    let files = license_files(roots);
+    let declared: Vec<(&Path, &str)> = packages
+        .iter()
+        .filter_map(|package| Some((package.manifest.as_path(), package.license.as_deref()?)))
+        .collect();
+    if files.is_empty() && declared.is_empty() {
+        return None;
+    }
+
+    let mut notice = String::from(
+        "> **License notice:** the code in this document is licensed as follows.\n>\n",
+    );
+    for file in &files {
+        let _ = writeln!(
+            notice,
+            "> - `{}`: {}",
+            file.path.display(),
+            file.spdx.as_deref().unwrap_or("unrecognized license text")
+        );
+    }
+    for (manifest, license) in &declared {
+        let _ = writeln!(notice, "> - `{}` declares {}", manifest.display(), license);
+    }
+    let mut copyrights: Vec<&str> = Vec::new();
+    for line in files.iter().flat_map(|file| &file.copyrights) {
+        if !copyrights.contains(&line.as_str()) {
+            copyrights.push(line);
+        }
+    }
+    if !copyrights.is_empty() {
+        notice.push_str(">\n");
+        for line in copyrights {
+            let _ = writeln!(notice, "> {}", line);
+        }
+    }
+    notice.push('\n');
+    Some(notice)
+}
+
+fn license_files(roots: &[PathBuf]) -> Vec<LicenseFile> {
+    let mut files = Vec::new();
+    let labels = root_labels(roots);
+    for (root, label) in roots.iter().zip(&labels) {
+        let Ok(entries) = fs::read_dir(root) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && is_license_file(path))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let name = path.file_name().map(PathBuf::from).unwrap_or_default();
+            files.push(LicenseFile {
+                path: if roots.len() > 1 {
+                    label.join(name)
+                } else {
+                    name
+                },
+                spdx: identify(&text),
+                copyrights: text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| is_copyright_line(line))
+                    .map(str::to_string)
+                    .collect(),
+            });
+        }
+    }
+    files
+}
+
+/// A copyright line of the project, such as "Copyright (c) 2024 Jane Doe", not
+/// license prose, a placeholder of a license template or the license author's own notice
+fn is_copyright_line(line: &str) -> bool {
+    let Some(rest) = line
+        .strip_prefix("Copyright ")
+        .or_else(|| line.strip_prefix("COPYRIGHT "))
+    else {
+        return false;
+    };
+    let rest = rest.trim_start();
+    let dated = rest.starts_with(|c: char| c.is_ascii_digit())
+        || rest.starts_with('©')
+        || rest.to_lowercase().starts_with("(c)");
+    dated
+        && !["[yyyy]", "{yyyy}", "<year>", "Free Software Foundation"]
+            .iter()
+            .any(|placeholder| line.contains(placeholder))
+}
+
+/// LICENSE, LICENSE-MIT, LICENCE.md, COPYING.txt, UNLICENSE and the like
+pub fn is_license_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+    ["LICENSE", "LICENCE", "COPYING", "UNLICENSE"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// SPDX identifier of a license text
+fn identify(text: &str) -> Option<String> {
+    if let Some(tagged) = text.lines().find_map(|line| {
+        let (_, rest) = line.split_once("SPDX-License-Identifier:")?;
+        Some(rest.trim().trim_end_matches("*/").trim().to_string())
+    }) {
+        return Some(tagged);
+    }
+    let normalized = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let title: String = normalized.chars().take(300).collect();
+    LICENSES
+        .iter()
+        .find(|known| {
+            let searched = if known.in_title { &title } else { &normalized };
+            known.phrases.iter().all(|phrase| searched.contains(phrase))
+                && !known
+                    .excluding
+                    .iter()
+                    .any(|phrase| normalized.contains(phrase))
+        })
+        .map(|known| known.id.to_string())
+}
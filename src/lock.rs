@@ -0,0 +1,216 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::CliArgs;
+use crate::cache::sha256_hex;
+use crate::error::{ConcatError, output_error};
+
+/// Default location of the lock file, in the working directory
+pub const LOCK_FILE: &str = "md_concat.lock";
+
+const HEADER: &str = "# Written by `md_concat lock`; do not edit by hand.\n\
+                      # Runs with --frozen fail if they would select different files.\n\n";
+
+/// The selection rules of a run and the files they resolved to
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Lock {
+    pub version: u32,
+    pub filters: Filters,
+    #[serde(rename = "file", default)]
+    pub files: Vec<LockedFile>,
+}
+
+/// The options that decide which files are selected
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Filters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files_from: Option<PathBuf>,
+    pub input_dirs: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_ref: Option<String>,
+    pub extensions: Vec<String>,
+    pub exclude_dirs: Vec<String>,
+    pub only_paths: Vec<String>,
+    pub workspace_members: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests: Option<String>,
+    pub submodules: String,
+    pub gitignore: bool,
+    pub additional_gitignore: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seeds: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expand_imports: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relevant_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub semantic_query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pack: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub weight_recency: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_bytes: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LockedFile {
+    /// Path as shown in the section heading
+    pub path: PathBuf,
+    /// SHA-256 of the source file on disk
+    pub sha256: String,
+}
+
+impl Lock {
+    pub fn new(args: &CliArgs, files: &[(PathBuf, PathBuf)]) -> Self {
+        let non_empty = |values: &[String]| -> Vec<String> {
+            values.iter().filter(|v| !v.is_empty()).cloned().collect()
+        };
+        let filters = Filters {
+            files_from: args.files_from.clone(),
+            input_dirs: if args.files_from.is_some() {
+                Vec::new()
+            } else {
+                args.input_dirs.clone()
+            },
+            at_ref: args.at_ref.clone(),
+            extensions: non_empty(&args.extensions),
+            exclude_dirs: non_empty(&args.exclude_dirs),
+            only_paths: non_empty(&args.only_paths),
+            workspace_members: args.workspace_member.clone(),
+            tests: args.tests.map(|mode| value_name(&mode)),
+            submodules: value_name(&args.submodules),
+            gitignore: args.respect_gitignore,
+            additional_gitignore: args.additional_gitignore_files.clone(),
+            seeds: args.seed.clone(),
+            expand_imports: args.expand_imports,
+            relevant_to: args.relevant_to.clone(),
+            semantic_query: args.semantic_query.clone(),
+            max_tokens: args.max_tokens,
+            pack: args.max_tokens.map(|_| value_name(&args.pack)),
+            weight_recency: args.weight_recency,
+            max_output_bytes: args.max_output_bytes,
+        };
+        // Piped --stdin-section content is not part of the selection
+        let files = files
+            .iter()
+            .filter(|(_, abs_path)| {
+                args.stdin_file
+                    .as_deref()
+                    .is_none_or(|stdin_file| abs_path != stdin_file.as_ref() as &Path)
+            })
+            .map(|(rel_path, abs_path)| LockedFile {
+                path: rel_path.clone(),
+                sha256: fs::read(abs_path)
+                    .map(|bytes| sha256_hex(&bytes))
+                    .unwrap_or_default(),
+            })
+            .collect();
+        Self {
+            version: 1,
+            filters,
+            files,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConcatError> {
+        let text = fs::read_to_string(path).map_err(|source| ConcatError::InputFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|e| {
+            ConcatError::Config(format!("invalid lock file {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConcatError> {
+        let body = toml::to_string(self).expect("lock serializes");
+        fs::write(path, format!("{}{}", HEADER, body)).map_err(output_error(path))
+    }
+
+    /// Reports how `current` differs from this lock and returns the number of
+    /// added, removed and modified files
+    fn compare(&self, current: &Lock) -> usize {
+        let mut differences = 0;
+        for locked in &self.files {
+            match current.files.iter().find(|file| file.path == locked.path) {
+                None => {
+                    differences += 1;
+                    status!("removed   {}", locked.path.display());
+                }
+                Some(file) if file.sha256 != locked.sha256 => {
+                    differences += 1;
+                    status!("modified  {}", locked.path.display());
+                }
+                Some(_) => {}
+            }
+        }
+        for file in &current.files {
+            if !self.files.iter().any(|locked| locked.path == file.path) {
+                differences += 1;
+                status!("added     {}", file.path.display());
+            }
+        }
+        differences
+    }
+}
+
+/// Resolves the selection of a run with the given options and records it in `lock_file`
+pub fn lock(lock_file: &Path, options: &[String]) -> Result<(), ConcatError> {
+    let argv = ["md_concat", "--dry-run"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(options.iter().cloned());
+    let args = crate::parse_args(argv)?;
+    if args.extensions.is_empty() && args.files_from.is_none() && args.from_manifest.is_none() {
+        return Err(ConcatError::Config(
+            "lock needs extensions (after `--` or from a configuration file)".into(),
+        ));
+    }
+    let (found_files, _) = crate::prepare(&args)?;
+    Lock::new(&args, &found_files).save(lock_file)?;
+    status!(
+        "Locked {} file(s) in {}",
+        found_files.len(),
+        lock_file.display()
+    );
+    Ok(())
+}
+
+/// Fails if the selected files or their contents no longer match the lock
+/// file, or if the selection rules changed (--frozen). The order of the files
+/// is not part of the comparison.
+pub fn check_frozen(
+    lock_file: &Path,
+    args: &CliArgs,
+    files: &[(PathBuf, PathBuf)],
+) -> Result<(), ConcatError> {
+    let locked = Lock::load(lock_file)?;
+    let current = Lock::new(args, files);
+    let differences = locked.compare(&current);
+    if differences > 0 {
+        status!(
+            "{} is out of date; run `md_concat lock` to update it",
+            lock_file.display()
+        );
+        return Err(ConcatError::Drift(differences));
+    }
+    if locked.filters != current.filters {
+        return Err(ConcatError::Config(format!(
+            "the selection rules differ from {}; run `md_concat lock` to update it",
+            lock_file.display()
+        )));
+    }
+    Ok(())
+}
+
+fn value_name(value: &impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
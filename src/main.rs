@@ -1,11 +1,99 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use std::collections::HashSet;
-use std::fs::{self, File};
-use std::io::{self, BufWriter, Read, Write};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+/// Set when informational messages must stay off stdout (e.g. when listing files)
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints an informational message to stdout unless quiet mode is active
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::QUIET.load(std::sync::atomic::Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+mod anonymize;
+mod apply;
+mod assets;
+mod bucket;
+mod budget;
+mod cache;
+mod check;
+mod comments;
+mod config;
+mod dedupe;
+mod directives;
+mod document;
+mod error;
+mod exclude;
+mod explain;
+mod filelist;
+mod front_matter;
 mod gitignore;
-use gitignore::{GitignoreManager, collect_files_with_gitignore};
+mod gitref;
+mod history;
+mod imports;
+mod inject;
+mod languages;
+mod license;
+mod lock;
+mod manifest;
+mod mcp;
+mod ordering;
+mod output;
+mod overrides;
+mod paths;
+mod presets;
+mod project;
+mod public_api;
+mod redact;
+mod relevance;
+mod report;
+mod sanitize;
+mod select;
+mod semantic;
+mod serve;
+mod snapshot_diff;
+mod split;
+mod submodules;
+mod summary;
+mod symbols;
+mod template;
+mod testcode;
+mod unpack;
+mod verify;
+mod watch;
+mod workspace;
+mod writer;
+use anonymize::PathMap;
+use budget::PackStrategy;
+use cache::RunCache;
+use config::{Config, Priorities, PriorityRule};
+use error::ConcatError;
+use exclude::{ExcludeDirs, OnlyPaths};
+use gitignore::{GitignoreManager, RootLabel, collect_files_with_gitignore};
+use manifest::Manifest;
+use ordering::SortOrder;
+use output::{OutputFormat, OutputTarget};
+use paths::PathStyle;
+use presets::Preset;
+use report::{Report, Skipped};
+use split::SplitBy;
+use submodules::{SubmoduleMode, Submodules};
+use testcode::TestMode;
+use workspace::Workspace;
+use writer::{
+    FenceStyle, FileStats, HeaderField, InputEncoding, MarkdownMode, UnreadablePolicy,
+    WriteOptions, write_document, write_files,
+};
 
 /// Token counting strategies for different LLMs
 #[derive(Debug, Clone)]
@@ -38,6 +126,11 @@ impl TokenCountStrategy {
             TokenCountStrategy::WordBased => "Word-based",
         }
     }
+
+    /// Estimates the token count for the given number of characters
+    fn estimate(&self, char_count: usize) -> usize {
+        (char_count as f64 / self.chars_per_token()).ceil() as usize
+    }
 }
 
 /// Estimates tokens for multiple LLM strategies and returns a formatted report
@@ -50,14 +143,16 @@ pub fn estimate_tokens_report(char_count: usize, word_count: usize) -> String {
     ];
 
     let mut report = String::new();
-    report.push_str(&format!("=== Token Count Estimates ===\n"));
+    report.push_str("=== Token Count Estimates ===\n");
     report.push_str(&format!("Characters: {}\n", char_count));
     report.push_str(&format!("Words: {}\n\n", word_count));
 
     for strategy in strategies {
-        let estimated_tokens = char_count as f64 / strategy.chars_per_token();
-        let token_count = estimated_tokens.ceil() as usize;
-        report.push_str(&format!("{}: ~{} tokens\n", strategy.name(), token_count));
+        report.push_str(&format!(
+            "{}: ~{} tokens\n",
+            strategy.name(),
+            strategy.estimate(char_count)
+        ));
     }
 
     report
@@ -68,6 +163,11 @@ pub fn estimate_tokens_report(char_count: usize, word_count: usize) -> String {
 pub struct TokenCounter {
     pub char_count: usize,
     pub word_count: usize,
+    /// Size of the text in bytes (UTF-8)
+    pub byte_count: usize,
+    /// Whether the text so far ends inside a word, so a word split across
+    /// several calls is counted once
+    in_word: bool,
 }
 
 impl TokenCounter {
@@ -76,8 +176,15 @@ impl TokenCounter {
     }
 
     pub fn add_text(&mut self, text: &str) {
-        self.char_count += text.chars().count();
-        self.word_count += text.split_whitespace().count();
+        self.byte_count += text.len();
+        for c in text.chars() {
+            self.char_count += 1;
+            let in_word = !c.is_whitespace();
+            if in_word && !self.in_word {
+                self.word_count += 1;
+            }
+            self.in_word = in_word;
+        }
     }
 
     pub fn get_token_estimates(&self) -> String {
@@ -87,84 +194,1921 @@ impl TokenCounter {
 
 /// Estimates the number of tokens in a text string using the specified strategy
 pub fn estimate_tokens(text: &str, strategy: &TokenCountStrategy) -> usize {
-    let char_count = text.chars().count();
-    let estimated_tokens = char_count as f64 / strategy.chars_per_token();
-    estimated_tokens.ceil() as usize
+    strategy.estimate(text.chars().count())
 }
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+/// Strategy used where a single per-file estimate is shown
+pub const PER_FILE_STRATEGY: TokenCountStrategy = TokenCountStrategy::Conservative;
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct CliArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The output Markdown file path.
-    output_file: PathBuf,
+    #[arg(required_unless_present_any = ["inject", "list", "dry_run", "outputs"])]
+    output_file: Option<PathBuf>,
 
     /// Input directories to search for files (defaults to current directory if none provided).
     #[arg(long = "input-dirs", value_delimiter = ',', default_value = ".")]
     input_dirs: Vec<PathBuf>,
 
-    /// Comma-separated list of file extensions to include (e.g., "c,h,rs").
-    #[arg(long, value_delimiter = ',', required = true)]
+    /// Read the files as they are at this git revision (tag, branch or commit) from the
+    /// repository holding the input directories, instead of the working tree
+    #[arg(
+        long = "at-ref",
+        value_name = "REF",
+        conflicts_with_all = ["files_from", "from_manifest", "watch"]
+    )]
+    at_ref: Option<String>,
+
+    /// Read the files from an S3 or Google Cloud Storage bucket (`s3://bucket/prefix` or
+    /// `gs://bucket/prefix`) instead of the input directories; needs the `object-store` feature
+    #[arg(
+        long,
+        value_name = "URL",
+        conflicts_with_all = ["input_dirs", "at_ref", "files_from", "from_manifest", "watch"]
+    )]
+    remote: Option<String>,
+
+    /// Files of --at-ref or --remote, written out for the run
+    #[arg(skip)]
+    snapshot: Option<Arc<gitref::Snapshot>>,
+
+    /// Leave out test code, keep only test code, or tag test files (by path conventions such as
+    /// `tests/`, `__tests__`, `*_test.go`, and Rust `#[cfg(test)]` modules)
+    #[arg(long, value_enum)]
+    tests: Option<TestMode>,
+
+    /// Prefix headings with the input directory's name (default: only with several input directories)
+    #[arg(long = "root-label", value_enum, default_value_t = RootLabel::Auto)]
+    root_label: RootLabel,
+
+    /// Only include these members of the Cargo, npm/pnpm or Go workspace in the input directory, plus its shared root configuration
+    #[arg(
+        long = "workspace-member",
+        value_delimiter = ',',
+        conflicts_with = "files_from"
+    )]
+    workspace_member: Vec<String>,
+
+    /// Comma-separated list of file extensions to include (e.g., "c,h,rs"). Required unless set in a configuration file.
+    #[arg(long, value_delimiter = ',')]
     extensions: Vec<String>,
 
     /// Comma-separated list of directory names to exclude from search (e.g., "target,.git,build").
     #[arg(long = "exclude-dirs", value_delimiter = ',', default_value = "")]
     exclude_dirs: Vec<String>,
 
+    /// Only walk these sub-paths of each input directory (e.g. "src/,proto/,docs/adr/");
+    /// everything else is pruned without being visited
+    #[arg(
+        long = "only-paths",
+        value_delimiter = ',',
+        conflicts_with = "files_from"
+    )]
+    only_paths: Vec<String>,
+
+    /// How to collect git submodules listed in .gitmodules: include (default), exclude, or
+    /// shallow (only the files at the top of each submodule)
+    #[arg(long, value_enum, default_value_t = SubmoduleMode::Include)]
+    submodules: SubmoduleMode,
+
     /// Whether to respect .gitignore files (default: true)
     #[arg(long = "no-gitignore", action = clap::ArgAction::SetFalse)]
     respect_gitignore: bool,
 
+    /// Log to stderr every path a .gitignore rule ignores or re-includes, with the pattern and
+    /// the file and line it comes from
+    #[arg(long = "debug-ignore", conflicts_with = "respect_gitignore")]
+    debug_ignore: bool,
+
+    /// Whether to honor `md_concat:ignore-file` and `md_concat:begin-ignore` / `end-ignore`
+    /// comments in the files (default: true)
+    #[arg(long = "no-directives", action = clap::ArgAction::SetFalse)]
+    directives: bool,
+
     /// Additional gitignore files to consider
     #[arg(long = "additional-gitignore", value_delimiter = ',')]
     additional_gitignore_files: Vec<PathBuf>,
+
+    /// Keep an incremental cache so unchanged runs skip rewriting the output
+    #[arg(long)]
+    cache: bool,
+
+    /// Cache file location (defaults to "<OUTPUT_FILE>.cache.json")
+    #[arg(long = "cache-file", requires = "cache")]
+    cache_file: Option<PathBuf>,
+
+    /// Replace the region between md_concat markers in an existing Markdown file instead of writing a new one
+    #[arg(long, value_name = "FILE", conflicts_with = "output_file")]
+    inject: Option<PathBuf>,
+
+    /// Split the document into numbered parts of at most about N tokens ("out.md" becomes
+    /// "out.part1.md", ...); later parts start with a recap of the others
+    #[arg(
+        long = "split-tokens",
+        value_name = "N",
+        conflicts_with_all = ["inject", "outputs", "front_matter", "cache"]
+    )]
+    split_tokens: Option<usize>,
+
+    /// Write one document per top-level directory or language next to the output file ("out.md"
+    /// becomes "out.backend.md" or "out.rust.md", ...) and make the output file an index
+    /// linking to them
+    #[arg(
+        long = "split-by",
+        value_enum,
+        value_name = "GROUP",
+        conflicts_with_all = [
+            "split_tokens",
+            "summary_footer",
+            "inject",
+            "outputs",
+            "front_matter",
+            "cache"
+        ]
+    )]
+    split_by: Option<SplitBy>,
+
+    /// Append a "Symbol Index" section listing the functions, methods and types defined in
+    /// Rust, Python, JavaScript, TypeScript and Go files with their file and line
+    #[arg(long = "symbol-index")]
+    symbol_index: bool,
+
+    /// Only include these functions, methods and types (with their doc comments), given as
+    /// "Type::method" or plain names; files defining none of them are left out
+    #[arg(long = "extract-symbols", value_delimiter = ',', value_name = "NAMES")]
+    extract_symbols: Vec<String>,
+
+    /// Reduce Rust files to their public interface: `pub` items with docs and signatures,
+    /// function bodies elided; files without public items are left out
+    #[arg(long = "public-api", conflicts_with = "extract_symbols")]
+    public_api: bool,
+
+    /// Only include the functions and methods reachable through calls from this entry point
+    /// (e.g. "main" or "Server::run"); files with none of them are left out
+    #[arg(
+        long = "slice-from",
+        value_name = "SYMBOL",
+        conflicts_with_all = ["extract_symbols", "public_api"]
+    )]
+    slice_from: Option<String>,
+
+    /// Start the document with a notice naming the project's licenses, detected from its
+    /// LICENSE/COPYING files (as SPDX identifiers) and manifests, with its copyright lines
+    #[arg(long = "license-notice", conflicts_with = "anonymize_paths")]
+    license_notice: bool,
+
+    /// Start the document with a "Project Overview" section: name, version, description and
+    /// dependencies from the Cargo.toml, package.json, pyproject.toml or go.mod of each input
+    /// directory
+    #[arg(long = "project-summary", conflicts_with = "anonymize_paths")]
+    project_summary: bool,
+
+    /// Append a "Dependencies" table of the direct dependencies and their versions from the same
+    /// manifests as --project-summary
+    #[arg(long = "deps-appendix", conflicts_with = "anonymize_paths")]
+    deps_appendix: bool,
+
+    /// Append an "Assets (not included)" table of the images, archives, binaries and other
+    /// files whose contents are left out, with their types and sizes
+    #[arg(long = "assets-inventory", conflicts_with = "anonymize_paths")]
+    assets_inventory: bool,
+
+    /// Start the document with a section listing the last N commits: hash, date, author,
+    /// subject and the files each touched
+    #[arg(
+        long = "git-log-summary",
+        value_name = "N",
+        conflicts_with = "anonymize_paths"
+    )]
+    git_log_summary: Option<usize>,
+
+    /// List the last N commits that changed each file (date, hash and subject) under its
+    /// heading
+    #[arg(long = "file-history", value_name = "N")]
+    file_history: Option<usize>,
+
+    /// Show each file's estimated tokens in its heading: "## src/main.rs (≈1,245 tokens)"
+    #[arg(long = "annotate-tokens")]
+    annotate_tokens: bool,
+
+    /// Warn on stderr about files estimated above N tokens, and mark their headings
+    #[arg(long = "warn-file-tokens", value_name = "N")]
+    warn_file_tokens: Option<usize>,
+
+    /// End the document with a summary: file count, a tree of the files, tokens per directory
+    /// (see --report-depth) and the options of the run
+    #[arg(
+        long = "summary-footer",
+        conflicts_with_all = ["split_tokens", "anonymize_paths"]
+    )]
+    summary_footer: bool,
+
+    /// Start the document with a YAML front matter block describing the run (time, version, roots, filters, totals)
+    #[arg(long = "front-matter", conflicts_with_all = ["inject", "anonymize_paths"])]
+    front_matter: bool,
+
+    /// Additional file to write in the same run, as FILE or FORMAT:FILE with FORMAT md, xml or json (inferred from the extension); repeatable
+    #[arg(
+        long = "output",
+        value_name = "[FORMAT:]FILE",
+        conflicts_with = "dry_run"
+    )]
+    outputs: Vec<OutputTarget>,
+
+    /// Overwrite the output file if it already exists
+    #[arg(long)]
+    force: bool,
+
+    /// Keep the previous output file by renaming it before writing ("bak" or "timestamp")
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "bak")]
+    backup: Option<BackupMode>,
+
+    /// Collect files and estimate tokens (per file and in total) without writing anything
+    #[arg(long = "dry-run", conflicts_with = "watch")]
+    dry_run: bool,
+
+    /// Print only the relative paths that would be included, one per line
+    #[arg(long, conflicts_with_all = ["dry_run", "watch", "inject"])]
+    list: bool,
+
+    /// Read the exact list of files to include from FILE ("-" for stdin) instead of searching directories
+    #[arg(long = "files-from", value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Add the content piped on stdin (logs, stack traces) as a last section headed by LABEL,
+    /// e.g. `--stdin-section error.log`
+    #[arg(long = "stdin-section", value_name = "LABEL")]
+    stdin_section: Option<PathBuf>,
+
+    /// Content of --stdin-section, read once for the run
+    #[arg(skip)]
+    stdin_file: Option<Arc<tempfile::TempPath>>,
+
+    /// Fail if the selected files or their contents differ from the lock file written by
+    /// `md_concat lock` (default "md_concat.lock")
+    #[arg(
+        long,
+        value_name = "LOCK_FILE",
+        num_args = 0..=1,
+        default_missing_value = lock::LOCK_FILE
+    )]
+    frozen: Option<PathBuf>,
+
+    /// Re-collect exactly the files recorded in a previous run's manifest, in their recorded
+    /// order, resolved against the input directories; missing files are an error
+    #[arg(
+        long = "from-manifest",
+        value_name = "MANIFEST",
+        conflicts_with_all = ["files_from", "only_paths", "workspace_member"]
+    )]
+    from_manifest: Option<PathBuf>,
+
+    /// Directory that relative --files-from entries are resolved against
+    #[arg(
+        long = "files-root",
+        value_name = "DIR",
+        default_value = ".",
+        requires = "files_from"
+    )]
+    files_root: PathBuf,
+
+    /// Order of files in the output (defaults to path order; --files-from keeps the listed order)
+    #[arg(long, value_enum, visible_alias = "order")]
+    sort: Option<SortOrder>,
+
+    /// Reverse the sort order
+    #[arg(long)]
+    reverse: bool,
+
+    /// Prompt-cache-friendly layout: least recently modified files first, and no
+    /// run-specific metadata (such as mtime) in the output
+    #[arg(long = "cache-stable", conflicts_with_all = ["sort", "reverse"])]
+    cache_stable: bool,
+
+    /// Remove a leading directory from the shown paths: "auto" for the longest one all
+    /// files share, or a PATH stripped from the files below it
+    #[arg(long = "strip-prefix", value_name = "auto|PATH")]
+    strip_prefix: Option<String>,
+
+    /// How to show file paths in headings and listings: relative (to the input directory),
+    /// absolute, basename, or from-root (relative to the git repository root)
+    #[arg(long = "path-style", value_enum, default_value_t = PathStyle::Relative)]
+    path_style: PathStyle,
+
+    /// Show relative paths with the platform's separator (`\` on Windows) instead of `/`
+    #[arg(long = "native-separators", conflicts_with = "deterministic")]
+    native_separators: bool,
+
+    /// Ask for confirmation before including more than this many files (0 disables the check)
+    #[arg(long = "max-files", value_name = "N", default_value_t = 1000)]
+    max_files: usize,
+
+    /// Ask for confirmation before including files that total more than this many bytes
+    /// (0 disables the check)
+    #[arg(
+        long = "max-input-bytes",
+        value_name = "N",
+        default_value_t = 50_000_000
+    )]
+    max_input_bytes: u64,
+
+    /// Include a large selection without asking (see --max-files and --max-input-bytes)
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Byte-identical output across platforms: `/` separators, byte-wise path order, and
+    /// no timestamps or absolute paths
+    #[arg(long, conflicts_with = "cache_stable")]
+    deterministic: bool,
+
+    /// Token budget: lowest-priority files are dropped until the estimate fits
+    #[arg(long = "max-tokens", value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// Start the selection from these files (relative paths, comma-separated) instead of
+    /// keeping every file found; see --expand-imports
+    #[arg(long, value_delimiter = ',', value_name = "PATH")]
+    seed: Vec<PathBuf>,
+
+    /// Also keep the files the --seed files import or include, following imports up to
+    /// N hops (default: 0, only the seeds)
+    #[arg(long = "expand-imports", value_name = "N", requires = "seed")]
+    expand_imports: Option<usize>,
+
+    /// Keep only the files matching these keywords (BM25 over paths and contents), most
+    /// relevant first; combine with --max-tokens to keep the top files that fit
+    #[arg(long = "relevant-to", value_name = "QUERY")]
+    relevant_to: Option<String>,
+
+    /// Rank the files by meaning rather than keywords, with a local embedding model over
+    /// chunks of each file; combine with --max-tokens to keep the top files that fit.
+    /// Needs a build with `--features semantic`
+    #[arg(
+        long = "semantic-query",
+        value_name = "QUERY",
+        conflicts_with = "relevant_to"
+    )]
+    semantic_query: Option<String>,
+
+    /// Where --semantic-query keeps chunk embeddings between runs
+    #[arg(long = "semantic-cache", value_name = "FILE", default_value = semantic::EMBEDDINGS_FILE)]
+    semantic_cache: PathBuf,
+
+    /// How to fit the selection into --max-tokens: truncate (drop the lowest-priority files) or
+    /// greedy (keep the most priority weight per token)
+    #[arg(long, value_enum, default_value_t = PackStrategy::Truncate)]
+    pack: PackStrategy,
+
+    /// When pruning to --max-tokens, favour files changed often and recently in git history:
+    /// up to 10 is added to each file's priority weight
+    #[arg(long = "weight-recency")]
+    weight_recency: bool,
+
+    /// Size limit for the output, e.g. "10MB" or "512KiB": files past the point where the
+    /// document would exceed it are left out (with --strict, the run fails instead)
+    #[arg(long = "max-output-bytes", value_name = "SIZE", value_parser = budget::parse_size)]
+    max_output_bytes: Option<usize>,
+
+    /// Comma-separated relative paths that always appear first, in the given order
+    #[arg(long, value_delimiter = ',')]
+    pin: Vec<PathBuf>,
+
+    /// Abort with an error if any file cannot be opened or read
+    #[arg(long, conflicts_with = "skip_unreadable")]
+    strict: bool,
+
+    /// Leave out files that cannot be opened or read instead of embedding an error note
+    #[arg(long = "skip-unreadable")]
+    skip_unreadable: bool,
+
+    /// How to decode files that are not valid UTF-8
+    #[arg(long, value_enum, default_value_t = InputEncoding::Utf8)]
+    encoding: InputEncoding,
+
+    /// Keep UTF-8 byte order marks at the start of files instead of stripping them
+    #[arg(long = "keep-bom")]
+    keep_bom: bool,
+
+    /// Prefix each line of file content with its line number
+    #[arg(long = "line-numbers")]
+    line_numbers: bool,
+
+    /// Remove comments (doc comments included) from Rust, Python, JavaScript, TypeScript and Go
+    /// files
+    #[arg(long = "strip-comments")]
+    strip_comments: bool,
+
+    /// Keep only the first N lines of each file, noting how many were left out
+    #[arg(long = "max-lines-per-file", value_name = "N")]
+    max_lines_per_file: Option<usize>,
+
+    /// How to write Markdown files: fence (in a code block, the default) or passthrough
+    /// (as-is, with their headings demoted below the file heading)
+    #[arg(long, value_enum, default_value_t = MarkdownMode::Fence)]
+    md: MarkdownMode,
+
+    /// Heading level of each file's heading (1-6), to nest the document under existing
+    /// headings of a larger one
+    #[arg(
+        long = "heading-level",
+        value_name = "N",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u8).range(1..=6)
+    )]
+    heading_level: u8,
+
+    /// Character of the code fences around file contents: backtick or tilde
+    #[arg(long, value_enum, default_value_t = FenceStyle::Backtick)]
+    fence: FenceStyle,
+
+    /// Metadata to list below each file heading (comma-separated)
+    #[arg(long = "header-fields", value_enum, value_delimiter = ',')]
+    header_fields: Vec<HeaderField>,
+
+    /// Fence languages for extensions, overriding the built-in table (e.g. "vue=html,tpl=jinja")
+    #[arg(
+        long = "lang-map",
+        value_name = "EXT=LANGUAGE",
+        value_delimiter = ',',
+        value_parser = languages::parse_mapping
+    )]
+    lang_map: Vec<(String, String)>,
+
+    /// File whose contents are placed before the concatenated files (can be repeated)
+    #[arg(long = "prepend-file", value_name = "FILE")]
+    prepend_file: Vec<PathBuf>,
+
+    /// Text placed before the concatenated files, after any --prepend-file (can be repeated)
+    #[arg(long = "prepend-text", value_name = "TEXT")]
+    prepend_text: Vec<String>,
+
+    /// File whose contents are placed after the concatenated files (can be repeated)
+    #[arg(long = "append-file", value_name = "FILE")]
+    append_file: Vec<PathBuf>,
+
+    /// Text placed after the concatenated files, after any --append-file (can be repeated)
+    #[arg(long = "append-text", value_name = "TEXT")]
+    append_text: Vec<String>,
+
+    /// Replace secrets (API keys, private keys, tokens, .env values) with [REDACTED:<kind>]
+    #[arg(long = "redact-secrets")]
+    redact_secrets: bool,
+
+    /// TOML file of named regex rules whose matches are replaced (see README)
+    #[arg(long = "redact-rules", value_name = "FILE")]
+    redact_rules: Option<PathBuf>,
+
+    /// Write files whose content duplicates an earlier file as a short reference
+    #[arg(long = "dedupe-content")]
+    dedupe_content: bool,
+
+    /// Write files at least this similar (0-1) to an earlier file as a diff against it
+    #[arg(long = "dedupe-similar", value_name = "THRESHOLD", value_parser = dedupe::parse_threshold)]
+    dedupe_similar: Option<f64>,
+
+    /// Neutralize instruction-like phrases in file contents (e.g. "ignore previous instructions")
+    #[arg(long)]
+    sanitize: bool,
+
+    /// Write "<OUTPUT_FILE>.manifest.json" listing each included file's sha256, size and tokens
+    #[arg(long)]
+    manifest: bool,
+
+    /// Replace directory and file names with stable pseudonyms (dir_01/file_03.rs)
+    #[arg(long = "anonymize-paths")]
+    anonymize_paths: bool,
+
+    /// Where the pseudonym-to-path mapping is kept (defaults to "<OUTPUT_FILE>.paths.json")
+    #[arg(
+        long = "anonymize-map",
+        value_name = "FILE",
+        requires = "anonymize_paths"
+    )]
+    anonymize_map: Option<PathBuf>,
+
+    /// Task or question appended as a final "Task" section
+    #[arg(long, value_name = "TEXT")]
+    task: Option<String>,
+
+    /// Tera template file rendered in place of each `## path` heading
+    #[arg(long = "header-template", value_name = "FILE")]
+    header_template: Option<PathBuf>,
+
+    /// Tera template file for the whole document; `{{ content }}` holds the file sections
+    #[arg(long = "document-template", value_name = "FILE")]
+    document_template: Option<PathBuf>,
+
+    /// Hand-pick files from the candidates with an interactive fuzzy finder
+    #[arg(long, conflicts_with = "watch")]
+    select: bool,
+
+    /// Separate listed paths (--list and --files-from) with NUL instead of newline
+    #[arg(short = '0', long = "null")]
+    null: bool,
+
+    /// Print additional reports after the run (dirs: bytes, tokens and file counts per directory;
+    /// skipped: candidates left out and why)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    report: Vec<Report>,
+
+    /// Directory depth that --report dirs aggregates at
+    #[arg(long = "report-depth", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    report_depth: u16,
+
+    /// Keep running and regenerate the output whenever matching files change
+    #[arg(long)]
+    watch: bool,
+
+    /// Configuration file to use instead of ./md_concat.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Ignore all configuration files
+    #[arg(long = "no-config", conflicts_with_all = ["config", "profile"])]
+    no_config: bool,
+
+    /// Built-in set of extensions and excluded directories for an ecosystem
+    #[arg(long, value_enum)]
+    preset: Option<Preset>,
+
+    /// Named profile from the configuration file's [profiles] table
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Priority rules from the configuration file
+    #[arg(skip)]
+    priority: Vec<PriorityRule>,
+
+    /// Configuration files the options were read from, watched by --watch
+    #[arg(skip)]
+    config_sources: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Recreate the files of a generated document
+    Unpack {
+        /// Markdown file produced by md_concat
+        input: PathBuf,
+        /// Directory to recreate the files in
+        #[arg(long, default_value = ".")]
+        dest: PathBuf,
+        /// Overwrite files that already exist
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write the sections of an edited document back to the source files
+    Apply {
+        /// Edited Markdown file in md_concat's layout
+        input: PathBuf,
+        /// Directory the section paths are relative to
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+        /// Only show the changes
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+        /// Apply without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Compare two generated documents file by file
+    Diff {
+        /// Older document
+        old: PathBuf,
+        /// Newer document
+        new: PathBuf,
+    },
+    /// Serve packing tools to other programs
+    #[command(group(clap::ArgGroup::new("mode").required(true).args(["mcp", "http"])))]
+    Serve {
+        /// Speak the Model Context Protocol over stdin/stdout
+        #[arg(long)]
+        mcp: bool,
+        /// Serve an HTTP API on this address (e.g. 127.0.0.1:8765)
+        #[arg(long, value_name = "ADDR")]
+        http: Option<String>,
+        /// Allow --http to listen on a non-loopback address. The API has no
+        /// authentication, so anyone who can reach it can read the project.
+        #[arg(long = "allow-remote", requires = "http")]
+        allow_remote: bool,
+    },
+    /// Fail if the configured document would exceed a token budget (for CI)
+    Check {
+        /// Largest acceptable document, in estimated tokens
+        #[arg(long = "max-tokens")]
+        max_tokens: usize,
+        /// Configuration profile that selects the files
+        #[arg(long)]
+        profile: Option<String>,
+        /// Configuration file to use instead of the discovered ones
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+    },
+    /// Show why a path is or isn't included, rule by rule
+    Explain {
+        /// File to explain
+        path: PathBuf,
+        /// Options of the run to explain, after `--` (configuration files apply as usual)
+        #[arg(last = true, value_name = "OPTIONS")]
+        options: Vec<String>,
+    },
+    /// Record the selection rules and the resolved files with their hashes in a lock file
+    Lock {
+        /// Lock file to write
+        #[arg(long = "lock-file", default_value = lock::LOCK_FILE)]
+        lock_file: PathBuf,
+        /// Options of the run to lock, after `--` (configuration files apply as usual)
+        #[arg(last = true, value_name = "OPTIONS")]
+        options: Vec<String>,
+    },
+    /// Check whether a generated document still matches the source files
+    Verify {
+        /// Markdown file produced by md_concat
+        input: PathBuf,
+        /// Directory the section paths are relative to
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+    },
+}
+
+/// How an existing output file is preserved before being replaced
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum BackupMode {
+    /// Rename to "<OUTPUT_FILE>.bak"
+    Bak,
+    /// Rename to "<OUTPUT_FILE>.<YYYYMMDD-HHMMSS>.bak"
+    Timestamp,
+}
+
+impl CliArgs {
+    /// Returns the file that this run writes to, if any; with several, the first one
+    fn output_path(&self) -> Option<&Path> {
+        self.inject
+            .as_deref()
+            .or(self.output_file.as_deref())
+            .or_else(|| self.outputs.first().map(|target| target.path.as_path()))
+    }
+
+    /// Every file written with a format, starting with the document itself
+    fn output_targets(&self) -> Vec<OutputTarget> {
+        let document = self.output_file.as_ref().map(|path| OutputTarget {
+            format: OutputFormat::Markdown,
+            path: path.clone(),
+        });
+        document.into_iter().chain(self.outputs.clone()).collect()
+    }
+
+    /// Returns "<OUTPUT_FILE><suffix>", the default location of files written alongside the output
+    fn sidecar_path(&self, suffix: &str) -> Option<PathBuf> {
+        let mut name = self.output_path()?.as_os_str().to_os_string();
+        name.push(suffix);
+        Some(PathBuf::from(name))
+    }
+
+    /// Returns the path mapping location if paths are anonymized
+    fn anonymize_map_path(&self) -> Option<PathBuf> {
+        if !self.anonymize_paths {
+            return None;
+        }
+        self.anonymize_map
+            .clone()
+            .or_else(|| self.sidecar_path(".paths.json"))
+    }
+
+    /// Git revision the files are read at
+    fn revision(&self) -> &str {
+        self.at_ref.as_deref().unwrap_or("HEAD")
+    }
+
+    /// Where the files of an input directory are read from: the directory itself, or
+    /// its copy from --at-ref or --remote
+    fn input_root(&self, input_dir: &Path) -> PathBuf {
+        match self.snapshot {
+            Some(ref snapshot) => snapshot.root_for(input_dir),
+            None => input_dir.to_path_buf(),
+        }
+    }
+
+    /// Returns the cache file location if caching is enabled
+    fn cache_path(&self) -> Option<PathBuf> {
+        if !self.cache {
+            return None;
+        }
+        self.cache_file
+            .clone()
+            .or_else(|| self.sidecar_path(".cache.json"))
+    }
+
+    /// Returns the manifest location if a manifest is requested
+    fn manifest_path(&self) -> Option<PathBuf> {
+        if !self.manifest {
+            return None;
+        }
+        self.sidecar_path(".manifest.json")
+    }
+
+    /// Every file a run writes apart from split documents: the outputs, the
+    /// sidecar files and the caches
+    fn written_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .output_targets()
+            .into_iter()
+            .map(|target| target.path)
+            .collect();
+        files.extend(self.inject.clone());
+        files.extend(self.cache_path());
+        files.extend(self.manifest_path());
+        files.extend(self.anonymize_map_path());
+        if self.semantic_query.is_some() {
+            files.push(self.semantic_cache.clone());
+        }
+        files
+    }
+}
+
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+fn try_main() -> Result<(), ConcatError> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let matches = CliArgs::command().get_matches_from(&argv);
+    let mut args = CliArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match args.command {
+        Some(Command::Completions { shell }) => {
+            let mut command = CliArgs::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Command::Unpack {
+            ref input,
+            ref dest,
+            force,
+        }) => return unpack::unpack(input, dest, force),
+        Some(Command::Apply {
+            ref input,
+            ref root,
+            dry_run,
+            yes,
+        }) => return apply::apply(input, root, dry_run, yes),
+        Some(Command::Verify {
+            ref input,
+            ref root,
+        }) => return verify::verify(input, root),
+        Some(Command::Diff { ref old, ref new }) => return snapshot_diff::diff(old, new),
+        Some(Command::Check {
+            max_tokens,
+            ref profile,
+            ref config,
+        }) => return check::check(max_tokens, profile.as_deref(), config.as_deref()),
+        Some(Command::Serve {
+            http: Some(ref addr),
+            allow_remote,
+            ..
+        }) => return serve::http(addr, allow_remote),
+        Some(Command::Serve { .. }) => {
+            QUIET.store(true, Ordering::Relaxed);
+            return mcp::serve();
+        }
+        Some(Command::Explain {
+            ref path,
+            ref options,
+        }) => return explain::explain(path, options),
+        Some(Command::Lock {
+            ref lock_file,
+            ref options,
+        }) => return lock::lock(lock_file, options),
+        None => {}
+    }
+
+    if args.list {
+        QUIET.store(true, Ordering::Relaxed);
+    }
+
+    apply_config(&mut args, &matches, &argv)?;
+    open_snapshot(&mut args)?;
+    read_stdin_section(&mut args)?;
+
+    if args.extensions.is_empty() && args.files_from.is_none() && args.from_manifest.is_none() {
+        CliArgs::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--extensions is required (on the command line or in a configuration file)",
+            )
+            .exit();
+    }
+
+    if args.watch {
+        watch::watch(&args, &argv)
+    } else {
+        run(&args)
+    }
+}
+
+/// Files chosen for a run, in output order
+struct Selection {
+    files: Vec<(PathBuf, PathBuf)>,
+    /// Reported once the output is written, if files had to be dropped to fit --max-tokens
+    budget_exceeded: Option<ConcatError>,
+    /// Directory removed from the front of the shown paths by --strip-prefix
+    stripped_prefix: Option<PathBuf>,
+    /// Candidates left out, for --report skipped
+    skipped: Vec<Skipped>,
+}
+
+/// Keeps the files for which `keep` holds, noting the others as skipped for `reason`
+fn retain_files(
+    found_files: &mut Vec<(PathBuf, PathBuf)>,
+    skipped: &mut Vec<Skipped>,
+    reason: &str,
+    mut keep: impl FnMut(&Path, &Path) -> bool,
+) {
+    found_files.retain(|(rel_path, abs_path)| {
+        let kept = keep(rel_path, abs_path);
+        if !kept {
+            skipped.push(Skipped::new(rel_path.clone(), reason));
+        }
+        kept
+    });
+}
+
+/// Orders the files by descending score, leaving out those for which `unmatched`
+/// gives a reason
+fn rank_files(
+    found_files: &mut Vec<(PathBuf, PathBuf)>,
+    skipped: &mut Vec<Skipped>,
+    scores: Vec<f64>,
+    unmatched: impl Fn(f64) -> Option<&'static str>,
+) {
+    let mut ranked: Vec<((PathBuf, PathBuf), f64)> = found_files.drain(..).zip(scores).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    for (file, score) in ranked {
+        match unmatched(score) {
+            Some(reason) => skipped.push(Skipped::new(file.0, reason)),
+            None => found_files.push(file),
+        }
+    }
+}
+
+/// Collects, orders and (with --select or --max-tokens) narrows down the files
+/// to concatenate
+fn select_files(
+    args: &CliArgs,
+    write_options: &WriteOptions,
+    priorities: &Priorities,
+) -> Result<Selection, ConcatError> {
+    let output_file = args.output_path();
+    let (mut found_files, mut skipped) = match (&args.files_from, &args.from_manifest) {
+        (Some(source), _) => (
+            filelist::read_file_list(source, &args.files_root, args.null)?,
+            Vec::new(),
+        ),
+        (None, Some(manifest)) => (
+            Manifest::load(manifest)?.recorded_files(&args.input_dirs)?,
+            Vec::new(),
+        ),
+        (None, None) => collect_from_input_dirs(args)?,
+    };
+    if !args.workspace_member.is_empty() {
+        let before = found_files.clone();
+        restrict_to_workspace_members(&mut found_files, args)?;
+        let kept: HashSet<&PathBuf> = found_files.iter().map(|(_, abs_path)| abs_path).collect();
+        skipped.extend(
+            before
+                .into_iter()
+                .filter(|(_, abs_path)| !kept.contains(abs_path))
+                .map(|(rel_path, _)| {
+                    Skipped::new(rel_path, "outside the selected workspace members")
+                }),
+        );
+    }
+    match args.tests {
+        Some(TestMode::Exclude) => {
+            retain_files(
+                &mut found_files,
+                &mut skipped,
+                "test file (--tests exclude)",
+                |rel_path, _| !testcode::is_test_path(rel_path),
+            );
+        }
+        Some(TestMode::Only) => retain_files(
+            &mut found_files,
+            &mut skipped,
+            "not test code (--tests only)",
+            |rel_path, abs_path| {
+                testcode::is_test_path(rel_path)
+                    || (rel_path.extension().is_some_and(|ext| ext == "rs")
+                        && fs::read_to_string(abs_path)
+                            .is_ok_and(|text| text.contains("#[cfg(test)]")))
+            },
+        ),
+        Some(TestMode::Tag) | None => {}
+    }
+    if args.directives {
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            "md_concat:ignore-file directive",
+            |_, abs_path| {
+                !fs::read(abs_path)
+                    .is_ok_and(|bytes| directives::ignores_file(&String::from_utf8_lossy(&bytes)))
+            },
+        );
+    }
+    if !args.extract_symbols.is_empty() {
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            "defines none of the --extract-symbols",
+            |rel_path, abs_path| {
+                fs::read_to_string(abs_path).is_ok_and(|text| {
+                    symbols::extract(rel_path, &text, &args.extract_symbols).is_some()
+                })
+            },
+        );
+    }
+    if args.public_api {
+        // Non-Rust files are kept whole; Rust files without public items are left out
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            "no public items (--public-api)",
+            |rel_path, abs_path| {
+                rel_path.extension().is_none_or(|ext| ext != "rs")
+                    || fs::read_to_string(abs_path)
+                        .is_ok_and(|text| public_api::summarize(&text).is_some())
+            },
+        );
+    }
+    if !args.seed.is_empty() {
+        let hops = args.expand_imports.unwrap_or(0);
+        let seeds = args
+            .seed
+            .iter()
+            .map(|seed| {
+                let seed = imports::normalize(seed);
+                found_files
+                    .iter()
+                    .position(|(rel_path, _)| imports::normalize(rel_path) == seed)
+                    .ok_or_else(|| {
+                        ConcatError::Config(format!(
+                            "seed {} is not among the selected files",
+                            seed.display()
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<usize>, ConcatError>>()?;
+        let mut reached = imports::reachable(&found_files, &seeds, hops).into_iter();
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            "not imported by the --seed files",
+            |_, _| reached.next().unwrap_or(false),
+        );
+        status!(
+            "{} file(s) within {} import hop(s) of the seed files",
+            found_files.len(),
+            hops
+        );
+    }
+    if let Some(ref entry) = args.slice_from {
+        let sliced = writer::slice_files(&found_files, entry).ok_or_else(|| {
+            ConcatError::Config(format!("--slice-from: no function named {}", entry))
+        })?;
+        let mut sliced = sliced.into_iter();
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            "no function reachable from --slice-from",
+            |_, _| sliced.next().is_some_and(|names| !names.is_empty()),
+        );
+        status!(
+            "{} file(s) with functions reachable from {}",
+            found_files.len(),
+            entry
+        );
+    }
+    if let Some(ref query) = args.relevant_to {
+        // Most relevant first; files sharing no word with the query are left out
+        let scores = relevance::scores(&found_files, query);
+        rank_files(&mut found_files, &mut skipped, scores, |score| {
+            (score <= 0.0).then_some("no match for --relevant-to")
+        });
+        status!("{} file(s) match \"{}\"", found_files.len(), query);
+    }
+    if let Some(ref query) = args.semantic_query {
+        let scores = semantic::scores(&found_files, query, &args.semantic_cache)?;
+        rank_files(&mut found_files, &mut skipped, scores, |_| None);
+    }
+    if let (Some(max_tokens), true) = (
+        args.max_tokens,
+        args.relevant_to.is_some() || args.semantic_query.is_some(),
+    ) {
+        // With a budget, the most relevant files that fit are kept
+        let stats = write_files(
+            &mut io::sink(),
+            &found_files,
+            &mut TokenCounter::new(),
+            write_options,
+        )?;
+        for file in budget::fill_in_order(&mut found_files, &stats, max_tokens) {
+            let reason = format!(
+                "less relevant files fill --max-tokens (~{} tokens)",
+                file.tokens()
+            );
+            skipped.push(Skipped::new(file.rel_path, reason));
+        }
+    }
+    if !args.native_separators {
+        for (rel_path, _) in found_files.iter_mut().filter(|(rel, _)| rel.is_relative()) {
+            *rel_path = paths::portable(rel_path);
+        }
+    }
+    if args.deterministic {
+        make_deterministic(&mut found_files, args)?;
+    }
+
+    // Never concatenate a previous output (or the files written alongside it) into itself
+    let generated = [
+        output_file.map(Path::to_path_buf),
+        args.cache_path(),
+        args.anonymize_map_path(),
+        args.manifest_path(),
+        args.semantic_query
+            .as_ref()
+            .map(|_| args.semantic_cache.clone()),
+    ];
+    let targets = args.outputs.iter().map(|target| &target.path);
+    for generated_file in generated.iter().flatten().chain(targets) {
+        if let Some(canonical) = canonicalize_output(generated_file) {
+            let before = found_files.len();
+            retain_files(
+                &mut found_files,
+                &mut skipped,
+                "output of this run",
+                |_, abs_path| abs_path != canonical,
+            );
+            if found_files.len() != before {
+                status!("Skipping output file {}", generated_file.display());
+            }
+        }
+    }
+
+    let split_flag = match (args.split_tokens, args.split_by) {
+        (Some(_), _) => Some("--split-tokens"),
+        (None, Some(_)) => Some("--split-by"),
+        (None, None) => None,
+    };
+    if let (Some(output_file), Some(flag)) = (output_file, split_flag) {
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            &format!("output of this run ({})", flag),
+            |_, abs_path| !split::is_group_document(output_file, abs_path),
+        );
+    }
+
+    if found_files.is_empty() {
+        return Err(ConcatError::NoFilesMatched);
+    }
+
+    if args.cache_stable {
+        // Stable files form a shared prefix across runs; volatile files go last
+        ordering::sort_files(&mut found_files, SortOrder::Mtime, false);
+    } else if args.sort.is_some() || args.reverse {
+        ordering::sort_files(
+            &mut found_files,
+            args.sort.unwrap_or(SortOrder::Path),
+            args.reverse,
+        );
+    }
+
+    if !priorities.is_empty() {
+        ordering::sort_by_priority(&mut found_files, priorities);
+    }
+
+    if !args.pin.is_empty() {
+        ordering::pin_files(&mut found_files, &args.pin);
+    }
+
+    if args.select {
+        let candidates: Vec<String> = found_files
+            .iter()
+            .map(|(rel_path, _)| rel_path.display().to_string())
+            .collect();
+        let chosen = select::fuzzy_select(&candidates)?.ok_or(ConcatError::Cancelled)?;
+        let chosen: HashSet<usize> = chosen.into_iter().collect();
+        let mut index = 0;
+        retain_files(
+            &mut found_files,
+            &mut skipped,
+            "not chosen in --select",
+            |_, _| {
+                index += 1;
+                chosen.contains(&(index - 1))
+            },
+        );
+        status!("Selected {} file(s)", found_files.len());
+    }
+
+    let mut budget_exceeded = None;
+    if let Some(max_tokens) = args.max_tokens {
+        let stats = write_files(
+            &mut io::sink(),
+            &found_files,
+            &mut TokenCounter::new(),
+            write_options,
+        )?;
+        let mut weights: Vec<i64> = found_files
+            .iter()
+            .map(|(rel_path, _)| priorities.weight(rel_path))
+            .collect();
+        if args.weight_recency {
+            for (weight, bonus) in weights
+                .iter_mut()
+                .zip(history::recency_weights(&found_files, args.revision()))
+            {
+                *weight += bonus;
+            }
+        }
+        let pruned =
+            budget::prune_to_budget(&mut found_files, &stats, &weights, max_tokens, args.pack);
+        if !pruned.is_empty() {
+            budget_exceeded = Some(ConcatError::BudgetExceeded {
+                budget: max_tokens,
+                dropped: pruned.len(),
+            });
+            let pruned_tokens: usize = pruned.iter().map(FileStats::tokens).sum();
+            status!(
+                "Token budget of {} exceeded: dropped {} file(s) (~{} tokens)",
+                max_tokens,
+                pruned.len(),
+                pruned_tokens
+            );
+            for file in &pruned {
+                status!(
+                    "  dropped {} (~{} tokens)",
+                    file.rel_path.display(),
+                    file.tokens()
+                );
+                skipped.push(Skipped::new(
+                    file.rel_path.clone(),
+                    format!("dropped to fit --max-tokens (~{} tokens)", file.tokens()),
+                ));
+            }
+        }
+    }
+
+    if let Some(max_bytes) = args.max_output_bytes {
+        // Each measurement renders the document; its warnings are left to the real write
+        let write_options = &WriteOptions {
+            quiet: true,
+            ..write_options.clone()
+        };
+        let (total, stats) = document_size(args, &found_files, write_options)?;
+        let mut left_out = Vec::new();
+        if total > max_bytes {
+            // The sections alone bound the files that fit; the symbol index and
+            // footer grow with each file, so the longest fitting run is searched below that
+            let (empty, _) = document_size(args, &[], write_options)?;
+            let mut bound = found_files.clone();
+            budget::truncate_to_bytes(&mut bound, &stats, max_bytes.saturating_sub(empty));
+            let (mut low, mut high) = (0, bound.len());
+            while low < high {
+                let middle = (low + high).div_ceil(2);
+                if document_size(args, &found_files[..middle], write_options)?.0 <= max_bytes {
+                    low = middle;
+                } else {
+                    high = middle - 1;
+                }
+            }
+            found_files.truncate(low);
+            left_out = stats[low..].to_vec();
+        }
+        if !left_out.is_empty() {
+            if args.strict {
+                return Err(ConcatError::OutputTooLarge {
+                    bytes: total,
+                    limit: max_bytes,
+                });
+            }
+            status!(
+                "Output size limit of {} bytes reached: left out {} file(s)",
+                max_bytes,
+                left_out.len()
+            );
+            for file in left_out {
+                skipped.push(Skipped::new(
+                    file.rel_path,
+                    format!("past --max-output-bytes ({} bytes)", file.bytes),
+                ));
+            }
+        }
+    }
+
+    // Filters, priorities and pins above match the relative paths
+    if args.path_style != PathStyle::Relative {
+        apply_path_style(&mut found_files, args);
+    }
+    let stripped_prefix = args
+        .strip_prefix
+        .as_deref()
+        .and_then(|prefix| paths::strip_prefix(&mut found_files, prefix));
+    if let Some(ref prefix) = stripped_prefix {
+        status!("Stripped common prefix {}/ from paths", prefix.display());
+    }
+    if let (Some(label), Some(file)) = (&args.stdin_section, &args.stdin_file) {
+        found_files.push((label.clone(), file.to_path_buf()));
+    }
+
+    Ok(Selection {
+        files: found_files,
+        budget_exceeded,
+        stripped_prefix,
+        skipped,
+    })
+}
+
+/// Size in bytes of the whole document the files would make (everything the
+/// writer adds around their sections and the front matter included), with the
+/// statistics of each file
+fn document_size(
+    args: &CliArgs,
+    found_files: &[(PathBuf, PathBuf)],
+    write_options: &WriteOptions,
+) -> Result<(usize, Vec<FileStats>), ConcatError> {
+    let mut counter = TokenCounter::new();
+    let stats = write_document(&mut io::sink(), found_files, &mut counter, write_options)?;
+    let mut bytes = counter.byte_count;
+    if args.front_matter {
+        bytes += front_matter::front_matter(args, &stats, None, counter.char_count).len();
+    }
+    Ok((bytes, stats))
+}
+
+/// Runs the selection and renders the document in memory, for the server modes
+fn pack(args: &CliArgs) -> Result<(String, Vec<FileStats>), ConcatError> {
+    let (found_files, write_options) = prepare(args)?;
+    let mut document = Vec::new();
+    let stats = write_document(
+        &mut document,
+        &found_files,
+        &mut TokenCounter::new(),
+        &write_options,
+    )?;
+    Ok((String::from_utf8_lossy(&document).into_owned(), stats))
+}
+
+/// Selects the files of a run and the options to render them with
+fn prepare(args: &CliArgs) -> Result<(Vec<(PathBuf, PathBuf)>, WriteOptions), ConcatError> {
+    let write_options = WriteOptions::from_args(args)?;
+    let priorities = Priorities::new(&args.priority)
+        .map_err(|e| ConcatError::Config(format!("invalid priority glob: {}", e)))?;
+    let found_files = select_files(args, &write_options, &priorities)?.files;
+    Ok((found_files, write_options))
+}
+
+/// Layers the configuration files below the command-line arguments `argv`
+fn apply_config(
+    args: &mut CliArgs,
+    matches: &clap::ArgMatches,
+    argv: &[OsString],
+) -> Result<(), ConcatError> {
+    let layers = if args.no_config {
+        Ok((Config::default(), Vec::new()))
+    } else {
+        Config::load_layers(args.config.as_deref())
+    };
+    let (config, sources) = layers
+        .and_then(|(config, sources)| {
+            let config = config.resolve(args.profile.as_deref(), args.preset)?;
+            Ok((config, sources))
+        })
+        .map_err(|e| ConcatError::Config(e.to_string()))?;
+    for source in &sources {
+        status!("Using configuration from {}", source.display());
+    }
+    args.config_sources = sources;
+    let applied = config.apply_to(args, matches);
+    check_config_conflicts(argv, &applied)
+}
+
+/// Runs clap's conflict and requirement checks again, with the options set by
+/// the configuration files added to the command line. Values are stand-ins that
+/// pass the option's parser, since only the presence of an option matters here.
+fn check_config_conflicts(
+    argv: &[OsString],
+    applied: &[(&str, Option<bool>)],
+) -> Result<(), ConcatError> {
+    let command = CliArgs::command();
+    let mut argv = argv.to_vec();
+    for &(id, flag) in applied {
+        let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else {
+            continue;
+        };
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        match (arg.get_action(), flag) {
+            (clap::ArgAction::SetTrue, Some(true)) | (clap::ArgAction::SetFalse, Some(false)) => {
+                argv.push(format!("--{}", long).into());
+            }
+            (_, Some(_)) => {}
+            (_, None) => {
+                if let Some(value) = stand_in_value(arg) {
+                    argv.push(format!("--{}={}", long, value).into());
+                }
+            }
+        }
+    }
+    match command.try_get_matches_from(argv) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                clap::error::ErrorKind::ArgumentConflict
+                    | clap::error::ErrorKind::MissingRequiredArgument
+            ) =>
+        {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            Err(ConcatError::Config(format!(
+                "{} (including the options set in the configuration files)",
+                message.trim_start_matches("error: ")
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A value accepted by the parser of `arg`
+fn stand_in_value(arg: &clap::Arg) -> Option<String> {
+    if let Some(value) = arg.get_possible_values().first() {
+        return Some(value.get_name().to_string());
+    }
+    let parser = arg.get_value_parser().clone();
+    ["1", "a=a", "a"]
+        .into_iter()
+        .find(|value| {
+            clap::Command::new("probe")
+                .arg(clap::Arg::new("value").value_parser(parser.clone()))
+                .try_get_matches_from(["probe", value])
+                .is_ok()
+        })
+        .map(str::to_string)
+}
+
+/// Parses the command line `argv` again and re-applies the configuration files,
+/// after one of them changed in watch mode. Stdin was consumed by the first
+/// run, so its section is carried over from `previous`.
+fn reload_args(argv: &[OsString], previous: &CliArgs) -> Result<CliArgs, ConcatError> {
+    let matches = CliArgs::command()
+        .try_get_matches_from(argv)
+        .map_err(|e| ConcatError::Config(e.to_string()))?;
+    let mut args =
+        CliArgs::from_arg_matches(&matches).map_err(|e| ConcatError::Config(e.to_string()))?;
+    apply_config(&mut args, &matches, argv)?;
+    args.stdin_file = previous.stdin_file.clone();
+    Ok(args)
+}
+
+/// Parses an argument list the way the command line is parsed, including the
+/// configuration files, but reports problems instead of exiting
+fn parse_args<I, T>(argv: I) -> Result<CliArgs, ConcatError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+    let matches = CliArgs::command()
+        .try_get_matches_from(&argv)
+        .map_err(|e| ConcatError::Config(e.to_string()))?;
+    let mut args =
+        CliArgs::from_arg_matches(&matches).map_err(|e| ConcatError::Config(e.to_string()))?;
+    apply_config(&mut args, &matches, &argv)?;
+    open_snapshot(&mut args)?;
+    read_stdin_section(&mut args)?;
+    Ok(args)
+}
+
+/// Writes out the files of --at-ref or --remote for the run
+fn open_snapshot(args: &mut CliArgs) -> Result<(), ConcatError> {
+    if let Some(ref url) = args.remote {
+        // The bucket URL takes the place of the input directory in headings and summaries
+        args.snapshot = Some(Arc::new(bucket::snapshot(
+            url,
+            &args.extensions,
+            &OnlyPaths::new(&args.only_paths),
+        )?));
+        args.input_dirs = vec![PathBuf::from(url)];
+        return Ok(());
+    }
+    match args.at_ref {
+        Some(ref reference) => {
+            let snapshot = gitref::Snapshot::new(&args.input_dirs, reference, &args.extensions)?;
+            args.snapshot = Some(Arc::new(snapshot));
+        }
+        None if args.files_from.is_none() && args.from_manifest.is_none() => {
+            if let Some(bare) = args
+                .input_dirs
+                .iter()
+                .find(|dir| gitref::is_bare_repository(dir))
+            {
+                return Err(ConcatError::Config(format!(
+                    "{} is a bare repository; choose the revision to read with --at-ref",
+                    bare.display()
+                )));
+            }
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Reads the content of --stdin-section before the run, so watch mode reuses it
+fn read_stdin_section(args: &mut CliArgs) -> Result<(), ConcatError> {
+    if args.stdin_section.is_none() {
+        return Ok(());
+    }
+    if args.files_from.as_deref() == Some(Path::new("-")) {
+        return Err(ConcatError::Config(
+            "--stdin-section and --files-from=- cannot both read stdin".into(),
+        ));
+    }
+    let file = filelist::read_stdin_section().map_err(|source| ConcatError::InputFile {
+        path: PathBuf::from("-"),
+        source,
+    })?;
+    args.stdin_file = Some(Arc::new(file));
+    Ok(())
+}
+
+/// Performs a single collection and concatenation run
+fn run(args: &CliArgs) -> Result<(), ConcatError> {
+    let output_file = args.output_path();
+
+    let write_options = WriteOptions::from_args(args)?;
+    let priorities = Priorities::new(&args.priority)
+        .map_err(|e| ConcatError::Config(format!("invalid priority glob: {}", e)))?;
+
+    let Selection {
+        files: mut found_files,
+        budget_exceeded,
+        stripped_prefix,
+        skipped,
+    } = select_files(args, &write_options, &priorities)?;
+
+    if let Some(ref lock_file) = args.frozen {
+        lock::check_frozen(lock_file, args, &found_files)?;
+    }
+
+    if args.list {
+        let separator = if args.null { '\0' } else { '\n' };
+        let mut stdout = io::stdout().lock();
+        for (rel_path, _) in &found_files {
+            write!(stdout, "{}{}", rel_path.display(), separator)?;
+        }
+        stdout.flush()?;
+        return budget_exceeded.map_or(Ok(()), Err);
+    }
+
+    // Anonymize after listing, so --list still shows the real paths
+    let path_map = match args.anonymize_map_path() {
+        Some(ref map_path) => Some(PathMap::load(map_path)?),
+        None if args.anonymize_paths => Some(PathMap::default()),
+        None => None,
+    };
+    let path_map = path_map.map(|mut map| {
+        for (rel_path, _) in &mut found_files {
+            *rel_path = map.anonymize(rel_path);
+        }
+        map
+    });
+
+    if args.dry_run {
+        let mut token_counter = TokenCounter::new();
+        let stats = write_document(
+            &mut io::sink(),
+            &found_files,
+            &mut token_counter,
+            &write_options,
+        )?;
+        print_file_table(&stats);
+        if !write_options.redactor.is_empty() {
+            redact::print_report(&stats);
+        }
+        if args.sanitize {
+            sanitize::print_report(&stats);
+        }
+        if args.dedupe_content || args.dedupe_similar.is_some() {
+            writer::print_dedupe_report(&stats);
+        }
+        print_reports(args, &stats, &skipped);
+        status!("\n{}", token_counter.get_token_estimates());
+        return budget_exceeded.map_or(Ok(()), Err);
+    }
+
+    let output_file = output_file.expect("clap requires an output file unless listing");
+    // Split runs write numbered parts instead of the output file
+    let targets = if args.split_tokens.is_some() || args.split_by.is_some() {
+        Vec::new()
+    } else {
+        args.output_targets()
+    };
+
+    if !args.yes {
+        confirm_large_selection(args, &found_files)?;
+    }
+
+    // Compare against the previous run and skip the rewrite if nothing changed
+    let cache_path = args.cache_path();
+    let cache_check = cache_path.as_ref().map(|path| {
+        let fingerprint = cache::fingerprint(args, &write_options, &found_files);
+        RunCache::load(path).check(&fingerprint, &found_files)
+    });
+
+    if let Some(ref check) = cache_check {
+        status!("Cache: re-read {} changed file(s)", check.reread);
+        if !check.changed && output_file.exists() {
+            // Refresh stored modification times so touched files are not re-read again
+            if let Some(ref path) = cache_path {
+                let _ = check.cache.save(path);
+            }
+            status!("{} is up to date", output_file.display());
+            return Ok(());
+        }
+    }
+
+    // Never silently clobber a previous output
+    for target in targets.iter().filter(|target| target.path.exists()) {
+        if args.backup.is_none() && !args.force {
+            return Err(ConcatError::OutputExists(target.path.clone()));
+        }
+    }
+
+    let mut token_counter = TokenCounter::new();
+
+    status!("\nConcatenating {} files...", found_files.len());
+
+    let stats = match (args.split_tokens, args.split_by) {
+        (Some(part_tokens), _) => split::write_parts(
+            output_file,
+            &found_files,
+            part_tokens,
+            args.force,
+            &mut token_counter,
+            &write_options,
+        )?,
+        (None, Some(split_by)) => split::write_groups(
+            output_file,
+            &found_files,
+            split_by,
+            args.force,
+            &mut token_counter,
+            &write_options,
+        )?,
+        (None, None) => write_outputs(
+            args,
+            output_file,
+            &targets,
+            &found_files,
+            stripped_prefix.as_deref(),
+            &mut token_counter,
+            &write_options,
+        )?,
+    };
+
+    if let (Some(path), Some(check)) = (&cache_path, &cache_check)
+        && let Err(e) = check.cache.save(path)
+    {
+        eprintln!(
+            "Warning: Failed to write cache file {}: {}",
+            path.display(),
+            e
+        );
+    }
+
+    if let Some(manifest_path) = args.manifest_path() {
+        Manifest::new(&manifest_output(args, output_file), &found_files, &stats)
+            .save(&manifest_path)?;
+        status!("Manifest written to {}", manifest_path.display());
+    }
+
+    if let (Some(map), Some(map_path)) = (&path_map, args.anonymize_map_path()) {
+        map.save(&map_path)?;
+        status!("Path mapping written to {}", map_path.display());
+    }
+
+    if !write_options.redactor.is_empty() {
+        redact::print_report(&stats);
+    }
+    if args.sanitize {
+        sanitize::print_report(&stats);
+    }
+    if args.dedupe_content || args.dedupe_similar.is_some() {
+        writer::print_dedupe_report(&stats);
+    }
+    print_reports(args, &stats, &skipped);
+
+    // Generate and display token count report
+    status!("\n{}", token_counter.get_token_estimates());
+
+    let unreadable = stats.iter().filter(|file| file.unreadable).count();
+    if unreadable > 0 && write_options.unreadable == UnreadablePolicy::Placeholder {
+        return Err(ConcatError::UnreadableFiles(unreadable));
+    }
+    budget_exceeded.map_or(Ok(()), Err)
+}
+
+/// Replaces the relative paths shown for the files according to --path-style
+fn apply_path_style(found_files: &mut [(PathBuf, PathBuf)], args: &CliArgs) {
+    let start = match args.files_from {
+        Some(_) => Some(args.files_root.clone()),
+        None => args.input_dirs.first().map(|dir| args.input_root(dir)),
+    };
+    let repository = match args.path_style {
+        PathStyle::FromRoot => start.and_then(|start| paths::repository_root(&start)),
+        _ => None,
+    };
+    for (rel_path, abs_path) in found_files.iter_mut() {
+        let canonical = fs::canonicalize(&*abs_path).unwrap_or_else(|_| abs_path.clone());
+        let shown = args
+            .path_style
+            .display(rel_path, &canonical, repository.as_deref());
+        *rel_path = if args.native_separators || shown.is_absolute() {
+            shown
+        } else {
+            paths::portable(&shown)
+        };
+    }
+}
+
+/// Orders paths byte-wise, refusing anything that would differ between machines
+fn make_deterministic(
+    found_files: &mut [(PathBuf, PathBuf)],
+    args: &CliArgs,
+) -> Result<(), ConcatError> {
+    if args.sort == Some(SortOrder::Mtime) {
+        return Err(ConcatError::Config(
+            "--deterministic cannot sort by modification time".into(),
+        ));
+    }
+    if args.path_style == PathStyle::Absolute {
+        return Err(ConcatError::Config(
+            "--deterministic cannot show absolute paths (--path-style absolute)".into(),
+        ));
+    }
+    if let Some((rel_path, _)) = found_files.iter().find(|(rel, _)| rel.is_absolute()) {
+        return Err(ConcatError::Config(format!(
+            "--deterministic: {} is outside the file root and would be shown as an absolute path",
+            rel_path.display()
+        )));
+    }
+    if args.files_from.is_none() && args.from_manifest.is_none() {
+        found_files.sort_by(|a, b| paths::compare_bytes(&a.0, &b.0));
+    }
+    Ok(())
 }
 
-fn main() -> io::Result<()> {
-    let args = CliArgs::parse();
+/// Keeps the files of the requested workspace members and adds the workspace's
+/// shared root configuration files
+fn restrict_to_workspace_members(
+    found_files: &mut Vec<(PathBuf, PathBuf)>,
+    args: &CliArgs,
+) -> Result<(), ConcatError> {
+    let root = &args.input_root(&args.input_dirs[0]);
+    let workspace = Workspace::detect(root)?.ok_or_else(|| {
+        ConcatError::Config(format!(
+            "--workspace-member: no Cargo, npm, pnpm or Go workspace found in {}",
+            root.display()
+        ))
+    })?;
+    let members = workspace.select(&args.workspace_member)?;
+    status!(
+        "{} workspace: including {} member(s)",
+        workspace.kind(),
+        members.len()
+    );
 
-    let output_file = &args.output_file;
+    found_files.retain(|(_, abs_path)| {
+        members
+            .iter()
+            .any(|member| abs_path.starts_with(&member.dir))
+            || workspace.root_files.contains(abs_path)
+    });
+    let canonical_root = fs::canonicalize(root)?;
+    for root_file in &workspace.root_files {
+        if !found_files
+            .iter()
+            .any(|(_, abs_path)| abs_path == root_file)
+            && let Ok(rel_path) = root_file.strip_prefix(&canonical_root)
+        {
+            found_files.push((rel_path.to_path_buf(), root_file.clone()));
+        }
+    }
+    found_files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(())
+}
 
+/// Renders the document once and writes it to --inject and every output target.
+/// Returns one entry per input file, in the same order.
+fn write_outputs(
+    args: &CliArgs,
+    output_file: &Path,
+    targets: &[OutputTarget],
+    found_files: &[(PathBuf, PathBuf)],
+    stripped_prefix: Option<&Path>,
+    token_counter: &mut TokenCounter,
+    write_options: &WriteOptions,
+) -> Result<Vec<FileStats>, ConcatError> {
+    let mut document = Vec::new();
+    let stats = write_document(&mut document, found_files, token_counter, write_options)?;
+    if args.front_matter {
+        let header =
+            front_matter::front_matter(args, &stats, stripped_prefix, token_counter.char_count);
+        token_counter.add_text(&header);
+        document.splice(0..0, header.into_bytes());
+    }
+    // Paths, the stdin section and the front matter can still change after
+    // files were left out to fit the limit
+    if let Some(limit) = args.max_output_bytes
+        && document.len() > limit
+    {
+        return Err(ConcatError::OutputTooLarge {
+            bytes: document.len(),
+            limit,
+        });
+    }
+    if let Some(ref target) = args.inject {
+        inject::inject_into(target, &document)?;
+        status!(
+            "Successfully concatenated {} files into {}",
+            found_files.len(),
+            target.display()
+        );
+    }
+    for target in targets {
+        match target.format {
+            // --backup keeps the previous outputs once the new ones are complete
+            OutputFormat::Markdown => {
+                output::write_atomic_with_backup(&target.path, args.backup, |writer| {
+                    writer.write_all(&document)?;
+                    Ok(())
+                })?
+            }
+            OutputFormat::Xml => {
+                let xml = output::render_xml(&stats);
+                output::write_atomic_with_backup(&target.path, args.backup, |writer| {
+                    writer.write_all(xml.as_bytes())?;
+                    Ok(())
+                })?
+            }
+            OutputFormat::Json => {
+                let manifest =
+                    Manifest::new(&manifest_output(args, output_file), found_files, &stats);
+                output::write_atomic_with_backup(&target.path, args.backup, |writer| {
+                    serde_json::to_writer_pretty(writer, &manifest).map_err(io::Error::from)?;
+                    Ok(())
+                })?
+            }
+        }
+        status!(
+            "Successfully concatenated {} files into {}",
+            found_files.len(),
+            target.path.display()
+        );
+    }
+    Ok(stats)
+}
+
+/// Asks before writing a selection with more files than --max-files or more
+/// bytes than --max-input-bytes, e.g. because of a mistyped extension list
+fn confirm_large_selection(
+    args: &CliArgs,
+    found_files: &[(PathBuf, PathBuf)],
+) -> Result<(), ConcatError> {
+    let too_many = args.max_files > 0 && found_files.len() > args.max_files;
+    if !too_many && args.max_input_bytes == 0 {
+        return Ok(());
+    }
+    let bytes: u64 = found_files
+        .iter()
+        .filter_map(|(_, abs_path)| fs::metadata(abs_path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    let too_large = args.max_input_bytes > 0 && bytes > args.max_input_bytes;
+    if !too_many && !too_large {
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        return Err(if too_many {
+            ConcatError::TooManyFiles {
+                count: found_files.len(),
+                limit: args.max_files,
+            }
+        } else {
+            ConcatError::SelectionTooLarge {
+                bytes,
+                limit: args.max_input_bytes,
+            }
+        });
+    }
+    let prompt = format!(
+        "About to include {} files ({:.1} MB, ~{} tokens). Continue? [y/N] ",
+        found_files.len(),
+        bytes as f64 / 1_000_000.0,
+        PER_FILE_STRATEGY.estimate(bytes as usize)
+    );
+    if apply::confirm(&prompt)? {
+        Ok(())
+    } else {
+        Err(ConcatError::Cancelled)
+    }
+}
+
+/// The document path recorded in manifests; only its file name with --deterministic
+fn manifest_output(args: &CliArgs, output_file: &Path) -> PathBuf {
+    match output_file.file_name() {
+        Some(name) if args.deterministic => PathBuf::from(name),
+        _ => output_file.to_path_buf(),
+    }
+}
+
+/// Prints the reports requested with --report
+fn print_reports(args: &CliArgs, stats: &[FileStats], skipped: &[Skipped]) {
+    if let Some(limit) = args.warn_file_tokens {
+        report::warn_large_files(stats, limit);
+    }
+    for report in &args.report {
+        match report {
+            Report::Dirs => report::print_dirs(stats, args.report_depth.into()),
+            Report::Skipped => report::print_skipped(skipped, stats),
+        }
+    }
+}
+
+/// Files found by walking the input directories, and the candidates left out on the way
+type Collected = (Vec<(PathBuf, PathBuf)>, Vec<Skipped>);
+
+/// Walks the input directories and collects matching files as (relative, canonical) path pairs
+fn collect_from_input_dirs(args: &CliArgs) -> Result<Collected, ConcatError> {
     // Canonicalize all input directories and deduplicate them
     let mut canonical_dirs = HashSet::new();
     let mut valid_input_dirs = Vec::new();
 
     for input_dir in &args.input_dirs {
-        match fs::canonicalize(input_dir) {
+        match fs::canonicalize(args.input_root(input_dir)) {
             Ok(canonical_path) => {
                 if canonical_dirs.insert(canonical_path.clone()) {
                     valid_input_dirs.push(canonical_path);
-                    println!("Input directory: {}", input_dir.display());
+                    status!("Input directory: {}", input_dir.display());
                 } else {
-                    println!(
+                    status!(
                         "Skipping duplicate directory: {} (same as already included directory)",
                         input_dir.display()
                     );
                 }
             }
-            Err(e) => {
-                eprintln!(
-                    "Error: Input directory '{}' is not accessible: {}",
-                    input_dir.display(),
-                    e
-                );
-                std::process::exit(1);
+            Err(source) => {
+                return Err(ConcatError::InputDir {
+                    path: input_dir.clone(),
+                    source,
+                });
             }
         }
     }
 
     // Convert extensions to a HashSet for O(1) lookup
-    let extensions: HashSet<String> = args.extensions.into_iter().collect();
-    println!("Extensions: {:?}", extensions);
-
-    // Convert exclude_dirs to a HashSet for O(1) lookup, filtering out empty strings
-    let exclude_dirs: HashSet<String> = args
-        .exclude_dirs
-        .into_iter()
-        .filter(|s| !s.is_empty())
-        .collect();
+    let extensions: HashSet<String> = args.extensions.iter().cloned().collect();
+    status!("Extensions: {:?}", extensions);
 
+    let exclude_dirs = ExcludeDirs::new(&args.exclude_dirs)?;
     if !exclude_dirs.is_empty() {
-        println!("Excluding directories: {:?}", exclude_dirs);
+        let entries: Vec<&String> = args.exclude_dirs.iter().filter(|s| !s.is_empty()).collect();
+        status!("Excluding directories: {:?}", entries);
     }
 
     // Initialize gitignore manager if needed
@@ -173,8 +2117,11 @@ fn main() -> io::Result<()> {
             &valid_input_dirs,
             &args.additional_gitignore_files,
         ) {
-            Ok(manager) => {
-                println!("Gitignore support enabled");
+            Ok(mut manager) => {
+                status!("Gitignore support enabled");
+                if args.debug_ignore {
+                    manager.enable_debug();
+                }
                 Some(manager)
             }
             Err(e) => {
@@ -184,91 +2131,83 @@ fn main() -> io::Result<()> {
             }
         }
     } else {
-        println!("Gitignore support disabled");
+        status!("Gitignore support disabled");
         None
     };
 
-    // Collect files using the new system
-    let found_files = if let Some(ref manager) = gitignore_manager {
-        collect_files_with_gitignore(&valid_input_dirs, &extensions, &exclude_dirs, manager, true)
-    } else {
-        collect_files_with_gitignore(
-            &valid_input_dirs,
-            &extensions,
-            &exclude_dirs,
-            &GitignoreManager::new(),
-            false,
-        )
-    };
-
-    let output_file_handle = File::create(output_file)?;
-    let mut writer = BufWriter::new(output_file_handle);
-    let mut token_counter = TokenCounter::new();
-
-    println!("\nConcatenating {} files...", found_files.len());
-
-    for (rel_path, abs_path) in &found_files {
-        let display_path = rel_path.display();
-        let ext = rel_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let label_roots = args.root_label.applies(valid_input_dirs.len());
+    let submodules = Submodules::discover(&valid_input_dirs, args.submodules);
+    match args.submodules {
+        SubmoduleMode::Exclude if submodules.len() > 0 => {
+            status!("Excluding {} submodule(s)", submodules.len());
+        }
+        SubmoduleMode::Shallow if submodules.len() > 0 => {
+            status!(
+                "Including only the top level of {} submodule(s)",
+                submodules.len()
+            );
+        }
+        _ => {}
+    }
 
-        let header = format!("## {}\n\n", display_path);
-        let code_start = format!("```{}\n", ext);
+    let only_paths = OnlyPaths::new(&args.only_paths);
+    if !args.only_paths.is_empty() {
+        status!("Only walking: {:?}", args.only_paths);
+    }
 
-        // Count tokens for markdown formatting
-        token_counter.add_text(&header);
-        token_counter.add_text(&code_start);
-
-        writeln!(writer, "## {}\n", display_path)?;
-        writeln!(writer, "```{}", ext)?;
-
-        match File::open(abs_path) {
-            Ok(mut input_file) => {
-                let mut buffer = String::new();
-                if input_file.read_to_string(&mut buffer).is_ok() {
-                    token_counter.add_text(&buffer);
-                    write!(writer, "{}", buffer)?;
-                    if !buffer.ends_with('\n') {
-                        token_counter.add_text("\n");
-                        writeln!(writer)?;
-                    }
-                } else {
-                    let error_msg =
-                        "\nError: Could not read file content (e.g., binary or non-UTF-8)";
-                    eprintln!(
-                        "Warning: Failed to read file content (possibly not UTF-8): {}",
-                        abs_path.display()
-                    );
-                    token_counter.add_text(error_msg);
-                    token_counter.add_text("\n");
-                    write!(writer, "{}", error_msg)?;
-                    writeln!(writer)?;
-                }
-            }
-            Err(e) => {
-                let error_msg = format!("\nError: Could not open file: {}", e);
-                eprintln!("Error opening file {}: {}", abs_path.display(), e);
-                token_counter.add_text(&error_msg);
-                token_counter.add_text("\n");
-                write!(writer, "{}", error_msg)?;
-                writeln!(writer)?;
-            }
-        }
+    // Collect files using the new system
+    Ok(collect_files_with_gitignore(
+        &valid_input_dirs,
+        &extensions,
+        &exclude_dirs,
+        &only_paths,
+        &gitignore_manager.unwrap_or_default(),
+        label_roots,
+        &submodules,
+    ))
+}
 
-        let code_end = "```\n\n";
-        token_counter.add_text(code_end);
-        writeln!(writer, "```\n")?;
+/// Resolves the output path the same way collected files are resolved, even
+/// when the output does not exist yet
+fn canonicalize_output(output_file: &Path) -> Option<PathBuf> {
+    if let Ok(path) = fs::canonicalize(output_file) {
+        return Some(path);
     }
+    let parent = output_file
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Some(
+        fs::canonicalize(parent)
+            .ok()?
+            .join(output_file.file_name()?),
+    )
+}
 
-    writer.flush()?;
+/// Prints one row per file with its size and estimated token count
+fn print_file_table(stats: &[FileStats]) {
+    let width = stats
+        .iter()
+        .map(|s| s.rel_path.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("File".len());
 
     println!(
-        "Successfully concatenated {} files into {}",
-        found_files.len(),
-        output_file.display()
+        "\n{:<width$}  {:>10}  {:>10}",
+        "File", "Characters", "~Tokens"
+    );
+    for file in stats {
+        println!(
+            "{:<width$}  {:>10}  {:>10}",
+            file.rel_path.display().to_string(),
+            file.char_count,
+            file.tokens()
+        );
+    }
+    println!(
+        "\n{} file(s) would be included ({} estimate)",
+        stats.len(),
+        PER_FILE_STRATEGY.name()
     );
-
-    // Generate and display token count report
-    println!("\n{}", token_counter.get_token_estimates());
-
-    Ok(())
 }
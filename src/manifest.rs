@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::sha256_hex;
+use crate::error::{ConcatError, output_error};
+use crate::writer::FileStats;
+
+/// Sidecar describing the files included in a generated document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    /// The document this manifest describes
+    pub output: PathBuf,
+    /// Included files in output order
+    pub files: Vec<ManifestEntry>,
+    pub total_tokens: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ManifestEntry {
+    /// Path as shown in the section heading
+    pub path: PathBuf,
+    /// SHA-256 of the source file on disk
+    pub sha256: String,
+    /// Size of the source file in bytes
+    pub size: usize,
+    /// Estimated tokens of the file's section
+    pub tokens: usize,
+}
+
+impl Manifest {
+    /// Describes the files of a finished run. `files` and `stats` are aligned;
+    /// files left out of the output (unreadable and skipped) are not listed.
+    pub fn new(output: &Path, files: &[(PathBuf, PathBuf)], stats: &[FileStats]) -> Self {
+        let files: Vec<ManifestEntry> = files
+            .iter()
+            .zip(stats)
+            .filter(|(_, file)| file.char_count > 0)
+            .map(|((_, abs_path), file)| ManifestEntry {
+                path: file.rel_path.clone(),
+                sha256: fs::read(abs_path)
+                    .map(|bytes| sha256_hex(&bytes))
+                    .unwrap_or_default(),
+                size: file.size,
+                tokens: file.tokens(),
+            })
+            .collect();
+        Self {
+            output: output.to_path_buf(),
+            total_tokens: files.iter().map(|entry| entry.tokens).sum(),
+            files,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConcatError> {
+        let text = fs::read_to_string(path).map_err(|source| ConcatError::InputFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&text)
+            .map_err(|e| ConcatError::Config(format!("invalid manifest {}: {}", path.display(), e)))
+    }
+
+    /// Resolves the recorded files, in their recorded order, against the input
+    /// directories (recorded paths may start with the directory's root label).
+    /// Every file has to exist; files whose content changed are reported.
+    pub fn recorded_files(
+        &self,
+        input_dirs: &[PathBuf],
+    ) -> Result<Vec<(PathBuf, PathBuf)>, ConcatError> {
+        let mut files = Vec::new();
+        let mut missing = Vec::new();
+        for entry in &self.files {
+            let Some(abs_path) = input_dirs.iter().find_map(|dir| locate(dir, &entry.path)) else {
+                missing.push(entry.path.display().to_string());
+                continue;
+            };
+            let changed = fs::read(&abs_path)
+                .map(|bytes| sha256_hex(&bytes) != entry.sha256)
+                .unwrap_or(true);
+            if changed {
+                eprintln!(
+                    "Warning: {} differs from the manifest",
+                    entry.path.display()
+                );
+            }
+            files.push((entry.path.clone(), abs_path));
+        }
+        if !missing.is_empty() {
+            return Err(ConcatError::Config(format!(
+                "{} file(s) recorded in the manifest are missing: {}",
+                missing.len(),
+                missing.join(", ")
+            )));
+        }
+        Ok(files)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConcatError> {
+        let json = serde_json::to_string_pretty(self).expect("manifest serializes");
+        fs::write(path, json).map_err(output_error(path))
+    }
+}
+
+/// The file a recorded path refers to below `dir`, as `src/a.rs` or `label/src/a.rs`
+fn locate(dir: &Path, recorded: &Path) -> Option<PathBuf> {
+    let unlabeled = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| dir.file_name().map(PathBuf::from))
+        .and_then(|label| recorded.strip_prefix(label).ok().map(Path::to_path_buf));
+    [Some(recorded.to_path_buf()), unlabeled]
+        .into_iter()
+        .flatten()
+        .filter_map(|rel_path| fs::canonicalize(dir.join(rel_path)).ok())
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,170 @@
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::TokenCounter;
+use crate::document;
+use crate::error::ConcatError;
+use crate::serve;
+use crate::writer::{self, WriteOptions};
+
+/// Protocol revision answered when the client does not ask for one
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Runs a Model Context Protocol server on stdin/stdout until stdin closes.
+/// Messages are newline-delimited JSON-RPC 2.0.
+pub fn serve() -> Result<(), ConcatError> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(&message),
+            Err(e) => Some(error_response(
+                Value::Null,
+                -32700,
+                &format!("Parse error: {}", e),
+            )),
+        };
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Answers one message; notifications get no response
+fn handle(message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match message.get("method").and_then(Value::as_str) {
+        Some("initialize") => json!({
+            "protocolVersion": params
+                .get("protocolVersion")
+                .and_then(Value::as_str)
+                .unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        }),
+        Some("ping") => json!({}),
+        Some("tools/list") => json!({ "tools": tools() }),
+        Some("tools/call") => call_tool(&params),
+        Some(method) => {
+            return Some(error_response(
+                id,
+                -32601,
+                &format!("Method not found: {}", method),
+            ));
+        }
+        None => return Some(error_response(id, -32600, "Invalid request")),
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "pack_repo",
+            "description": "Concatenate the source files below the given directories into one Markdown document, dropping files to fit an optional token budget",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "paths": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Directories to search, relative to the server's working directory (default: \".\")",
+                    },
+                    "budget": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Maximum estimated tokens of the document",
+                    },
+                    "extensions": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "File extensions to include (default: from the configuration file)",
+                    },
+                    "exclude_dirs": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Directory names to skip",
+                    },
+                },
+            },
+        },
+        {
+            "name": "get_file_section",
+            "description": "Render a single file the way it appears in a packed document",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "File path relative to the server's working directory",
+                    },
+                },
+                "required": ["path"],
+            },
+        },
+    ])
+}
+
+/// Runs a tool. Failures are reported in the result so the model can see them.
+fn call_tool(params: &Value) -> Value {
+    let arguments = params.get("arguments").unwrap_or(&Value::Null);
+    let outcome = match params.get("name").and_then(Value::as_str) {
+        Some("pack_repo") => pack_repo(arguments),
+        Some("get_file_section") => get_file_section(arguments),
+        Some(name) => Err(format!("Unknown tool: {}", name)),
+        None => Err("Missing tool name".to_string()),
+    };
+    let (text, is_error) = match outcome {
+        Ok(text) => (text, false),
+        Err(message) => (message, true),
+    };
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+fn pack_repo(arguments: &Value) -> Result<String, String> {
+    let args = serve::pack_args(arguments).map_err(|e| e.to_string())?;
+    let (document, _) = crate::pack(&args).map_err(|e| e.to_string())?;
+    Ok(document)
+}
+
+fn get_file_section(arguments: &Value) -> Result<String, String> {
+    let path = arguments
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or("path is required")?;
+    let rel_path = document::safe_relative(Path::new(path))
+        .ok_or_else(|| format!("{}: path leaves the working directory", path))?;
+    if !rel_path.is_file() {
+        return Err(format!("{}: no such file", path));
+    }
+
+    let args = crate::parse_args(["md_concat", "--dry-run"]).map_err(|e| e.to_string())?;
+    let options = WriteOptions::from_args(&args).map_err(|e| e.to_string())?;
+    let files = [(rel_path.to_path_buf(), PathBuf::from(rel_path))];
+    let mut section = Vec::new();
+    writer::write_files(&mut section, &files, &mut TokenCounter::new(), &options)
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8_lossy(&section).into_owned())
+}
@@ -0,0 +1,143 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::PER_FILE_STRATEGY;
+use crate::config::Priorities;
+use crate::imports;
+
+/// Sort keys for the collected files
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Relative path (default)
+    Path,
+    /// File size in bytes
+    Size,
+    /// Last modification time
+    Mtime,
+    /// Estimated token count of the file content
+    Tokens,
+    /// File extension, then path
+    Extension,
+    /// Dependencies before the files that import them
+    Deps,
+}
+
+/// Sorts files by the given key; ties are broken by relative path
+pub fn sort_files(files: &mut [(PathBuf, PathBuf)], order: SortOrder, reverse: bool) {
+    match order {
+        SortOrder::Path => files.sort_by(|a, b| a.0.cmp(&b.0)),
+        SortOrder::Size => sort_by_cached(files, |abs| fs::metadata(abs).map_or(0, |m| m.len())),
+        SortOrder::Mtime => sort_by_cached(files, |abs| {
+            fs::metadata(abs)
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        }),
+        SortOrder::Tokens => sort_by_cached(files, |abs| {
+            fs::read(abs).map_or(0, |bytes| {
+                PER_FILE_STRATEGY.estimate(String::from_utf8_lossy(&bytes).chars().count())
+            })
+        }),
+        SortOrder::Extension => sort_by_cached(files, |abs| {
+            abs.extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default()
+        }),
+        SortOrder::Deps => {
+            files.sort_by(|a, b| a.0.cmp(&b.0));
+            order_by_dependencies(files);
+        }
+    }
+
+    if reverse {
+        files.reverse();
+    }
+}
+
+/// Topologically orders files so that imported files come before their importers.
+/// Among files that are ready, the current order is kept; import cycles are
+/// broken by emitting the earliest remaining file.
+fn order_by_dependencies(files: &mut [(PathBuf, PathBuf)]) {
+    let deps = imports::dependencies(files);
+    let mut remaining: Vec<usize> = deps.iter().map(Vec::len).collect();
+    let mut dependents = vec![Vec::new(); files.len()];
+    for (file, file_deps) in deps.iter().enumerate() {
+        for &dep in file_deps {
+            dependents[dep].push(file);
+        }
+    }
+
+    let mut ready: BTreeSet<usize> = (0..files.len()).filter(|&i| remaining[i] == 0).collect();
+    let mut emitted = vec![false; files.len()];
+    let mut order = Vec::with_capacity(files.len());
+
+    while order.len() < files.len() {
+        let next = match ready.pop_first() {
+            Some(next) => next,
+            None => (0..files.len())
+                .find(|&i| !emitted[i])
+                .expect("files remain"),
+        };
+        if emitted[next] {
+            continue;
+        }
+        emitted[next] = true;
+        order.push(next);
+
+        for &dependent in &dependents[next] {
+            remaining[dependent] = remaining[dependent].saturating_sub(1);
+            if remaining[dependent] == 0 && !emitted[dependent] {
+                ready.insert(dependent);
+            }
+        }
+    }
+
+    let sorted: Vec<_> = order.iter().map(|&i| files[i].clone()).collect();
+    files.clone_from_slice(&sorted);
+}
+
+/// Sorts by a key computed once per file, then by relative path
+fn sort_by_cached<K: Ord>(files: &mut [(PathBuf, PathBuf)], key: impl Fn(&Path) -> K) {
+    files.sort_by_cached_key(|(rel_path, abs_path)| (key(abs_path), rel_path.clone()));
+}
+
+/// Stable-sorts files by descending priority weight, keeping the existing order within a weight
+pub fn sort_by_priority(files: &mut [(PathBuf, PathBuf)], priorities: &Priorities) {
+    files.sort_by_cached_key(|(rel_path, _)| Reverse(priorities.weight(rel_path)));
+}
+
+/// Moves the pinned files to the front, in the order they were pinned,
+/// keeping the relative order of everything else
+pub fn pin_files(files: &mut Vec<(PathBuf, PathBuf)>, pins: &[PathBuf]) {
+    let mut pinned = Vec::new();
+
+    for pin in pins {
+        match files
+            .iter()
+            .position(|(rel_path, _)| same_path(rel_path, pin))
+        {
+            Some(index) => pinned.push(files.remove(index)),
+            None => eprintln!(
+                "Warning: Pinned file {} is not part of the selection",
+                pin.display()
+            ),
+        }
+    }
+
+    files.splice(0..0, pinned);
+}
+
+/// Compares relative paths component-wise so "./src/main.rs" matches "src/main.rs"
+fn same_path(a: &Path, b: &Path) -> bool {
+    let normal = |p: &Path| {
+        p.components()
+            .filter(|c| !matches!(c, std::path::Component::CurDir))
+            .collect::<PathBuf>()
+    };
+    normal(a) == normal(b)
+}
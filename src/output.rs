@@ -0,0 +1,247 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+use crate::BackupMode;
+use crate::error::{ConcatError, output_error};
+use crate::writer::FileStats;
+
+/// Writes `path` atomically: content goes to a temporary file in the destination
+/// directory, which only replaces the destination once everything was written.
+/// If `write` fails the temporary file is removed and the destination is untouched.
+pub fn write_atomic<T, F>(path: &Path, write: F) -> Result<T, ConcatError>
+where
+    F: FnOnce(&mut BufWriter<&mut File>) -> Result<T, ConcatError>,
+{
+    write_atomic_with_backup(path, None, write)
+}
+
+/// Like `write_atomic`, but an existing destination is first renamed to its
+/// `backup` name. That only happens once the new content is complete, so a
+/// failed run leaves the previous output where it was.
+pub fn write_atomic_with_backup<T, F>(
+    path: &Path,
+    backup: Option<BackupMode>,
+    write: F,
+) -> Result<T, ConcatError>
+where
+    F: FnOnce(&mut BufWriter<&mut File>) -> Result<T, ConcatError>,
+{
+    let not_writable = || output_error(path);
+
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut temp = NamedTempFile::new_in(dir).map_err(not_writable())?;
+
+    let result = {
+        let mut writer = BufWriter::new(temp.as_file_mut());
+        let result = write(&mut writer)?;
+        writer.flush().map_err(not_writable())?;
+        result
+    };
+
+    // Temporary files are private by default; keep the permissions a plain create would give
+    match fs::metadata(path) {
+        Ok(metadata) => temp.as_file().set_permissions(metadata.permissions()),
+        Err(_) => set_default_permissions(temp.as_file()),
+    }
+    .map_err(not_writable())?;
+
+    if let Some(mode) = backup
+        && path.exists()
+    {
+        let backup_path = backup_path(path, mode);
+        fs::rename(path, &backup_path).map_err(output_error(&backup_path))?;
+        status!("Backed up previous output to {}", backup_path.display());
+    }
+    temp.persist(path).map_err(|e| not_writable()(e.error))?;
+    Ok(result)
+}
+
+#[cfg(unix)]
+fn set_default_permissions(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(fs::Permissions::from_mode(0o644))
+}
+
+#[cfg(not(unix))]
+fn set_default_permissions(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Returns the path an existing output file is renamed to before being replaced
+pub fn backup_path(path: &Path, mode: BackupMode) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    if let BackupMode::Timestamp = mode {
+        name.push(chrono::Local::now().format(".%Y%m%d-%H%M%S").to_string());
+    }
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Format of a file written with --output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The Markdown document
+    Markdown,
+    /// The processed file contents as XML elements
+    Xml,
+    /// Per-file statistics, in the --manifest layout
+    Json,
+}
+
+impl OutputFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(OutputFormat::Markdown),
+            "xml" => Some(OutputFormat::Xml),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A file written with --output, given as "FILE" (format inferred from the
+/// extension) or "FORMAT:FILE"
+#[derive(Debug, Clone)]
+pub struct OutputTarget {
+    pub format: OutputFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for OutputTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some((name, path)) = value.split_once(':')
+            && let Some(format) = OutputFormat::from_name(name)
+        {
+            return Ok(Self {
+                format,
+                path: PathBuf::from(path),
+            });
+        }
+        let path = PathBuf::from(value);
+        let format = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(OutputFormat::from_name)
+            .ok_or_else(|| {
+                format!(
+                    "cannot infer the format of '{}'; use md:, xml: or json: in front of the path",
+                    value
+                )
+            })?;
+        Ok(Self { format, path })
+    }
+}
+
+/// Renders the processed contents of the included files as XML. Requires the
+/// statistics of a run with WriteOptions::keep_content set.
+pub fn render_xml(stats: &[FileStats]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<files>\n");
+    for file in stats {
+        let path = escape_xml(&file.rel_path.display().to_string());
+        if file.unreadable {
+            xml.push_str(&format!("<file path=\"{}\" unreadable=\"true\"/>\n", path));
+            continue;
+        }
+        let attributes = format!(
+            "path=\"{}\" language=\"{}\" tokens=\"{}\"",
+            path,
+            escape_xml(&file.language),
+            file.tokens()
+        );
+        match file.content.as_deref() {
+            Some(content) if !content.is_empty() => {
+                xml.push_str(&format!("<file {}>\n{}", attributes, escape_xml(content)));
+                if !content.ends_with('\n') {
+                    xml.push('\n');
+                }
+                xml.push_str("</file>\n");
+            }
+            _ => xml.push_str(&format!("<file {}/>\n", attributes)),
+        }
+    }
+    xml.push_str("</files>\n");
+    xml
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn failed_write_keeps_the_previous_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.md");
+        fs::write(&path, "previous").unwrap();
+
+        let result: Result<(), _> =
+            write_atomic_with_backup(&path, Some(BackupMode::Bak), |writer| {
+                writer.write_all(b"partial")?;
+                Err(ConcatError::UnreadableFiles(1))
+            });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+        assert!(!backup_path(&path, BackupMode::Bak).exists());
+    }
+
+    #[test]
+    fn backup_is_made_once_the_new_output_is_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.md");
+        fs::write(&path, "previous").unwrap();
+
+        write_atomic_with_backup(&path, Some(BackupMode::Bak), |writer| {
+            writer.write_all(b"new")?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(backup_path(&path, BackupMode::Bak)).unwrap(),
+            "previous"
+        );
+    }
+
+    #[test]
+    fn xml_keeps_empty_and_unreadable_files() {
+        let stats = [
+            FileStats {
+                rel_path: PathBuf::from("empty.rs"),
+                language: "rust".to_string(),
+                content: Some(String::new()),
+                ..Default::default()
+            },
+            FileStats {
+                rel_path: PathBuf::from("a&b.rs"),
+                language: "rust".to_string(),
+                content: Some("fn a() {}".to_string()),
+                ..Default::default()
+            },
+            FileStats {
+                rel_path: PathBuf::from("blob.rs"),
+                unreadable: true,
+                ..Default::default()
+            },
+        ];
+        let xml = render_xml(&stats);
+        assert!(xml.contains("<file path=\"empty.rs\" language=\"rust\" tokens=\"0\"/>\n"));
+        assert!(xml.contains("language=\"rust\" tokens=\"0\">\nfn a() {}\n</file>\n"));
+        assert!(xml.contains("path=\"a&amp;b.rs\""));
+        assert!(xml.contains("<file path=\"blob.rs\" unreadable=\"true\"/>\n"));
+    }
+}
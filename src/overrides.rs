@@ -0,0 +1,112 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConcatError;
+use crate::writer::{MarkdownMode, WriteOptions};
+
+/// File name of the per-directory option overrides
+pub const OVERRIDES_FILE: &str = ".mdconcat.toml";
+
+/// Options a `.mdconcat.toml` can change for the files of its directory and
+/// everything below it. Deeper files win over shallower ones, and all of them
+/// over the command line and the configuration files.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DirOverrides {
+    pub line_numbers: Option<bool>,
+    pub strip_comments: Option<bool>,
+    pub max_lines_per_file: Option<usize>,
+    pub md: Option<MarkdownMode>,
+}
+
+impl DirOverrides {
+    fn load(path: &Path) -> Result<Self, ConcatError> {
+        let text = fs::read_to_string(path).map_err(|source| ConcatError::InputFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text)
+            .map_err(|e| ConcatError::Config(format!("invalid {}: {}", path.display(), e)))
+    }
+
+    /// These overrides layered over the shallower `self`
+    fn merge(self, deeper: Self) -> Self {
+        Self {
+            line_numbers: deeper.line_numbers.or(self.line_numbers),
+            strip_comments: deeper.strip_comments.or(self.strip_comments),
+            max_lines_per_file: deeper.max_lines_per_file.or(self.max_lines_per_file),
+            md: deeper.md.or(self.md),
+        }
+    }
+
+    /// The write options of a file below the overriding directories
+    pub fn apply(&self, options: &WriteOptions) -> WriteOptions {
+        let mut options = options.clone();
+        if let Some(value) = self.line_numbers {
+            options.line_numbers = value;
+        }
+        if let Some(value) = self.strip_comments {
+            options.strip_comments = value;
+        }
+        if let Some(value) = self.max_lines_per_file {
+            // 0 lifts a limit set further up
+            options.max_lines_per_file = Some(value).filter(|&lines| lines > 0);
+        }
+        if let Some(value) = self.md {
+            options.markdown = value;
+        }
+        options
+    }
+}
+
+/// The merged overrides of each file, from the `.mdconcat.toml` files in the
+/// directories between the input directory (`roots`) holding it and the file.
+/// None for files without any, or outside every input directory.
+pub fn for_files(
+    files: &[(PathBuf, PathBuf)],
+    roots: &[PathBuf],
+) -> Result<Vec<Option<DirOverrides>>, ConcatError> {
+    let mut loaded: HashMap<PathBuf, Option<DirOverrides>> = HashMap::new();
+    files
+        .iter()
+        .map(|(_, abs_path)| {
+            let Some(dir) = fs::canonicalize(abs_path)
+                .ok()
+                .and_then(|path| path.parent().map(Path::to_path_buf))
+            else {
+                return Ok(None);
+            };
+            let Some(root) = roots
+                .iter()
+                .filter(|root| dir.starts_with(root))
+                .max_by_key(|root| root.components().count())
+            else {
+                return Ok(None);
+            };
+            let mut dirs: Vec<&Path> = dir
+                .ancestors()
+                .take_while(|ancestor| ancestor.starts_with(root))
+                .collect();
+            dirs.reverse();
+
+            let mut merged: Option<DirOverrides> = None;
+            for dir in dirs {
+                if !loaded.contains_key(dir) {
+                    let path = dir.join(OVERRIDES_FILE);
+                    let overrides = if path.is_file() {
+                        Some(DirOverrides::load(&path)?)
+                    } else {
+                        None
+                    };
+                    loaded.insert(dir.to_path_buf(), overrides);
+                }
+                if let Some(overrides) = &loaded[dir] {
+                    merged = Some(merged.unwrap_or_default().merge(overrides.clone()));
+                }
+            }
+            Ok(merged)
+        })
+        .collect()
+}
@@ -0,0 +1,156 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+
+/// How file paths are shown in headings and listings
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathStyle {
+    /// Relative to the input directory (the default)
+    #[default]
+    Relative,
+    /// Absolute path of the file
+    Absolute,
+    /// File name only
+    Basename,
+    /// Relative to the root of the git repository holding the input
+    FromRoot,
+}
+
+impl PathStyle {
+    /// The path to show for a file; `repository` is the root used by FromRoot,
+    /// which falls back to the relative path for files outside it
+    pub fn display(self, rel_path: &Path, abs_path: &Path, repository: Option<&Path>) -> PathBuf {
+        match self {
+            PathStyle::Relative => rel_path.to_path_buf(),
+            PathStyle::Absolute => abs_path.to_path_buf(),
+            PathStyle::Basename => abs_path
+                .file_name()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| rel_path.to_path_buf()),
+            PathStyle::FromRoot => repository
+                .and_then(|root| abs_path.strip_prefix(root).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| rel_path.to_path_buf()),
+        }
+    }
+}
+
+/// Removes a leading directory shared by the shown paths: the longest one
+/// common to all of them for "auto", otherwise the given one from the paths
+/// below it. Returns the prefix if any path was shortened.
+pub fn strip_prefix(found_files: &mut [(PathBuf, PathBuf)], prefix: &str) -> Option<PathBuf> {
+    let prefix = if prefix == "auto" {
+        common_dir(found_files.iter().map(|(rel_path, _)| rel_path.as_path()))
+    } else {
+        let prefix = Path::new(prefix);
+        // "./src/" and "src" name the same prefix
+        prefix
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect()
+    };
+    let mut stripped = false;
+    for (rel_path, _) in found_files.iter_mut() {
+        if let Ok(rest) = rel_path.strip_prefix(&prefix)
+            && !rest.as_os_str().is_empty()
+        {
+            *rel_path = rest.to_path_buf();
+            stripped = true;
+        }
+    }
+    (stripped && !prefix.as_os_str().is_empty()).then_some(prefix)
+}
+
+/// Longest directory containing all the paths
+fn common_dir<'a>(mut paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+    let Some(first) = paths.next() else {
+        return PathBuf::new();
+    };
+    let mut common: Vec<Component> = first
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .collect();
+    for path in paths {
+        let shared = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .zip(&common)
+            .take_while(|(a, b)| a == *b)
+            .count();
+        common.truncate(shared);
+    }
+    common.into_iter().collect()
+}
+
+/// Closest directory at or above `start` holding a `.git` directory or file
+pub fn repository_root(start: &Path) -> Option<PathBuf> {
+    let start = start.canonicalize().ok()?;
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Renders a relative path with `/` separators whatever the platform
+pub fn portable(path: &Path) -> PathBuf {
+    let parts: Vec<String> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            Component::ParentDir => Some("..".to_string()),
+            _ => None,
+        })
+        .collect();
+    if parts.is_empty() && !path.as_os_str().is_empty() {
+        return PathBuf::from(".");
+    }
+    PathBuf::from(parts.join("/"))
+}
+
+/// An input directory or list file as the document names it. With
+/// --deterministic, absolute paths (which differ between machines) are reduced
+/// to their last component and the others use `/` separators.
+pub fn shown(path: &Path, deterministic: bool) -> PathBuf {
+    match path.file_name() {
+        _ if !deterministic => path.to_path_buf(),
+        Some(name) if path.is_absolute() => PathBuf::from(name),
+        _ => portable(path),
+    }
+}
+
+/// Compares paths by the bytes of their portable form, independent of locale and platform
+pub fn compare_bytes(a: &Path, b: &Path) -> std::cmp::Ordering {
+    portable(a)
+        .as_os_str()
+        .as_encoded_bytes()
+        .cmp(portable(b).as_os_str().as_encoded_bytes())
+}
+
+/// Indented directory tree of the given relative paths
+pub fn tree(paths: &[&Path]) -> String {
+    let mut sorted = paths.to_vec();
+    sorted.sort();
+    let mut tree = String::new();
+    let mut previous_dirs: Vec<String> = Vec::new();
+    for path in sorted {
+        let mut components: Vec<String> = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let name = components.pop().unwrap_or_default();
+        let shared = previous_dirs
+            .iter()
+            .zip(&components)
+            .take_while(|(a, b)| a == b)
+            .count();
+        for (depth, dir) in components.iter().enumerate().skip(shared) {
+            tree.push_str(&format!("{}{}/\n", "  ".repeat(depth), dir));
+        }
+        tree.push_str(&format!("{}{}\n", "  ".repeat(components.len()), name));
+        previous_dirs = components;
+    }
+    tree
+}
@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::config::Config;
+
+/// Built-in bundles of extensions and excluded directories for common ecosystems
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Rust sources, manifests and docs
+    Rust,
+    /// JavaScript/TypeScript sources and JSON
+    Node,
+    /// Python sources and packaging files
+    Python,
+    /// Go sources and module files
+    Go,
+    /// Front-end markup, styles and scripts
+    Web,
+}
+
+impl Preset {
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Preset::Rust => &["rs", "toml", "md"],
+            Preset::Node => &["ts", "tsx", "js", "jsx", "mjs", "cjs", "json"],
+            Preset::Python => &["py", "pyi", "toml", "cfg"],
+            Preset::Go => &["go", "mod"],
+            Preset::Web => &[
+                "html", "css", "scss", "js", "jsx", "ts", "tsx", "vue", "svelte", "json",
+            ],
+        }
+    }
+
+    fn exclude_dirs(self) -> &'static [&'static str] {
+        match self {
+            Preset::Rust => &["target", ".git"],
+            Preset::Node => &["node_modules", "dist", "build", "coverage", ".next", ".git"],
+            Preset::Python => &[
+                "__pycache__",
+                ".venv",
+                "venv",
+                ".tox",
+                ".mypy_cache",
+                ".pytest_cache",
+                "build",
+                "dist",
+                ".git",
+            ],
+            Preset::Go => &["vendor", ".git"],
+            Preset::Web => &["node_modules", "dist", "build", ".git"],
+        }
+    }
+
+    /// The preset expressed as a configuration layer
+    pub fn config(self) -> Config {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Config {
+            extensions: Some(strings(self.extensions())),
+            exclude_dirs: Some(strings(self.exclude_dirs())),
+            ..Config::default()
+        }
+    }
+}
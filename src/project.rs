@@ -0,0 +1,412 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::gitignore::root_labels;
+
+/// Manifest files looked for at the top of each input directory, in this order
+pub const MANIFESTS: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+
+/// A package described by a manifest file
+#[derive(Debug, Default)]
+pub struct Package {
+    /// Manifest path as shown, prefixed with the input directory's name when there are several
+    pub manifest: PathBuf,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// Language or runtime requirement, e.g. "Rust edition 2024" or "Go 1.22"
+    pub requires: Option<String>,
+    /// License expression the manifest declares, e.g. "MIT OR Apache-2.0"
+    pub license: Option<String>,
+    /// Direct dependencies
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug)]
+pub struct Dependency {
+    pub name: String,
+    /// Version requirement as written, or where the dependency comes from (path, git)
+    pub version: String,
+    /// "normal", "dev", "build", "peer", or the name of an optional group
+    pub kind: String,
+}
+
+/// The packages whose manifests sit directly in one of the `roots`. Manifests
+/// that fail to parse are reported and left out.
+pub fn detect(roots: &[PathBuf]) -> Vec<Package> {
+    let mut packages = Vec::new();
+    let labels = root_labels(roots);
+    for (root, label) in roots.iter().zip(&labels) {
+        for file in MANIFESTS {
+            let path = root.join(file);
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let parsed = match *file {
+                "Cargo.toml" => cargo(&text),
+                "package.json" => npm(&text),
+                "pyproject.toml" => pyproject(&text),
+                _ => Ok(go_mod(&text)),
+            };
+            match parsed {
+                Ok(mut package) => {
+                    package.manifest = if roots.len() > 1 {
+                        label.join(file)
+                    } else {
+                        PathBuf::from(file)
+                    };
+                    packages.push(package);
+                }
+                Err(e) => status!("Could not read {}: {}", path.display(), e),
+            }
+        }
+    }
+    packages
+}
+
+/// "Project Overview" section with the name, version, description and
+/// dependencies of each package (--project-summary). It has no code block,
+/// so it is never mistaken for a file section.
+pub fn overview_section(packages: &[Package], heading_level: usize) -> String {
+    let mut section = format!("{} Project Overview\n\n", "#".repeat(heading_level));
+    for package in packages {
+        let mut line = format!("- **{}**", package.name.as_deref().unwrap_or("(unnamed)"));
+        if let Some(ref version) = package.version {
+            let _ = write!(line, " {}", version);
+        }
+        let _ = write!(line, " (`{}`", package.manifest.display());
+        if let Some(ref requires) = package.requires {
+            let _ = write!(line, ", {}", requires);
+        }
+        line.push(')');
+        if let Some(ref description) = package.description {
+            let _ = write!(line, ": {}", description.trim());
+        }
+        let _ = writeln!(section, "{}", line);
+
+        let mut kinds: Vec<&str> = Vec::new();
+        for dependency in &package.dependencies {
+            if !kinds.contains(&dependency.kind.as_str()) {
+                kinds.push(&dependency.kind);
+            }
+        }
+        for kind in kinds {
+            let names: Vec<String> = package
+                .dependencies
+                .iter()
+                .filter(|dependency| dependency.kind == kind)
+                .map(|dependency| match dependency.version.as_str() {
+                    "*" => dependency.name.clone(),
+                    version => format!("{} {}", dependency.name, version),
+                })
+                .collect();
+            let label = match kind {
+                "normal" => "Dependencies".to_string(),
+                "dev" => "Dev dependencies".to_string(),
+                "build" => "Build dependencies".to_string(),
+                "peer" => "Peer dependencies".to_string(),
+                "workspace" => "Workspace dependencies".to_string(),
+                group => format!("Optional `{}` dependencies", group),
+            };
+            let _ = writeln!(
+                section,
+                "  - {} ({}): {}",
+                label,
+                names.len(),
+                names.join(", ")
+            );
+        }
+    }
+    section.push('\n');
+    section
+}
+
+/// "Dependencies" appendix: a table of the direct dependencies of each
+/// package with their version requirements and kinds (--deps-appendix)
+pub fn dependencies_section(packages: &[Package], heading_level: usize) -> String {
+    let mut section = format!("{} Dependencies\n\n", "#".repeat(heading_level));
+    if packages
+        .iter()
+        .all(|package| package.dependencies.is_empty())
+    {
+        section.push_str("No dependencies declared.\n\n");
+        return section;
+    }
+    // Versions such as "^1 || ^2" would end the table cell
+    let cell = |text: &str| text.replace('|', "\\|");
+    let several = packages.len() > 1;
+    if several {
+        section.push_str("| Manifest | Dependency | Version | Kind |\n|---|---|---|---|\n");
+    } else {
+        section.push_str("| Dependency | Version | Kind |\n|---|---|---|\n");
+    }
+    for package in packages {
+        for dependency in &package.dependencies {
+            if several {
+                let _ = write!(section, "| `{}` ", package.manifest.display());
+            }
+            let _ = writeln!(
+                section,
+                "| `{}` | {} | {} |",
+                dependency.name,
+                cell(&dependency.version),
+                match dependency.kind.as_str() {
+                    kind @ ("normal" | "dev" | "build" | "peer" | "workspace") => kind.to_string(),
+                    group => format!("optional ({})", cell(group)),
+                }
+            );
+        }
+    }
+    section.push('\n');
+    section
+}
+
+fn cargo(text: &str) -> Result<Package, String> {
+    let manifest: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+    let package = manifest.get("package");
+    let field = |key: &str| {
+        let value = package?.get(key)?;
+        match value.as_str() {
+            Some(text) => Some(text.to_string()),
+            // `version.workspace = true` and the like
+            None => value
+                .get("workspace")
+                .filter(|inherited| inherited.as_bool() == Some(true))
+                .map(|_| "(from workspace)".to_string()),
+        }
+    };
+    let mut dependencies = Vec::new();
+    for (table, kind) in [
+        ("dependencies", "normal"),
+        ("dev-dependencies", "dev"),
+        ("build-dependencies", "build"),
+    ] {
+        cargo_dependencies(manifest.get(table), kind, &mut dependencies);
+    }
+    cargo_dependencies(
+        manifest
+            .get("workspace")
+            .and_then(|w| w.get("dependencies")),
+        "workspace",
+        &mut dependencies,
+    );
+    Ok(Package {
+        name: field("name").or_else(|| manifest.get("workspace").map(|_| "(workspace)".into())),
+        version: field("version"),
+        description: field("description"),
+        requires: field("edition").map(|edition| format!("Rust edition {}", edition)),
+        license: field("license"),
+        dependencies,
+        ..Default::default()
+    })
+}
+
+fn cargo_dependencies(table: Option<&toml::Value>, kind: &str, into: &mut Vec<Dependency>) {
+    let Some(table) = table.and_then(toml::Value::as_table) else {
+        return;
+    };
+    for (name, spec) in table {
+        let version = match spec {
+            toml::Value::String(version) => version.clone(),
+            spec => {
+                let text = |key: &str| spec.get(key).and_then(toml::Value::as_str);
+                if let Some(version) = text("version") {
+                    version.to_string()
+                } else if spec.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+                    "(from workspace)".to_string()
+                } else if let Some(path) = text("path") {
+                    format!("path {}", path)
+                } else if let Some(git) = text("git") {
+                    format!("git {}", git)
+                } else {
+                    "*".to_string()
+                }
+            }
+        };
+        // `package = "..."` renames a dependency; show the crate it really is
+        let name = spec
+            .get("package")
+            .and_then(toml::Value::as_str)
+            .unwrap_or(name);
+        into.push(Dependency {
+            name: name.to_string(),
+            version,
+            kind: kind.to_string(),
+        });
+    }
+}
+
+fn npm(text: &str) -> Result<Package, String> {
+    let package: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let field = |key: &str| package.get(key)?.as_str().map(str::to_string);
+    let mut dependencies = Vec::new();
+    for (object, kind) in [
+        ("dependencies", "normal"),
+        ("devDependencies", "dev"),
+        ("peerDependencies", "peer"),
+    ] {
+        let Some(object) = package.get(object).and_then(|o| o.as_object()) else {
+            continue;
+        };
+        for (name, version) in object {
+            dependencies.push(Dependency {
+                name: name.clone(),
+                version: version.as_str().unwrap_or("*").to_string(),
+                kind: kind.to_string(),
+            });
+        }
+    }
+    Ok(Package {
+        name: field("name"),
+        version: field("version"),
+        description: field("description"),
+        requires: package
+            .get("engines")
+            .and_then(|engines| engines.get("node")?.as_str())
+            .map(|node| format!("Node {}", node)),
+        license: field("license"),
+        dependencies,
+        ..Default::default()
+    })
+}
+
+/// PEP 621 `[project]` metadata, or Poetry's `[tool.poetry]`
+fn pyproject(text: &str) -> Result<Package, String> {
+    let manifest: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+    let poetry = manifest.get("tool").and_then(|tool| tool.get("poetry"));
+    let Some(project) = manifest.get("project").or(poetry) else {
+        return Ok(Package::default());
+    };
+    let field = |key: &str| project.get(key)?.as_str().map(str::to_string);
+
+    let mut dependencies = Vec::new();
+    let mut requires = field("requires-python");
+    if let Some(list) = project.get("dependencies").and_then(toml::Value::as_array) {
+        pep508_dependencies(list, "normal", &mut dependencies);
+    }
+    if let Some(groups) = project
+        .get("optional-dependencies")
+        .and_then(toml::Value::as_table)
+    {
+        for (group, list) in groups {
+            if let Some(list) = list.as_array() {
+                pep508_dependencies(list, group, &mut dependencies);
+            }
+        }
+    }
+    // Poetry keeps dependencies in tables, with the Python requirement among them
+    let poetry_tables = [
+        (project.get("dependencies"), "normal"),
+        (project.get("dev-dependencies"), "dev"),
+        (
+            project
+                .get("group")
+                .and_then(|group| group.get("dev"))
+                .and_then(|dev| dev.get("dependencies")),
+            "dev",
+        ),
+    ];
+    for (table, kind) in poetry_tables {
+        let Some(table) = table.and_then(toml::Value::as_table) else {
+            continue;
+        };
+        for (name, spec) in table {
+            let version = spec
+                .as_str()
+                .or_else(|| spec.get("version")?.as_str())
+                .unwrap_or("*")
+                .to_string();
+            if name == "python" {
+                requires = Some(version);
+                continue;
+            }
+            dependencies.push(Dependency {
+                name: name.clone(),
+                version,
+                kind: kind.to_string(),
+            });
+        }
+    }
+    Ok(Package {
+        name: field("name"),
+        version: field("version"),
+        description: field("description"),
+        requires: requires.map(|python| format!("Python {}", python)),
+        // A PEP 639 expression, or the older `{ text = "..." }` when it is short enough to be one
+        license: field("license").or_else(|| {
+            project
+                .get("license")?
+                .get("text")?
+                .as_str()
+                .filter(|text| text.len() <= 64 && !text.contains('\n'))
+                .map(str::to_string)
+        }),
+        dependencies,
+        ..Default::default()
+    })
+}
+
+/// Requirements such as `requests[socks]>=2.31; python_version < "3.13"`
+fn pep508_dependencies(list: &[toml::Value], kind: &str, into: &mut Vec<Dependency>) {
+    for requirement in list.iter().filter_map(toml::Value::as_str) {
+        let requirement = requirement.split(';').next().unwrap_or("").trim();
+        let end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+            .unwrap_or(requirement.len());
+        let (name, rest) = requirement.split_at(end);
+        let rest = match rest.trim_start().strip_prefix('[') {
+            Some(extras) => extras.split_once(']').map_or("", |(_, rest)| rest),
+            None => rest,
+        };
+        let version = rest.trim().trim_matches(['(', ')']);
+        into.push(Dependency {
+            name: name.to_string(),
+            version: if version.is_empty() { "*" } else { version }.to_string(),
+            kind: kind.to_string(),
+        });
+    }
+}
+
+/// The module path, Go version and direct requirements of a go.mod; indirect
+/// requirements are left out
+fn go_mod(text: &str) -> Package {
+    let mut package = Package::default();
+    let mut in_require = false;
+    for line in text.lines() {
+        let indirect = line.contains("// indirect");
+        let line = line.split("//").next().unwrap_or("").trim();
+        let requirement = if in_require {
+            if line == ")" {
+                in_require = false;
+                continue;
+            }
+            line
+        } else if let Some(rest) = line.strip_prefix("require") {
+            match rest.trim() {
+                "(" => {
+                    in_require = true;
+                    continue;
+                }
+                rest => rest,
+            }
+        } else {
+            if let Some(module) = line.strip_prefix("module ") {
+                package.name = Some(module.trim().to_string());
+            } else if let Some(version) = line.strip_prefix("go ") {
+                package.requires = Some(format!("Go {}", version.trim()));
+            }
+            continue;
+        };
+        if let Some((name, version)) = requirement.split_once(char::is_whitespace)
+            && !indirect
+        {
+            package.dependencies.push(Dependency {
+                name: name.to_string(),
+                version: version.trim().to_string(),
+                kind: "normal".to_string(),
+            });
+        }
+    }
+    package
+}
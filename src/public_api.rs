@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use tree_sitter::{Node, Parser};
+
+/// Reduces Rust source to its public interface: `pub` items with their doc
+/// comments and attributes, function bodies elided, private struct fields
+/// hidden. Returns None if the file has no public items.
+pub fn summarize(text: &str) -> Option<String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .ok()?;
+    let tree = parser.parse(text, None)?;
+    let root = tree.root_node();
+
+    let mut private_types = HashSet::new();
+    collect_private_types(root, text, &mut private_types);
+    let summary = Summary {
+        text,
+        private_types,
+    };
+    let mut output = String::new();
+    summary.items(root, Scope::Module, &mut output);
+    (!output.is_empty()).then_some(output)
+}
+
+/// What kind of block items are listed from
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Module,
+    /// Inherent impl: only `pub` items are public
+    Impl,
+    /// Trait definition or trait impl: every item is public
+    Trait,
+}
+
+struct Summary<'a> {
+    text: &'a str,
+    /// Types declared without `pub`, whose impls are left out
+    private_types: HashSet<&'a str>,
+}
+
+impl<'a> Summary<'a> {
+    /// Appends the public items among the children of `parent`, separated by blank lines
+    fn items(&self, parent: Node, scope: Scope, output: &mut String) {
+        let mut cursor = parent.walk();
+        let children: Vec<Node> = parent.named_children(&mut cursor).collect();
+        let mut first = true;
+        for (index, node) in children.iter().enumerate() {
+            // Inner doc comments describe the enclosing module
+            if scope == Scope::Module && self.slice(*node).starts_with("//!") {
+                output.push_str(
+                    &self.text[line_start(self.text, node.start_byte())..node.end_byte()],
+                );
+                if !output.ends_with('\n') {
+                    output.push('\n');
+                }
+                continue;
+            }
+            let Some(rendered) = self.item(*node, scope) else {
+                continue;
+            };
+            if !first || (scope == Scope::Module && !output.is_empty()) {
+                output.push('\n');
+            }
+            first = false;
+            let start = self.leading(&children[..index], *node);
+            output.push_str(&self.text[start..node.start_byte()]);
+            output.push_str(&rendered);
+            output.push('\n');
+        }
+    }
+
+    /// The rendered item, if it is part of the public interface
+    fn item(&self, node: Node, scope: Scope) -> Option<String> {
+        let public = scope == Scope::Trait || is_pub(node, self.text);
+        match node.kind() {
+            "function_item" if public => Some(self.elided(node, "body")),
+            "function_signature_item" | "associated_type" if scope == Scope::Trait => {
+                Some(self.slice(node).to_string())
+            }
+            "struct_item" if public => Some(self.structure(node)),
+            "enum_item"
+            | "union_item"
+            | "type_item"
+            | "const_item"
+            | "static_item"
+            | "use_declaration"
+            | "extern_crate_declaration"
+                if public =>
+            {
+                Some(self.slice(node).to_string())
+            }
+            "macro_definition" if scope == Scope::Module && self.exports_macro(node) => {
+                let name = node.child_by_field_name("name")?;
+                Some(format!(
+                    "{} {{ ... }}",
+                    &self.text[node.start_byte()..name.end_byte()]
+                ))
+            }
+            "trait_item" if public => {
+                let body = node.child_by_field_name("body")?;
+                Some(self.block(node, body, Scope::Trait))
+            }
+            "mod_item" if public => match node.child_by_field_name("body") {
+                Some(body) => Some(self.block(node, body, Scope::Module)),
+                None => Some(self.slice(node).to_string()),
+            },
+            "impl_item" if scope == Scope::Module => self.implementation(node),
+            _ => None,
+        }
+    }
+
+    /// Inherent impls list their public items; trait impls are shown as a single line
+    fn implementation(&self, node: Node) -> Option<String> {
+        let self_type = node.child_by_field_name("type")?;
+        if self
+            .private_types
+            .contains(type_name(self.slice(self_type)))
+        {
+            return None;
+        }
+        let body = node.child_by_field_name("body")?;
+        if node.child_by_field_name("trait").is_some() {
+            return Some(self.elided(node, "body"));
+        }
+        let mut inner = String::new();
+        self.items(body, Scope::Impl, &mut inner);
+        (!inner.is_empty()).then(|| self.wrap(node, body, &inner))
+    }
+
+    /// A container item with its public items listed inside
+    fn block(&self, node: Node, body: Node, scope: Scope) -> String {
+        let mut inner = String::new();
+        self.items(body, scope, &mut inner);
+        self.wrap(node, body, &inner)
+    }
+
+    fn wrap(&self, node: Node, body: Node, inner: &str) -> String {
+        let header = &self.text[node.start_byte()..body.start_byte()];
+        if inner.is_empty() {
+            return format!("{}{{}}", header);
+        }
+        format!("{}{{\n{}{}}}", header, inner, self.indent(node))
+    }
+
+    /// A struct with its private named fields replaced by a marker, as rustdoc shows them
+    fn structure(&self, node: Node) -> String {
+        let Some(body) = node.child_by_field_name("body") else {
+            return self.slice(node).to_string();
+        };
+        if body.kind() != "field_declaration_list" {
+            return self.slice(node).to_string();
+        }
+        let mut cursor = body.walk();
+        let children: Vec<Node> = body.named_children(&mut cursor).collect();
+        let mut inner = String::new();
+        let mut hidden = false;
+        for (index, field) in children.iter().enumerate() {
+            if field.kind() != "field_declaration" {
+                continue;
+            }
+            if !is_pub(*field, self.text) {
+                hidden = true;
+                continue;
+            }
+            let start = self.leading(&children[..index], *field);
+            inner.push_str(&self.text[start..field.end_byte()]);
+            inner.push_str(",\n");
+        }
+        if hidden {
+            inner.push_str(&format!("{}    /* private fields */\n", self.indent(node)));
+        }
+        self.wrap(node, body, &inner)
+    }
+
+    /// The item up to its body, with the body replaced by `{ ... }`
+    fn elided(&self, node: Node, body_field: &str) -> String {
+        match node.child_by_field_name(body_field) {
+            Some(body) => format!(
+                "{} {{ ... }}",
+                self.text[node.start_byte()..body.start_byte()].trim_end()
+            ),
+            _ => self.slice(node).to_string(),
+        }
+    }
+
+    /// Start of the first line of the doc comments and attributes right before `node`
+    fn leading(&self, previous: &[Node], node: Node) -> usize {
+        let mut first = node;
+        for sibling in previous.iter().rev() {
+            let is_doc = match sibling.kind() {
+                "line_comment" => self.slice(*sibling).starts_with("///"),
+                "block_comment" => self.slice(*sibling).starts_with("/**"),
+                "attribute_item" => true,
+                _ => false,
+            };
+            if !is_doc || sibling.end_position().row + 1 < first.start_position().row {
+                break;
+            }
+            first = *sibling;
+        }
+        line_start(self.text, first.start_byte())
+    }
+
+    /// Whether a `macro_rules!` definition is preceded by `#[macro_export]`
+    fn exports_macro(&self, node: Node) -> bool {
+        let mut previous = node.prev_named_sibling();
+        while let Some(sibling) = previous {
+            match sibling.kind() {
+                "attribute_item" if self.slice(sibling).contains("macro_export") => return true,
+                "attribute_item" | "line_comment" | "block_comment" => {}
+                _ => return false,
+            }
+            previous = sibling.prev_named_sibling();
+        }
+        false
+    }
+
+    fn indent(&self, node: Node) -> &'a str {
+        &self.text[line_start(self.text, node.start_byte())..node.start_byte()]
+    }
+
+    fn slice(&self, node: Node) -> &'a str {
+        &self.text[node.byte_range()]
+    }
+}
+
+/// Whether the item is declared plain `pub` (restricted visibility such as `pub(crate)` is not public)
+fn is_pub(node: Node, text: &str) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .any(|child| child.kind() == "visibility_modifier" && &text[child.byte_range()] == "pub")
+}
+
+fn collect_private_types<'a>(node: Node, text: &'a str, types: &mut HashSet<&'a str>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "struct_item" | "enum_item" | "union_item" | "type_item" if !is_pub(child, text) => {
+                if let Some(name) = child.child_by_field_name("name") {
+                    types.insert(&text[name.byte_range()]);
+                }
+            }
+            "mod_item" => {
+                if let Some(body) = child.child_by_field_name("body") {
+                    collect_private_types(body, text, types);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `Stack<T>` and `crate::Stack` -> `Stack`
+fn type_name(ty: &str) -> &str {
+    let ty = ty.split('<').next().unwrap_or(ty);
+    ty.rsplit("::").next().unwrap_or(ty).trim()
+}
+
+fn line_start(text: &str, byte: usize) -> usize {
+    text[..byte].rfind('\n').map_or(0, |newline| newline + 1)
+}
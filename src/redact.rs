@@ -0,0 +1,222 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ConcatError;
+use crate::writer::FileStats;
+
+/// Built-in secret patterns as (name, regex). When a pattern has a capture
+/// group, only the group is replaced so the surrounding key name stays readable.
+const SECRET_RULES: &[(&str, &str)] = &[
+    (
+        "private-key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY(?: BLOCK)?-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY(?: BLOCK)?-----",
+    ),
+    (
+        "aws-key",
+        r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA|AIPA|ANPA|ANVA)[A-Z0-9]{16}\b",
+    ),
+    (
+        "aws-secret",
+        r#"(?i)aws_?secret_?(?:access_?)?key["']?\s*[:=]\s*["']?([A-Za-z0-9/+=]{40})\b"#,
+    ),
+    (
+        "github-token",
+        r"\b(?:ghp|gho|ghu|ghs|ghr)_[A-Za-z0-9]{36}\b",
+    ),
+    ("github-token", r"\bgithub_pat_[A-Za-z0-9_]{60,}\b"),
+    ("gitlab-token", r"\bglpat-[A-Za-z0-9_-]{20}\b"),
+    ("slack-token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}\b"),
+    ("stripe-key", r"\b[rs]k_live_[A-Za-z0-9]{20,}\b"),
+    ("google-api-key", r"\bAIza[0-9A-Za-z_-]{35}\b"),
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+    ),
+    ("bearer-token", r"(?i)\bbearer\s+([A-Za-z0-9._~+/-]{20,}=*)"),
+    (
+        "password",
+        r#"(?i)(?:password|passwd|secret|api_?key|access_?token|auth_?token)["']?\s*[:=]\s*["']([^"'\s]{8,})["']"#,
+    ),
+];
+
+/// Assignments in .env files, whose values are redacted wholesale
+const ENV_RULE: (&str, &str) = (
+    "env-value",
+    r"(?m)^[ \t]*(?:export[ \t]+)?[A-Za-z_][A-Za-z0-9_]*[ \t]*=[ \t]*([^\s#].*?)[ \t]*$",
+);
+
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    regex: Regex,
+    replacement: String,
+    /// Replace only the first capture group (built-in rules) instead of the
+    /// whole match with `$group` references expanded (user rules)
+    secret_group: bool,
+    /// Only applies to .env files
+    env_only: bool,
+}
+
+/// A rules file given with --redact-rules
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    name: String,
+    pattern: String,
+    /// Defaults to `[REDACTED:<name>]`; may reference capture groups as `$1` or `${name}`
+    replacement: Option<String>,
+}
+
+/// Content transform that replaces secrets and other sensitive text
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    rules: Vec<Rule>,
+}
+
+/// Number of replacements per rule name
+pub type RedactionCounts = BTreeMap<String, usize>;
+
+impl Redactor {
+    /// Builds a redactor from the built-in secret rules (if enabled) followed
+    /// by the rules in the given file
+    pub fn new(secrets: bool, rules_file: Option<&Path>) -> Result<Self, ConcatError> {
+        let mut rules = Vec::new();
+        if secrets {
+            let builtin = SECRET_RULES.iter().map(|&rule| (rule, false));
+            rules.extend(
+                builtin
+                    .chain([(ENV_RULE, true)])
+                    .map(|((name, pattern), env_only)| Rule {
+                        name: name.to_string(),
+                        regex: Regex::new(pattern).expect("built-in redaction rule is valid"),
+                        replacement: format!("[REDACTED:{}]", name),
+                        secret_group: true,
+                        env_only,
+                    }),
+            );
+        }
+        if let Some(path) = rules_file {
+            rules.extend(load_rules(path)?);
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies every rule to the content of the given file
+    pub fn redact(&self, rel_path: &Path, mut text: String) -> (String, RedactionCounts) {
+        let mut counts = RedactionCounts::new();
+        let is_env = is_env_file(rel_path);
+
+        for rule in self.rules.iter().filter(|rule| is_env || !rule.env_only) {
+            let mut output = String::with_capacity(text.len());
+            let mut last = 0;
+            let mut count = 0;
+            for captures in rule.regex.captures_iter(&text) {
+                let whole = captures.get(0).expect("group 0 is the whole match");
+                if !rule.secret_group {
+                    output.push_str(&text[last..whole.start()]);
+                    captures.expand(&rule.replacement, &mut output);
+                    last = whole.end();
+                    count += 1;
+                    continue;
+                }
+
+                // Replace the secret group if the pattern has one, else the whole match
+                let target = captures.get(1).unwrap_or(whole);
+                if target.as_str().starts_with("[REDACTED") {
+                    continue;
+                }
+                output.push_str(&text[last..target.start()]);
+                output.push_str(&rule.replacement);
+                last = target.end();
+                count += 1;
+            }
+            if count > 0 {
+                output.push_str(&text[last..]);
+                text = output;
+                *counts.entry(rule.name.clone()).or_default() += count;
+            }
+        }
+
+        (text, counts)
+    }
+}
+
+fn load_rules(path: &Path) -> Result<Vec<Rule>, ConcatError> {
+    let invalid = |reason: String| {
+        ConcatError::Config(format!(
+            "invalid redaction rules {}: {}",
+            path.display(),
+            reason
+        ))
+    };
+    let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let file: RulesFile = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+
+    file.rule
+        .into_iter()
+        .map(|spec| {
+            let regex = Regex::new(&spec.pattern)
+                .map_err(|e| invalid(format!("rule '{}': {}", spec.name, e)))?;
+            Ok(Rule {
+                replacement: spec
+                    .replacement
+                    .unwrap_or_else(|| format!("[REDACTED:{}]", spec.name)),
+                name: spec.name,
+                regex,
+                secret_group: false,
+                env_only: false,
+            })
+        })
+        .collect()
+}
+
+fn is_env_file(rel_path: &Path) -> bool {
+    let name = rel_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    name == ".env" || name.starts_with(".env.") || name.ends_with(".env")
+}
+
+/// Prints how many matches were redacted per rule, overall and for each file
+pub fn print_report(stats: &[FileStats]) {
+    let mut totals = RedactionCounts::new();
+    for file in stats {
+        for (name, count) in &file.redactions {
+            *totals.entry(name.clone()).or_default() += count;
+        }
+    }
+    if totals.is_empty() {
+        status!("Nothing found to redact");
+        return;
+    }
+
+    let total: usize = totals.values().sum();
+    status!("Redacted {} match(es): {}", total, describe(&totals));
+    for file in stats.iter().filter(|file| !file.redactions.is_empty()) {
+        status!(
+            "  {}: {}",
+            file.rel_path.display(),
+            describe(&file.redactions)
+        );
+    }
+}
+
+fn describe(counts: &RedactionCounts) -> String {
+    counts
+        .iter()
+        .map(|(name, count)| format!("{} {}", name, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
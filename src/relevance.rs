@@ -0,0 +1,93 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+/// BM25 document length normalization
+const B: f64 = 0.75;
+
+/// Scores each file against a keyword query with BM25 over the words of its
+/// path and content. Identifiers are split into their parts (`reconnectBackoff`,
+/// `reconnect_backoff` -> `reconnect`, `backoff`), so queries in plain words
+/// match code. Files sharing no word with the query score 0.
+pub fn scores(files: &[(PathBuf, PathBuf)], query: &str) -> Vec<f64> {
+    let query: HashSet<String> = words(query).collect();
+    let documents: Vec<HashMap<String, usize>> = files
+        .iter()
+        .map(|(rel_path, abs_path)| {
+            let content = fs::read(abs_path)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default();
+            let mut counts = HashMap::new();
+            for word in words(&rel_path.to_string_lossy()).chain(words(&content)) {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+            counts
+        })
+        .collect();
+    let lengths: Vec<usize> = documents.iter().map(|doc| doc.values().sum()).collect();
+    let average = lengths.iter().sum::<usize>() as f64 / lengths.len().max(1) as f64;
+
+    let count = documents.len() as f64;
+    let idf: HashMap<&str, f64> = query
+        .iter()
+        .map(|term| {
+            let containing = documents
+                .iter()
+                .filter(|doc| doc.contains_key(term))
+                .count() as f64;
+            let idf = ((count - containing + 0.5) / (containing + 0.5) + 1.0).ln();
+            (term.as_str(), idf)
+        })
+        .collect();
+
+    documents
+        .iter()
+        .zip(&lengths)
+        .map(|(doc, &length)| {
+            let norm = K1 * (1.0 - B + B * length as f64 / average.max(1.0));
+            idf.iter()
+                .map(|(term, idf)| {
+                    let frequency = doc.get(*term).copied().unwrap_or(0) as f64;
+                    idf * frequency * (K1 + 1.0) / (frequency + norm)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Lowercase words of `text`, with identifiers split at underscores, dashes
+/// and lower-to-upper case changes, and plurals reduced to the singular
+/// (`tokens` -> `token`). Single characters are left out.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .flat_map(split_camel_case)
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| singular(word.to_lowercase()))
+}
+
+fn singular(mut word: String) -> String {
+    if word.len() > 4 && word.ends_with("ies") {
+        word.truncate(word.len() - 3);
+        word.push('y');
+    } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+        word.pop();
+    }
+    word
+}
+
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut previous_lower = false;
+    for (i, c) in word.char_indices() {
+        if c.is_uppercase() && previous_lower {
+            parts.push(&word[start..i]);
+            start = i;
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    parts.push(&word[start..]);
+    parts
+}
@@ -0,0 +1,157 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::PER_FILE_STRATEGY;
+use crate::writer::FileStats;
+
+/// Extra reports printed after a run
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Report {
+    /// Bytes, tokens and file counts per directory
+    Dirs,
+    /// Candidate files and directories that were left out, and why
+    Skipped,
+}
+
+/// A file or directory left out of the output
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Skipped {
+    pub fn new(path: PathBuf, reason: impl Into<String>) -> Self {
+        Self {
+            path,
+            reason: reason.into(),
+        }
+    }
+
+    /// A directory that was not descended into; shown with a trailing slash
+    pub fn dir(path: PathBuf, reason: impl Into<String>) -> Self {
+        let mut shown = path.into_os_string();
+        shown.push("/");
+        Self::new(PathBuf::from(shown), reason)
+    }
+}
+
+/// Size of the included files below one directory
+#[derive(Default)]
+pub struct DirTotals {
+    pub files: usize,
+    pub bytes: usize,
+    pub tokens: usize,
+}
+
+/// Aggregates the included files by their directory, cut to the first `depth`
+/// components, largest first. Files above that depth are grouped under ".".
+pub fn dir_totals<'a>(
+    stats: impl IntoIterator<Item = &'a FileStats>,
+    depth: usize,
+) -> Vec<(PathBuf, DirTotals)> {
+    let mut dirs: BTreeMap<PathBuf, DirTotals> = BTreeMap::new();
+    for file in stats {
+        let parent = file.rel_path.parent().unwrap_or(file.rel_path.as_path());
+        let dir: PathBuf = parent.components().take(depth).collect();
+        let dir = if dir.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            dir
+        };
+        let totals = dirs.entry(dir).or_default();
+        totals.files += 1;
+        totals.bytes += file.size;
+        totals.tokens += file.tokens();
+    }
+
+    let mut rows: Vec<(PathBuf, DirTotals)> = dirs.into_iter().collect();
+    rows.sort_by(|a, b| b.1.tokens.cmp(&a.1.tokens).then_with(|| a.0.cmp(&b.0)));
+    rows
+}
+
+/// Prints the per-directory totals of --report dirs
+pub fn print_dirs(stats: &[FileStats], depth: usize) {
+    let rows = dir_totals(stats, depth);
+    let total_tokens: usize = rows.iter().map(|(_, totals)| totals.tokens).sum();
+    let width = rows
+        .iter()
+        .map(|(dir, _)| dir.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("Directory".len());
+
+    status!(
+        "\n{:<width$}  {:>6}  {:>10}  {:>10}  {:>6}",
+        "Directory",
+        "Files",
+        "Bytes",
+        "~Tokens",
+        "Share"
+    );
+    for (dir, totals) in &rows {
+        status!(
+            "{:<width$}  {:>6}  {:>10}  {:>10}  {:>5.1}%",
+            dir.display().to_string(),
+            totals.files,
+            totals.bytes,
+            totals.tokens,
+            totals.tokens as f64 * 100.0 / total_tokens.max(1) as f64
+        );
+    }
+}
+
+/// Lists what was left out, in the order it was decided: while walking the
+/// input directories, while narrowing down the selection, and while reading
+/// the files (--skip-unreadable)
+pub fn print_skipped(skipped: &[Skipped], stats: &[FileStats]) {
+    let unreadable = stats
+        .iter()
+        .filter(|file| file.unreadable && file.char_count == 0)
+        .map(|file| {
+            Skipped::new(
+                file.rel_path.clone(),
+                "unreadable, e.g. binary or not valid text (--skip-unreadable)",
+            )
+        });
+    let rows: Vec<Skipped> = skipped.iter().cloned().chain(unreadable).collect();
+    if rows.is_empty() {
+        status!("\nNo candidate files were skipped");
+        return;
+    }
+
+    let width = rows
+        .iter()
+        .map(|row| row.path.display().to_string().len())
+        .max()
+        .unwrap_or(0);
+    status!("\nSkipped {} candidate(s):", rows.len());
+    for row in &rows {
+        status!(
+            "  {:<width$}  {}",
+            row.path.display().to_string(),
+            row.reason
+        );
+    }
+}
+
+/// Warns on stderr about every file estimated above `limit` tokens, largest first
+pub fn warn_large_files(stats: &[FileStats], limit: usize) {
+    let mut large: Vec<(&FileStats, usize)> = stats
+        .iter()
+        .map(|file| (file, PER_FILE_STRATEGY.estimate(file.content_chars)))
+        .filter(|(_, tokens)| *tokens > limit)
+        .collect();
+    large.sort_by_key(|(_, tokens)| std::cmp::Reverse(*tokens));
+    for (file, tokens) in large {
+        eprintln!(
+            "Warning: {} is ~{} tokens, above --warn-file-tokens {}",
+            file.rel_path.display(),
+            tokens,
+            limit
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use regex::{Regex, RegexSet};
+use std::sync::LazyLock;
+
+use crate::writer::FileStats;
+
+/// Instruction-like phrases that try to steer a model reading the output
+const PATTERNS: &[&str] = &[
+    r"(?i)\b(?:ignore|disregard|forget|override)\s+(?:all\s+|any\s+)?(?:the\s+|your\s+)?(?:previous|prior|above|earlier|preceding|system)\s+(?:instructions?|prompts?|messages?|context|rules)",
+    r"(?i)\bforget\s+(?:everything|all)\s+(?:you\s+(?:were\s+)?(?:told|know)|above)",
+    r"(?i)\byou\s+are\s+now\s+(?:a|an|in|the)\b",
+    r"(?i)\b(?:new|updated|real)\s+(?:system\s+)?instructions?\s*:",
+    r"(?i)\b(?:reveal|print|output|repeat)\s+(?:your|the)\s+(?:system\s+prompt|instructions)",
+    r"(?i)\bdo\s+not\s+(?:tell|inform|alert)\s+the\s+user\b",
+    r"(?im)^\s*(?:system|assistant)\s*:",
+    r"<\|(?:im_start|im_end|endoftext|system|user|assistant)\|>",
+    r"\[/?INST\]|<</?SYS>>",
+    r"(?i)</?(?:system|instructions?)>",
+];
+
+static SET: LazyLock<RegexSet> =
+    LazyLock::new(|| RegexSet::new(PATTERNS).expect("sanitize patterns are valid"));
+static REGEXES: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    PATTERNS
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("sanitize pattern is valid"))
+        .collect()
+});
+
+/// Wraps every suspicious phrase in a visible marker so it reads as quoted
+/// data rather than an instruction. Returns the new text and the number of
+/// phrases neutralized.
+pub fn sanitize(text: String) -> (String, usize) {
+    let matched = SET.matches(&text);
+    if !matched.matched_any() {
+        return (text, 0);
+    }
+
+    let mut text = text;
+    let mut count = 0;
+    for index in matched.iter() {
+        let mut output = String::with_capacity(text.len());
+        let mut last = 0;
+        for found in REGEXES[index].find_iter(&text) {
+            // Phrases already wrapped by an earlier pattern are left alone
+            if text[..found.start()].ends_with("[neutralized: ") {
+                continue;
+            }
+            output.push_str(&text[last..found.start()]);
+            output.push_str("[neutralized: ");
+            output.push_str(found.as_str());
+            output.push(']');
+            last = found.end();
+            count += 1;
+        }
+        output.push_str(&text[last..]);
+        text = output;
+    }
+    (text, count)
+}
+
+/// Lists the files in which suspicious phrases were neutralized
+pub fn print_report(stats: &[FileStats]) {
+    let flagged: Vec<&FileStats> = stats.iter().filter(|file| file.sanitized > 0).collect();
+    if flagged.is_empty() {
+        status!("No instruction-like content found");
+        return;
+    }
+
+    let total: usize = flagged.iter().map(|file| file.sanitized).sum();
+    status!(
+        "Neutralized {} instruction-like phrase(s) in {} file(s):",
+        total,
+        flagged.len()
+    );
+    for file in flagged {
+        status!("  {}: {}", file.rel_path.display(), file.sanitized);
+    }
+}
@@ -0,0 +1,196 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal, Write};
+
+/// Lets the user pick a subset of `candidates` with an interactive fuzzy finder
+/// drawn on stderr. Returns the chosen indices in ascending order, or `None` if
+/// the selection was cancelled.
+pub fn fuzzy_select(candidates: &[String]) -> io::Result<Option<Vec<usize>>> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(io::Error::other(
+            "--select requires an interactive terminal",
+        ));
+    }
+
+    let mut stderr = io::stderr();
+    terminal::enable_raw_mode()?;
+    execute!(stderr, EnterAlternateScreen, Hide)?;
+
+    let result = Selector::new(candidates).run(&mut stderr);
+
+    execute!(stderr, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+struct Selector<'a> {
+    candidates: &'a [String],
+    matcher: Matcher,
+    query: String,
+    /// Indices of candidates matching the query, best match first
+    filtered: Vec<usize>,
+    selected: BTreeSet<usize>,
+    cursor: usize,
+    scroll: usize,
+}
+
+impl<'a> Selector<'a> {
+    fn new(candidates: &'a [String]) -> Self {
+        Self {
+            candidates,
+            matcher: Matcher::new(Config::DEFAULT.match_paths()),
+            query: String::new(),
+            filtered: (0..candidates.len()).collect(),
+            selected: BTreeSet::new(),
+            cursor: 0,
+            scroll: 0,
+        }
+    }
+
+    fn run(mut self, out: &mut impl Write) -> io::Result<Option<Vec<usize>>> {
+        loop {
+            self.render(out)?;
+
+            let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            }) = event::read()?
+            else {
+                continue;
+            };
+            let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+
+            match code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Enter => {
+                    if self.selected.is_empty()
+                        && let Some(&current) = self.filtered.get(self.cursor)
+                    {
+                        self.selected.insert(current);
+                    }
+                    return Ok(Some(self.selected.into_iter().collect()));
+                }
+                KeyCode::Up => self.move_cursor(-1),
+                KeyCode::Char('k') if ctrl => self.move_cursor(-1),
+                KeyCode::Down => self.move_cursor(1),
+                KeyCode::Char('j') if ctrl => self.move_cursor(1),
+                KeyCode::Tab => {
+                    if let Some(&current) = self.filtered.get(self.cursor)
+                        && !self.selected.remove(&current)
+                    {
+                        self.selected.insert(current);
+                    }
+                    self.move_cursor(1);
+                }
+                KeyCode::Char('a') if ctrl => {
+                    if self.filtered.iter().all(|i| self.selected.contains(i)) {
+                        for i in &self.filtered {
+                            self.selected.remove(i);
+                        }
+                    } else {
+                        self.selected.extend(self.filtered.iter().copied());
+                    }
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.refilter();
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.query.push(c);
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let last = self.filtered.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+    }
+
+    fn refilter(&mut self) {
+        let pattern = Pattern::parse(&self.query, CaseMatching::Smart, Normalization::Smart);
+        let mut buf = Vec::new();
+        let mut scored: Vec<(usize, u32)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, candidate)| {
+                let haystack = Utf32Str::new(candidate, &mut buf);
+                pattern
+                    .score(haystack, &mut self.matcher)
+                    .map(|score| (i, score))
+            })
+            .collect();
+        if !self.query.is_empty() {
+            scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        }
+        self.filtered = scored.into_iter().map(|(i, _)| i).collect();
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    fn render(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let rows = usize::from(height).saturating_sub(2).max(1);
+        let width = usize::from(width);
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + rows {
+            self.scroll = self.cursor + 1 - rows;
+        }
+
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        queue!(out, Print(truncate(&format!("> {}", self.query), width)))?;
+        let summary = format!(
+            "  {}/{} matching, {} selected  (Tab: toggle, Ctrl-A: toggle all, Enter: accept, Esc: cancel)",
+            self.filtered.len(),
+            self.candidates.len(),
+            self.selected.len()
+        );
+        queue!(out, MoveTo(0, 1), Print(truncate(&summary, width)))?;
+
+        for (row, &index) in self
+            .filtered
+            .iter()
+            .skip(self.scroll)
+            .take(rows)
+            .enumerate()
+        {
+            let marker = if self.selected.contains(&index) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let line = truncate(&format!("{} {}", marker, self.candidates[index]), width);
+            queue!(out, MoveTo(0, (row + 2) as u16))?;
+            if self.scroll + row == self.cursor {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(line),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+
+        out.flush()
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
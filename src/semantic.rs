@@ -0,0 +1,163 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::ConcatError;
+
+/// Default location of the embedding cache, in the working directory
+pub const EMBEDDINGS_FILE: &str = ".md_concat_embeddings.json";
+
+/// Scores each file by the cosine similarity between the query and its most
+/// similar chunk, using a local embedding model. Chunk embeddings are kept in
+/// `cache_file`, so only new or changed chunks are embedded again.
+#[cfg(feature = "semantic")]
+pub fn scores(
+    files: &[(PathBuf, PathBuf)],
+    query: &str,
+    cache_file: &Path,
+) -> Result<Vec<f64>, ConcatError> {
+    use fastembed::{EmbeddingModel, TextEmbedding, TextInitOptions};
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use crate::cache::sha256_hex;
+
+    let model_error = |e: fastembed::Error| ConcatError::Config(format!("embedding model: {}", e));
+    let mut model = TextEmbedding::try_new(
+        TextInitOptions::new(EmbeddingModel::AllMiniLML6V2).with_show_download_progress(false),
+    )
+    .map_err(model_error)?;
+
+    let cached = EmbeddingCache::load(cache_file);
+    let chunks: Vec<Vec<(String, String)>> = files
+        .iter()
+        .map(|(rel_path, abs_path)| {
+            let content = fs::read(abs_path)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_default();
+            chunk(&rel_path.to_string_lossy(), &content)
+                .into_iter()
+                .map(|text| (sha256_hex(text.as_bytes()), text))
+                .collect()
+        })
+        .collect();
+
+    let missing: Vec<&(String, String)> = chunks
+        .iter()
+        .flatten()
+        .filter(|(hash, _)| !cached.embeddings.contains_key(hash))
+        .collect();
+    status!(
+        "Embedding {} of {} chunk(s) for --semantic-query",
+        missing.len(),
+        chunks.iter().map(Vec::len).sum::<usize>()
+    );
+    let texts: Vec<&str> = missing.iter().map(|(_, text)| text.as_str()).collect();
+    let embedded = if texts.is_empty() {
+        Vec::new()
+    } else {
+        model.embed(&texts, None).map_err(model_error)?
+    };
+    let fresh: BTreeMap<&str, &[f32]> = missing
+        .iter()
+        .map(|(hash, _)| hash.as_str())
+        .zip(embedded.iter().map(Vec::as_slice))
+        .collect();
+
+    let query = model.embed([query], None).map_err(model_error)?.remove(0);
+    let mut cache = EmbeddingCache::default();
+    let scores = chunks
+        .iter()
+        .map(|file| {
+            file.iter()
+                .filter_map(|(hash, _)| {
+                    let embedding = fresh
+                        .get(hash.as_str())
+                        .map(|embedding| embedding.to_vec())
+                        .or_else(|| cached.embeddings.get(hash).cloned())?;
+                    let score = cosine(&query, &embedding);
+                    cache.embeddings.insert(hash.clone(), embedding);
+                    Some(score)
+                })
+                .fold(f64::NEG_INFINITY, f64::max)
+        })
+        .collect();
+
+    // Only the chunks of this run are kept, so the cache does not grow without bound
+    if let Err(e) = cache.save(cache_file) {
+        status!("Could not write {}: {}", cache_file.display(), e);
+    }
+    Ok(scores)
+}
+
+#[cfg(not(feature = "semantic"))]
+pub fn scores(
+    _files: &[(PathBuf, PathBuf)],
+    _query: &str,
+    _cache_file: &Path,
+) -> Result<Vec<f64>, ConcatError> {
+    Err(ConcatError::Config(
+        "--semantic-query needs md_concat built with `--features semantic`".into(),
+    ))
+}
+
+/// Embeddings of file chunks from previous runs, keyed by the SHA-256 of the chunk text
+#[cfg(feature = "semantic")]
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct EmbeddingCache {
+    /// Model that produced the embeddings; a cache of another model is discarded
+    model: String,
+    embeddings: std::collections::BTreeMap<String, Vec<f32>>,
+}
+
+#[cfg(feature = "semantic")]
+impl EmbeddingCache {
+    const MODEL: &str = "all-MiniLM-L6-v2";
+
+    fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Self>(&text).ok())
+            .filter(|cache| cache.model == Self::MODEL)
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let cache = Self {
+            model: Self::MODEL.to_string(),
+            embeddings: self.embeddings.clone(),
+        };
+        std::fs::write(path, serde_json::to_string(&cache)?)?;
+        Ok(())
+    }
+}
+
+/// Upper bound of a chunk, in characters; the model reads at most 256 word pieces
+#[cfg(feature = "semantic")]
+const CHUNK_CHARS: usize = 1000;
+
+/// Splits a file into chunks of whole lines, each headed by the file's path so
+/// that names of files and directories count towards every chunk
+#[cfg(feature = "semantic")]
+fn chunk(path: &str, content: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in content.lines() {
+        if !current.is_empty() && current.len() + line.len() > CHUNK_CHARS {
+            chunks.push(format!("{}\n{}", path, current));
+            current.clear();
+        }
+        let line: String = line.chars().take(CHUNK_CHARS).collect();
+        current.push_str(&line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() || chunks.is_empty() {
+        chunks.push(format!("{}\n{}", path, current));
+    }
+    chunks
+}
+
+#[cfg(feature = "semantic")]
+fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    dot / (norm(a) * norm(b)).max(f64::EPSILON)
+}
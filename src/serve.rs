@@ -0,0 +1,240 @@
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::document;
+use crate::error::ConcatError;
+use crate::writer::write_document;
+use crate::{CliArgs, TokenCounter};
+
+/// Builds the arguments of a packing run from JSON filters, as sent to the MCP
+/// `pack_repo` tool and the HTTP `/pack` endpoint. Configuration files in the
+/// working directory apply as they do on the command line. Paths must stay
+/// inside the working directory.
+pub fn pack_args(filters: &Value) -> Result<CliArgs, ConcatError> {
+    let mut argv = vec!["md_concat".to_string(), "--dry-run".to_string()];
+    if let Some(paths) = string_list(filters, "paths")? {
+        // Commas would split one path into several input directories
+        if let Some(path) = paths
+            .iter()
+            .find(|path| path.contains(',') || document::safe_relative(Path::new(path)).is_none())
+        {
+            return Err(ConcatError::Config(format!(
+                "{}: path leaves the working directory",
+                path
+            )));
+        }
+        argv.push(format!("--input-dirs={}", paths.join(",")));
+    }
+    if let Some(extensions) = string_list(filters, "extensions")? {
+        argv.push(format!("--extensions={}", extensions.join(",")));
+    }
+    if let Some(exclude_dirs) = string_list(filters, "exclude_dirs")? {
+        argv.push(format!("--exclude-dirs={}", exclude_dirs.join(",")));
+    }
+    if let Some(budget) = filters.get("budget") {
+        let budget = budget
+            .as_u64()
+            .ok_or_else(|| ConcatError::Config("budget must be a positive integer".into()))?;
+        argv.push(format!("--max-tokens={}", budget));
+    }
+
+    let args = crate::parse_args(argv)?;
+    if args.extensions.is_empty() && args.files_from.is_none() && args.from_manifest.is_none() {
+        return Err(ConcatError::Config(
+            "extensions are required (in the request or in a configuration file)".into(),
+        ));
+    }
+    Ok(args)
+}
+
+/// Reads an optional array of strings from the filters
+fn string_list(filters: &Value, key: &str) -> Result<Option<Vec<String>>, ConcatError> {
+    let Some(value) = filters.get(key) else {
+        return Ok(None);
+    };
+    value
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .map(Some)
+        .ok_or_else(|| ConcatError::Config(format!("{} must be an array of strings", key)))
+}
+
+/// Serves the packing API on `addr` until the process is stopped. Only
+/// loopback addresses are accepted unless `allow_remote` is set.
+pub fn http(addr: &str, allow_remote: bool) -> Result<(), ConcatError> {
+    if !allow_remote && !is_loopback(addr)? {
+        return Err(ConcatError::Config(format!(
+            "{} is not a loopback address; the HTTP API has no authentication \
+             (pass --allow-remote to listen on it anyway)",
+            addr
+        )));
+    }
+    let server = Server::http(addr)
+        .map_err(|e| ConcatError::Config(format!("cannot listen on {}: {}", addr, e)))?;
+    status!("Listening on http://{}", addr);
+    // Runs triggered by requests would otherwise log configuration sources each time
+    crate::QUIET.store(true, Ordering::Relaxed);
+
+    for request in server.incoming_requests() {
+        thread::spawn(move || {
+            if let Err(e) = handle(request) {
+                eprintln!("Warning: Request failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Whether every address `addr` resolves to is a loopback address
+fn is_loopback(addr: &str) -> Result<bool, ConcatError> {
+    let resolved: Vec<SocketAddr> = addr
+        .to_socket_addrs()
+        .map_err(|e| ConcatError::Config(format!("cannot listen on {}: {}", addr, e)))?
+        .collect();
+    Ok(!resolved.is_empty() && resolved.iter().all(|addr| addr.ip().is_loopback()))
+}
+
+fn handle(mut request: Request) -> io::Result<()> {
+    match (request.method(), request.url()) {
+        (Method::Get, "/health") => request.respond(json_response(
+            200,
+            &json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }),
+        )),
+        (Method::Post, "/pack") => {
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body)?;
+            match start_pack(&body) {
+                Ok(response) => request.respond(response),
+                Err(e) => request.respond(error_response(&e)),
+            }
+        }
+        (_, "/health" | "/pack") => request.respond(json_response(
+            405,
+            &json!({ "error": "method not allowed" }),
+        )),
+        _ => request.respond(json_response(404, &json!({ "error": "not found" }))),
+    }
+}
+
+/// Selects the files up front so that problems become error responses, then
+/// streams the document to the client while it is being written
+fn start_pack(body: &str) -> Result<Response<io::PipeReader>, ConcatError> {
+    let filters: Value = if body.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(body)
+            .map_err(|e| ConcatError::Config(format!("invalid request body: {}", e)))?
+    };
+    let args = pack_args(&filters)?;
+    let (found_files, options) = crate::prepare(&args)?;
+
+    let (reader, mut writer) = io::pipe()?;
+    let file_count = found_files.len();
+    thread::spawn(move || {
+        let result = write_document(
+            &mut writer,
+            &found_files,
+            &mut TokenCounter::new(),
+            &options,
+        )
+        .and_then(|_| writer.flush().map_err(ConcatError::from));
+        if let Err(e) = result {
+            eprintln!("Warning: Packing run failed: {}", e);
+        }
+    });
+
+    Ok(Response::new(
+        StatusCode(200),
+        vec![
+            header("Content-Type", "text/markdown; charset=utf-8"),
+            header("X-Md-Concat-Files", &file_count.to_string()),
+        ],
+        reader,
+        None,
+        None,
+    ))
+}
+
+fn error_response(error: &ConcatError) -> Response<io::Cursor<Vec<u8>>> {
+    let status = match error {
+        ConcatError::Config(_) | ConcatError::InputDir { .. } | ConcatError::InputFile { .. } => {
+            400
+        }
+        ConcatError::NoFilesMatched => 422,
+        _ => 500,
+    };
+    json_response(status, &json!({ "error": error.to_string() }))
+}
+
+fn json_response(status: u16, body: &Value) -> Response<io::Cursor<Vec<u8>>> {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn paths(paths: &[&str]) -> Value {
+        json!({ "paths": paths, "extensions": ["rs"] })
+    }
+
+    #[test]
+    fn only_loopback_addresses_count_as_local() {
+        assert!(is_loopback("127.0.0.1:8765").unwrap());
+        assert!(is_loopback("[::1]:8765").unwrap());
+        assert!(!is_loopback("0.0.0.0:8765").unwrap());
+        assert!(!is_loopback("192.168.1.10:8765").unwrap());
+        assert!(is_loopback("no-port").is_err());
+    }
+
+    #[test]
+    fn remote_addresses_need_allow_remote() {
+        assert!(matches!(
+            http("0.0.0.0:0", false),
+            Err(ConcatError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn paths_inside_the_working_directory_are_accepted() {
+        let args = pack_args(&paths(&["src", "./docs"])).unwrap();
+        assert_eq!(
+            args.input_dirs,
+            vec![PathBuf::from("src"), PathBuf::from("./docs")]
+        );
+    }
+
+    #[test]
+    fn paths_leaving_the_working_directory_are_rejected() {
+        for path in [
+            "../outside",
+            "src/../../outside",
+            "/etc",
+            "src,../outside",
+            "",
+        ] {
+            assert!(
+                matches!(pack_args(&paths(&[path])), Err(ConcatError::Config(_))),
+                "{} was accepted",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::PER_FILE_STRATEGY;
+use crate::document;
+use crate::error::ConcatError;
+
+/// Summarizes which file sections were added, removed or changed between two
+/// generated documents, with token deltas
+pub fn diff(old: &Path, new: &Path) -> Result<(), ConcatError> {
+    let old_text = read(old)?;
+    let new_text = read(new)?;
+    let old_sections = contents(&old_text);
+    let new_sections = contents(&new_text);
+
+    let mut paths: Vec<&PathBuf> = old_sections.keys().chain(new_sections.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for path in paths {
+        let line = match (old_sections.get(path), new_sections.get(path)) {
+            (None, Some(content)) => {
+                added += 1;
+                format!(
+                    "+ added    {} ({:+} tokens)",
+                    path.display(),
+                    tokens(content) as i64
+                )
+            }
+            (Some(content), None) => {
+                removed += 1;
+                format!(
+                    "- removed  {} ({:+} tokens)",
+                    path.display(),
+                    -(tokens(content) as i64)
+                )
+            }
+            (Some(before), Some(after)) if before != after => {
+                changed += 1;
+                format!(
+                    "~ changed  {} ({:+} tokens)",
+                    path.display(),
+                    tokens(after) as i64 - tokens(before) as i64
+                )
+            }
+            _ => continue,
+        };
+        status!("{}", line);
+    }
+
+    let old_tokens = tokens(&old_text);
+    let new_tokens = tokens(&new_text);
+    status!(
+        "\n{} added, {} removed, {} changed; ~{} -> ~{} tokens ({:+})",
+        added,
+        removed,
+        changed,
+        old_tokens,
+        new_tokens,
+        new_tokens as i64 - old_tokens as i64
+    );
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<String, ConcatError> {
+    fs::read_to_string(path).map_err(|source| ConcatError::InputFile {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Content of every section by path; sections that cannot be resolved count as empty
+fn contents(text: &str) -> BTreeMap<PathBuf, String> {
+    document::resolve(&document::parse(text))
+        .into_iter()
+        .map(|(path, content)| (path, content.unwrap_or_default()))
+        .collect()
+}
+
+fn tokens(text: &str) -> usize {
+    PER_FILE_STRATEGY.estimate(text.chars().count())
+}
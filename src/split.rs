@@ -0,0 +1,390 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::ConcatError;
+use crate::output;
+use crate::paths;
+use crate::writer::{CountingWriter, FileStats, WriteOptions, write_document, write_files};
+use crate::{PER_FILE_STRATEGY, TokenCounter};
+
+/// How --split-by groups the files into documents
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitBy {
+    /// One document per top-level directory, plus one for the files at the top
+    Dir,
+    /// One document per fence language (`rust`, `sql`, ...), as --lang-map sets it
+    Language,
+}
+
+impl SplitBy {
+    /// Name of the group a file belongs to, also used in its document's file name.
+    /// `language` is the file's fence language.
+    fn group(self, rel_path: &Path, language: &str) -> String {
+        match self {
+            SplitBy::Dir => {
+                let mut components = rel_path.components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(dir)), Some(_)) => dir.to_string_lossy().into_owned(),
+                    _ => "root".to_string(),
+                }
+            }
+            SplitBy::Language => {
+                if language.is_empty() {
+                    return "text".to_string();
+                }
+                // Usable in a file name: "c++" stays, "objective c" becomes "objective-c"
+                language
+                    .chars()
+                    .map(|c| {
+                        if c.is_alphanumeric() || "+-_#".contains(c) {
+                            c
+                        } else {
+                            '-'
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// How a group is described in the index
+    fn describe(self, group: &str) -> String {
+        match self {
+            SplitBy::Dir if group == "root" => "Files at the top level".to_string(),
+            SplitBy::Dir => format!("`{}/`", group),
+            SplitBy::Language => format!("`{}`", group),
+        }
+    }
+}
+
+/// Writes one document per group of files next to `output_file` ("out.md"
+/// becomes "out.backend.md", "out.frontend.md", ... or "out.rust.md",
+/// "out.sql.md", ...), each with the files of
+/// that group in output order, and `output_file` itself as an index linking to
+/// them (--split-by). Returns one entry per input file, in the same order.
+pub fn write_groups(
+    output_file: &Path,
+    found_files: &[(PathBuf, PathBuf)],
+    split_by: SplitBy,
+    force: bool,
+    token_counter: &mut TokenCounter,
+    options: &WriteOptions,
+) -> Result<Vec<FileStats>, ConcatError> {
+    // Fence languages as the sections will show them, judged from the content
+    // for extensions shared by several languages. Warnings come with the groups.
+    let languages: Vec<String> = match split_by {
+        SplitBy::Language => write_files(
+            &mut io::sink(),
+            found_files,
+            &mut TokenCounter::new(),
+            &WriteOptions {
+                quiet: true,
+                ..options.clone()
+            },
+        )?
+        .into_iter()
+        .map(|file| file.language)
+        .collect(),
+        SplitBy::Dir => vec![String::new(); found_files.len()],
+    };
+
+    // Groups in order of their first file
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, (rel_path, _)) in found_files.iter().enumerate() {
+        let name = split_by.group(rel_path, &languages[index]);
+        match groups.iter_mut().find(|(group, _)| *group == name) {
+            Some((_, members)) => members.push(index),
+            None => groups.push((name, vec![index])),
+        }
+    }
+
+    let paths: Vec<PathBuf> = groups
+        .iter()
+        .map(|(name, _)| group_path(output_file, name))
+        .collect();
+    if !force
+        && let Some(existing) = paths
+            .iter()
+            .chain([&output_file.to_path_buf()])
+            .find(|path| path.exists())
+    {
+        return Err(ConcatError::OutputExists(existing.clone()));
+    }
+
+    let mut stats: Vec<Option<FileStats>> = found_files.iter().map(|_| None).collect();
+    let mut index = format!(
+        "{} Index\n\nThe files are split into {} document(s) by {}:\n\n",
+        "#".repeat(options.heading_level.saturating_sub(1).max(1)),
+        groups.len(),
+        split_by
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    );
+    for ((name, members), path) in groups.iter().zip(&paths) {
+        let files: Vec<(PathBuf, PathBuf)> =
+            members.iter().map(|&i| found_files[i].clone()).collect();
+        let group_stats = output::write_atomic(path, |writer| {
+            write_document(writer, &files, token_counter, options)
+        })?;
+        let group_total: usize = group_stats.iter().map(FileStats::tokens).sum();
+        status!(
+            "{}: {} file(s), ~{} tokens -> {}",
+            name,
+            files.len(),
+            group_total,
+            path.display()
+        );
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let _ = writeln!(
+            index,
+            "- [{}]({}): {} file(s), ~{} tokens",
+            split_by.describe(name),
+            file_name,
+            files.len(),
+            group_total
+        );
+        for (&i, file_stats) in members.iter().zip(group_stats) {
+            stats[i] = Some(file_stats);
+        }
+    }
+    index.push('\n');
+    output::write_atomic(
+        output_file,
+        |writer| Ok(writer.write_all(index.as_bytes())?),
+    )?;
+    status!(
+        "Index of {} document(s) -> {}",
+        groups.len(),
+        output_file.display()
+    );
+    Ok(stats.into_iter().flatten().collect())
+}
+
+/// Whether `path` looks like one of the documents --split-by or --split-tokens
+/// writes next to `output_file`, so a later run does not read them back in
+pub fn is_group_document(output_file: &Path, path: &Path) -> bool {
+    let (Some(output_dir), Some(dir)) = (
+        crate::canonicalize_output(output_file)
+            .and_then(|output| output.parent().map(Path::to_path_buf)),
+        path.parent(),
+    ) else {
+        return false;
+    };
+    let stem = output_file
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let suffix = output_file
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    dir == output_dir
+        && name.starts_with(&format!("{}.", stem))
+        && name.ends_with(&suffix)
+        && name.len() > stem.len() + 1 + suffix.len()
+}
+
+/// "out.md" -> "out.backend.md"
+fn group_path(output_file: &Path, group: &str) -> PathBuf {
+    let stem = output_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match output_file.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, group, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, group),
+    };
+    output_file.with_file_name(name)
+}
+
+/// Writes the document as numbered parts of at most about `part_tokens` tokens
+/// each, next to `output_file` ("out.md" becomes "out.part1.md", ...). Files are
+/// never split; every part after the first starts with a recap of the others.
+/// Returns one entry per input file, in the same order.
+pub fn write_parts(
+    output_file: &Path,
+    found_files: &[(PathBuf, PathBuf)],
+    part_tokens: usize,
+    force: bool,
+    token_counter: &mut TokenCounter,
+    options: &WriteOptions,
+) -> Result<Vec<FileStats>, ConcatError> {
+    let sizes = write_files(
+        &mut io::sink(),
+        found_files,
+        &mut TokenCounter::new(),
+        options,
+    )?;
+    let overhead = PER_FILE_STRATEGY
+        .estimate(options.preamble.chars().count() + options.epilogue.chars().count());
+    let parts = partition(&sizes, part_tokens.saturating_sub(overhead));
+
+    let paths: Vec<PathBuf> = (1..=parts.len())
+        .map(|number| part_path(output_file, number))
+        .collect();
+    if !force && let Some(existing) = paths.iter().find(|path| path.exists()) {
+        return Err(ConcatError::OutputExists(existing.clone()));
+    }
+
+    let mut stats = Vec::with_capacity(found_files.len());
+    for (index, range) in parts.iter().enumerate() {
+        let part_stats = output::write_atomic(&paths[index], |writer| {
+            if index > 0 {
+                let recap = recap(found_files, &parts, index, options);
+                write!(
+                    CountingWriter::new(&mut *writer, token_counter),
+                    "{}",
+                    recap
+                )?;
+            }
+            write_document(writer, &found_files[range.clone()], token_counter, options)
+        })?;
+        let part_total: usize = part_stats.iter().map(FileStats::tokens).sum();
+        status!(
+            "Part {} of {}: {} file(s), ~{} tokens -> {}",
+            index + 1,
+            parts.len(),
+            range.len(),
+            part_total,
+            paths[index].display()
+        );
+        stats.extend(part_stats);
+    }
+    Ok(stats)
+}
+
+/// Groups consecutive files into parts that stay within `limit` tokens. A file
+/// larger than the limit gets a part of its own.
+fn partition(sizes: &[FileStats], limit: usize) -> Vec<Range<usize>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut tokens = 0;
+    for (index, file) in sizes.iter().enumerate() {
+        if index > start && tokens + file.tokens() > limit {
+            parts.push(start..index);
+            start = index;
+            tokens = 0;
+        }
+        tokens += file.tokens();
+    }
+    parts.push(start..sizes.len());
+    parts
+}
+
+/// "out.md" -> "out.part2.md"
+fn part_path(output_file: &Path, number: usize) -> PathBuf {
+    let stem = output_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match output_file.extension() {
+        Some(ext) => format!("{}.part{}.{}", stem, number, ext.to_string_lossy()),
+        None => format!("{}.part{}", stem, number),
+    };
+    output_file.with_file_name(name)
+}
+
+/// Header of a continuation part: its position, the files already covered and
+/// a tree of the files in this and the following parts
+fn recap(
+    found_files: &[(PathBuf, PathBuf)],
+    parts: &[Range<usize>],
+    index: usize,
+    options: &WriteOptions,
+) -> String {
+    let current = &parts[index];
+    let mut recap = format!(
+        "{} Part {} of {}\n\nThis document continues from earlier parts, which contained:\n\n",
+        "#".repeat(options.heading_level.saturating_sub(1).max(1)),
+        index + 1,
+        parts.len()
+    );
+    for (rel_path, _) in &found_files[..current.start] {
+        recap.push_str(&format!("- `{}`\n", rel_path.display()));
+    }
+    let remaining: Vec<&Path> = found_files[current.start..]
+        .iter()
+        .map(|(rel_path, _)| rel_path.as_path())
+        .collect();
+    let tree = paths::tree(&remaining);
+    let fence = options.fence.fence_for(&tree);
+    recap.push_str(&format!(
+        "\nFiles in this and the remaining parts:\n\n{}text\n{}{}\n\n---\n\n",
+        fence, tree, fence
+    ));
+    recap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn parts_and_group_documents_are_recognised() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let output_file = dir.join("out.md");
+
+        assert!(is_group_document(&output_file, &part_path(&output_file, 1)));
+        assert!(is_group_document(
+            &output_file,
+            &part_path(&output_file, 12)
+        ));
+        assert!(is_group_document(
+            &output_file,
+            &group_path(&output_file, "backend")
+        ));
+        assert!(!is_group_document(&output_file, &dir.join("out.rs")));
+        assert!(!is_group_document(&output_file, &dir.join("outline.md")));
+        assert!(!is_group_document(
+            &output_file,
+            &dir.join("docs").join("out.part1.md")
+        ));
+    }
+
+    #[test]
+    fn language_groups_follow_the_fence_language() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = dir.path().join("api.h");
+        fs::write(&header, "@interface Api : NSObject\n@end\n").unwrap();
+        let args =
+            crate::parse_args(["md_concat", "out.md", "--no-config", "--extensions=h"]).unwrap();
+        let options = WriteOptions::from_args(&args).unwrap();
+        let files = [(PathBuf::from("api.h"), header)];
+
+        let stats =
+            write_files(&mut io::sink(), &files, &mut TokenCounter::new(), &options).unwrap();
+        assert_ne!(
+            stats[0].language,
+            options.languages.language_for(&files[0].0)
+        );
+        assert_eq!(
+            SplitBy::Language.group(&files[0].0, &stats[0].language),
+            stats[0].language.replace(' ', "-")
+        );
+        assert_eq!(SplitBy::Language.group(Path::new("notes"), ""), "text");
+        assert_eq!(SplitBy::Dir.group(Path::new("src/a.rs"), ""), "src");
+        assert_eq!(SplitBy::Dir.group(Path::new("a.rs"), ""), "root");
+    }
+
+    #[test]
+    fn partition_keeps_parts_within_the_limit() {
+        let sizes: Vec<FileStats> = [40, 40, 40, 200, 10]
+            .iter()
+            .map(|&tokens| FileStats {
+                char_count: tokens * 3,
+                ..FileStats::default()
+            })
+            .collect();
+        assert_eq!(partition(&sizes, 100), vec![0..2, 2..3, 3..4, 4..5]);
+        assert_eq!(partition(&sizes, 1000), vec![0..5]);
+    }
+}
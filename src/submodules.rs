@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How directories registered as git submodules are collected
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmoduleMode {
+    /// Walk submodules like any other directory
+    #[default]
+    Include,
+    /// Leave submodules out
+    Exclude,
+    /// Only the files at the top of each submodule (README, manifests, ...)
+    Shallow,
+}
+
+/// Submodule directories found in the `.gitmodules` files of the input directories
+#[derive(Debug, Default)]
+pub struct Submodules {
+    mode: SubmoduleMode,
+    dirs: HashSet<PathBuf>,
+}
+
+impl Submodules {
+    pub fn discover(input_dirs: &[PathBuf], mode: SubmoduleMode) -> Self {
+        let mut dirs = HashSet::new();
+        if mode != SubmoduleMode::Include {
+            for input_dir in input_dirs {
+                let Ok(text) = fs::read_to_string(input_dir.join(".gitmodules")) else {
+                    continue;
+                };
+                for line in text.lines() {
+                    if let Some((key, value)) = line.split_once('=')
+                        && key.trim() == "path"
+                    {
+                        dirs.insert(input_dir.join(value.trim()));
+                    }
+                }
+            }
+        }
+        Self { mode, dirs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// Whether the walk must not descend into `dir`
+    pub fn skips_dir(&self, dir: &Path) -> bool {
+        match self.mode {
+            SubmoduleMode::Include => false,
+            SubmoduleMode::Exclude => self.dirs.contains(dir),
+            SubmoduleMode::Shallow => dir.parent().is_some_and(|parent| self.within(parent)),
+        }
+    }
+
+    fn within(&self, path: &Path) -> bool {
+        path.ancestors()
+            .any(|ancestor| self.dirs.contains(ancestor))
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::budget::PackStrategy;
+use crate::writer::FileStats;
+use crate::{CliArgs, PER_FILE_STRATEGY, paths, report};
+
+/// Closing section of a document: file count, a tree of the files, tokens per
+/// directory and the parameters of the run (--summary-footer)
+#[derive(Debug, Clone)]
+pub struct Footer {
+    /// Options of the run, as (label, value) pairs
+    parameters: Vec<(&'static str, String)>,
+    /// Directory depth of the per-directory totals (--report-depth)
+    depth: usize,
+}
+
+impl Footer {
+    pub fn new(args: &CliArgs) -> Self {
+        let mut parameters = Vec::new();
+        let code = |values: &[String]| -> String {
+            values
+                .iter()
+                .filter(|value| !value.is_empty())
+                .map(|value| format!("`{}`", value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (&args.files_from, &args.from_manifest) {
+            (Some(list), _) => parameters.push((
+                "Files from",
+                format!("`{}`", paths::shown(list, args.deterministic).display()),
+            )),
+            (None, Some(manifest)) => parameters.push((
+                "From manifest",
+                format!("`{}`", paths::shown(manifest, args.deterministic).display()),
+            )),
+            (None, None) => {
+                let roots: Vec<String> = args
+                    .input_dirs
+                    .iter()
+                    .map(|root| paths::shown(root, args.deterministic).display().to_string())
+                    .collect();
+                parameters.push(("Input directories", code(&roots)));
+                if let Some(ref reference) = args.at_ref {
+                    parameters.push(("Revision", format!("`{}`", reference)));
+                }
+            }
+        }
+        parameters.push(("Extensions", code(&args.extensions)));
+        for (label, values) in [
+            ("Excluded directories", &args.exclude_dirs),
+            ("Only paths", &args.only_paths),
+            ("Workspace members", &args.workspace_member),
+        ] {
+            let values = code(values);
+            if !values.is_empty() {
+                parameters.push((label, values));
+            }
+        }
+        if let Some(mode) = args.tests {
+            parameters.push(("Tests", format!("{:?}", mode).to_lowercase()));
+        }
+        parameters.push((
+            "Gitignore",
+            if args.respect_gitignore {
+                "respected"
+            } else {
+                "ignored"
+            }
+            .to_string(),
+        ));
+        if !args.seed.is_empty() {
+            let seeds: Vec<String> = args
+                .seed
+                .iter()
+                .map(|seed| seed.display().to_string())
+                .collect();
+            parameters.push((
+                "Seeds",
+                format!(
+                    "{} ({} import hop(s))",
+                    code(&seeds),
+                    args.expand_imports.unwrap_or(0)
+                ),
+            ));
+        }
+        if let Some(ref query) = args.relevant_to {
+            parameters.push(("Relevant to", format!("\"{}\"", query)));
+        }
+        if let Some(ref query) = args.semantic_query {
+            parameters.push(("Semantic query", format!("\"{}\"", query)));
+        }
+        if let Some(max_tokens) = args.max_tokens {
+            let mut budget = match args.pack {
+                PackStrategy::Truncate => max_tokens.to_string(),
+                PackStrategy::Greedy => format!("{} (greedy packing)", max_tokens),
+            };
+            if args.weight_recency {
+                budget.push_str(", weighted by git recency");
+            }
+            parameters.push(("Token budget", budget));
+        }
+        if let Some(max_bytes) = args.max_output_bytes {
+            parameters.push(("Size limit", format!("{} bytes", max_bytes)));
+        }
+        parameters.push((
+            "Generator",
+            format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        ));
+        Self {
+            parameters,
+            depth: args.report_depth.into(),
+        }
+    }
+
+    /// Renders the section for the files written to the document. It has no
+    /// code block, so it is never mistaken for a file section.
+    pub fn render(&self, stats: &[FileStats], heading_level: usize) -> String {
+        let included: Vec<&FileStats> = stats.iter().filter(|file| file.char_count > 0).collect();
+        let tokens: usize = included.iter().map(|file| file.tokens()).sum();
+        let mut summary = format!("{} Summary\n\n", "#".repeat(heading_level));
+        let _ = writeln!(
+            summary,
+            "{} file(s), ~{} tokens ({} estimate).\n",
+            included.len(),
+            tokens,
+            PER_FILE_STRATEGY.name()
+        );
+
+        let paths: Vec<&Path> = included
+            .iter()
+            .map(|file| file.rel_path.as_path())
+            .collect();
+        // The indented tree as a nested list
+        for line in paths::tree(&paths).lines() {
+            let name = line.trim_start();
+            let _ = writeln!(summary, "{}- `{}`", &line[..line.len() - name.len()], name);
+        }
+
+        summary.push_str("\n| Directory | Files | ~Tokens | Share |\n|---|---:|---:|---:|\n");
+        for (dir, totals) in report::dir_totals(included, self.depth) {
+            let _ = writeln!(
+                summary,
+                "| `{}` | {} | {} | {:.1}% |",
+                dir.display(),
+                totals.files,
+                totals.tokens,
+                totals.tokens as f64 * 100.0 / tokens.max(1) as f64
+            );
+        }
+
+        summary.push('\n');
+        for (label, value) in &self.parameters {
+            let _ = writeln!(summary, "- {}: {}", label, value);
+        }
+        summary.push('\n');
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_footer_shows_the_last_component_of_absolute_roots() {
+        let args = crate::parse_args([
+            "md_concat",
+            "out.md",
+            "--no-config",
+            "--extensions=rs",
+            "--input-dirs=/tmp/t1/secret_payments",
+            "--summary-footer",
+            "--deterministic",
+        ])
+        .unwrap();
+        let footer = Footer::new(&args).render(&[], 2);
+        assert!(footer.contains("`secret_payments`"), "{}", footer);
+        assert!(!footer.contains("/tmp/t1"), "{}", footer);
+    }
+
+    #[test]
+    fn footer_cannot_be_combined_with_anonymized_paths() {
+        let argv = [
+            "md_concat",
+            "out.md",
+            "--no-config",
+            "--extensions=rs",
+            "--summary-footer",
+            "--anonymize-paths",
+        ];
+        assert!(crate::parse_args(argv).is_err());
+    }
+}
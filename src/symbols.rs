@@ -0,0 +1,370 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor, Tree};
+
+/// A definition found in a source file
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// Name, qualified with the enclosing type for methods (`Type::method` in Rust, `Type.method` elsewhere)
+    pub name: String,
+    /// Kind from the grammar's tags query: function, method, class, interface, module, ...
+    pub kind: String,
+    /// 1-based line of the definition
+    pub line: usize,
+    /// Byte range of the definition node
+    pub range: Range<usize>,
+    /// Byte range of the whole item: the definition with its doc comments, attributes,
+    /// decorators and export keyword, from the start of its first line
+    pub item: Range<usize>,
+}
+
+impl Symbol {
+    /// Whether `wanted` names this symbol, qualified (`Type::method`) or not (`method`)
+    fn is_named(&self, wanted: &str) -> bool {
+        self.name == wanted || self.name.rsplit(['.', ':']).next() == Some(wanted)
+    }
+}
+
+/// Grammar and tags query for a file, chosen by extension
+fn grammar(rel_path: &Path) -> Option<(Language, String, &'static str)> {
+    let ext = rel_path.extension()?.to_str()?;
+    Some(match ext {
+        "rs" => (
+            tree_sitter_rust::LANGUAGE.into(),
+            tree_sitter_rust::TAGS_QUERY.to_string(),
+            "::",
+        ),
+        "py" | "pyi" => (
+            tree_sitter_python::LANGUAGE.into(),
+            tree_sitter_python::TAGS_QUERY.to_string(),
+            ".",
+        ),
+        "js" | "jsx" | "mjs" | "cjs" => (
+            tree_sitter_javascript::LANGUAGE.into(),
+            tree_sitter_javascript::TAGS_QUERY.to_string(),
+            ".",
+        ),
+        "ts" | "mts" | "cts" => (
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            typescript_query(),
+            ".",
+        ),
+        "tsx" => (
+            tree_sitter_typescript::LANGUAGE_TSX.into(),
+            typescript_query(),
+            ".",
+        ),
+        "go" => (
+            tree_sitter_go::LANGUAGE.into(),
+            tree_sitter_go::TAGS_QUERY.to_string(),
+            ".",
+        ),
+        _ => return None,
+    })
+}
+
+/// The TypeScript grammar extends JavaScript's, but its tags query only covers
+/// the additions; functions and methods come from the JavaScript patterns.
+fn typescript_query() -> String {
+    format!(
+        "{}\n{}",
+        TYPESCRIPT_BASE_QUERY,
+        tree_sitter_typescript::TAGS_QUERY
+    )
+}
+
+const TYPESCRIPT_BASE_QUERY: &str = r#"
+(function_declaration name: (identifier) @name) @definition.function
+(generator_function_declaration name: (identifier) @name) @definition.function
+(class_declaration name: (type_identifier) @name) @definition.class
+(method_definition name: (property_identifier) @name) @definition.method
+(enum_declaration name: (identifier) @name) @definition.enum
+(type_alias_declaration name: (type_identifier) @name) @definition.type
+(lexical_declaration
+  (variable_declarator
+    name: (identifier) @name
+    value: [(arrow_function) (function_expression)])) @definition.function
+"#;
+
+/// Syntax tree of `text`, for the languages of the symbol index
+pub fn syntax_tree(rel_path: &Path, text: &str) -> Option<Tree> {
+    let (language, _, _) = grammar(rel_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    parser.parse(text, None)
+}
+
+/// Parses `text` with the grammar for its extension
+fn parse(rel_path: &Path, text: &str) -> Option<(Tree, Query, &'static str)> {
+    let (language, query, separator) = grammar(rel_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(text, None)?;
+    let query = Query::new(&language, &query).ok()?;
+    Some((tree, query, separator))
+}
+
+/// Definitions in `text`, in source order. Files in unsupported languages, and
+/// files that fail to parse, have none.
+pub fn symbols(rel_path: &Path, text: &str) -> Vec<Symbol> {
+    match parse(rel_path, text) {
+        Some((tree, query, separator)) => definitions(&tree, &query, separator, text),
+        None => Vec::new(),
+    }
+}
+
+fn definitions(tree: &Tree, query: &Query, separator: &str, text: &str) -> Vec<Symbol> {
+    let names = query.capture_names();
+
+    let mut symbols = Vec::new();
+    // Nodes matched by several patterns (e.g. Rust methods are also functions) are listed once
+    let mut seen = HashSet::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(query, tree.root_node(), text.as_bytes());
+    while let Some(found) = matches.next() {
+        let mut name = None;
+        let mut definition = None;
+        for capture in found.captures() {
+            let capture_name = names[capture.index as usize];
+            if capture_name == "name" {
+                name = capture.node.utf8_text(text.as_bytes()).ok();
+            } else if let Some(kind) = capture_name.strip_prefix("definition.") {
+                definition = Some((kind, capture.node));
+            }
+        }
+        let (Some(name), Some((kind, node))) = (name, definition) else {
+            continue;
+        };
+        if !seen.insert(node.start_byte()) {
+            continue;
+        }
+        let name = match container(node, text) {
+            Some(container) => format!("{}{}{}", container, separator, name),
+            None => name.to_string(),
+        };
+        symbols.push(Symbol {
+            name,
+            kind: kind.to_string(),
+            line: node.start_position().row + 1,
+            range: node.byte_range(),
+            item: item_range(node, text),
+        });
+    }
+    symbols.sort_by_key(|symbol| symbol.range.start);
+    symbols
+}
+
+/// The named definitions of `text` with their doc comments, in source order and
+/// separated by blank lines, or None if the file defines none of them
+pub fn extract(rel_path: &Path, text: &str, names: &[String]) -> Option<String> {
+    extract_where(rel_path, text, |symbol| {
+        names.iter().any(|name| symbol.is_named(name))
+    })
+}
+
+/// Like [`extract`], for the definitions with exactly these qualified names
+pub fn extract_qualified(rel_path: &Path, text: &str, names: &HashSet<String>) -> Option<String> {
+    extract_where(rel_path, text, |symbol| names.contains(&symbol.name))
+}
+
+fn extract_where(rel_path: &Path, text: &str, keep: impl Fn(&Symbol) -> bool) -> Option<String> {
+    let mut extracted = String::new();
+    let mut end = 0;
+    for symbol in symbols(rel_path, text).into_iter().filter(keep) {
+        // A type asked for along with one of its methods already contains it
+        if symbol.item.start < end {
+            continue;
+        }
+        if !extracted.is_empty() {
+            extracted.push('\n');
+        }
+        extracted.push_str(text[symbol.item.clone()].trim_end());
+        extracted.push('\n');
+        end = symbol.item.end;
+    }
+    (!extracted.is_empty()).then_some(extracted)
+}
+
+/// Finds the functions and methods reachable from `entry` through calls, as
+/// the qualified names of each source, or None if no function is named `entry`.
+/// Calls are resolved by name alone: `client.send()` reaches every `send`, and
+/// `Client::send()` only `Client::send` when such a method exists.
+pub fn slice(sources: &[(&Path, &str)], entry: &str) -> Option<Vec<HashSet<String>>> {
+    // Every function of every source, with the names it calls
+    let mut functions: Vec<(usize, Symbol, Vec<String>)> = Vec::new();
+    for (source, (rel_path, text)) in sources.iter().enumerate() {
+        let Some((tree, query, separator)) = parse(rel_path, text) else {
+            continue;
+        };
+        let callables: Vec<Symbol> = definitions(&tree, &query, separator, text)
+            .into_iter()
+            .filter(|symbol| matches!(symbol.kind.as_str(), "function" | "method"))
+            .collect();
+        let mut calls = vec![Vec::new(); callables.len()];
+        for (position, callee) in call_sites(tree.root_node(), text) {
+            for (i, callable) in callables.iter().enumerate() {
+                if callable.range.contains(&position) {
+                    calls[i].push(callee.clone());
+                }
+            }
+        }
+        functions.extend(
+            callables
+                .into_iter()
+                .zip(calls)
+                .map(|(symbol, calls)| (source, symbol, calls)),
+        );
+    }
+
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (_, symbol, _)) in functions.iter().enumerate() {
+        by_name.entry(symbol.name.as_str()).or_default().push(i);
+        if let Some(short) = symbol.name.rsplit(['.', ':']).next()
+            && short != symbol.name
+        {
+            by_name.entry(short).or_default().push(i);
+        }
+    }
+
+    let mut queue: Vec<usize> = (0..functions.len())
+        .filter(|&i| functions[i].1.is_named(entry))
+        .collect();
+    if queue.is_empty() {
+        return None;
+    }
+    let mut reached: HashSet<usize> = queue.iter().copied().collect();
+    while let Some(i) = queue.pop() {
+        for callee in &functions[i].2 {
+            let short = callee.rsplit(['.', ':']).next().unwrap_or(callee);
+            let targets = by_name
+                .get(callee.as_str())
+                .or_else(|| by_name.get(short))
+                .into_iter()
+                .flatten();
+            for &target in targets {
+                if reached.insert(target) {
+                    queue.push(target);
+                }
+            }
+        }
+    }
+
+    let mut sliced = vec![HashSet::new(); sources.len()];
+    for i in reached {
+        let (source, symbol, _) = &functions[i];
+        sliced[*source].insert(symbol.name.clone());
+    }
+    Some(sliced)
+}
+
+/// Calls in the tree, as the byte offset of each call and the name it calls:
+/// the last `Type::name` or `name` of the callee, without generic arguments
+fn call_sites(root: Node, text: &str) -> Vec<(usize, String)> {
+    let mut calls = Vec::new();
+    let mut cursor = root.walk();
+    let mut descended = true;
+    loop {
+        let node = cursor.node();
+        if descended
+            && matches!(node.kind(), "call_expression" | "call")
+            && let Some(callee) = node
+                .child_by_field_name("function")
+                .and_then(|callee| callee.utf8_text(text.as_bytes()).ok())
+        {
+            let callee = callee.split(['<', '(']).next().unwrap_or(callee);
+            let parts: Vec<&str> = callee
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .filter(|part| !part.is_empty())
+                .collect();
+            let name = match parts.as_slice() {
+                [.., ty, name] if callee.contains(&format!("{}::{}", ty, name)) => {
+                    format!("{}::{}", ty, name)
+                }
+                [.., name] => name.to_string(),
+                [] => String::new(),
+            };
+            if !name.is_empty() {
+                calls.push((node.start_byte(), name));
+            }
+        }
+        if descended && cursor.goto_first_child() {
+            continue;
+        }
+        if cursor.goto_next_sibling() {
+            descended = true;
+            continue;
+        }
+        if !cursor.goto_parent() {
+            break;
+        }
+        descended = false;
+    }
+    calls
+}
+
+/// Extends a definition to the wrapper nodes and preceding comments that belong to it
+fn item_range(node: Node, text: &str) -> Range<usize> {
+    let mut item = node;
+    while let Some(parent) = item.parent()
+        && matches!(parent.kind(), "decorated_definition" | "export_statement")
+    {
+        item = parent;
+    }
+    let mut first = item;
+    while let Some(previous) = first.prev_sibling()
+        && matches!(
+            previous.kind(),
+            "line_comment" | "block_comment" | "comment" | "attribute_item"
+        )
+        && previous.end_position().row + 1 >= first.start_position().row
+    {
+        first = previous;
+    }
+    let line_start = text[..first.start_byte()]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    line_start..item.end_byte()
+}
+
+/// Name of the type, trait or class a definition is nested in
+fn container(node: Node, text: &str) -> Option<String> {
+    if node.kind() == "method_declaration" {
+        // Go: func (m *Manager) Name()
+        let receiver = node.child_by_field_name("receiver")?;
+        return find_kind(receiver, "type_identifier")
+            .and_then(|ty| ty.utf8_text(text.as_bytes()).ok())
+            .map(str::to_string);
+    }
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        let field = match ancestor.kind() {
+            "impl_item" => "type",
+            "trait_item"
+            | "class_definition"
+            | "class_declaration"
+            | "abstract_class_declaration"
+            | "class"
+            | "interface_declaration" => "name",
+            _ => {
+                current = ancestor.parent();
+                continue;
+            }
+        };
+        let name = ancestor.child_by_field_name(field)?;
+        let name = name.utf8_text(text.as_bytes()).ok()?;
+        // `impl<T> Stack<T>` is listed under `Stack`
+        return Some(name.split('<').next().unwrap_or(name).trim().to_string());
+    }
+    None
+}
+
+fn find_kind<'a>(node: Node<'a>, kind: &str) -> Option<Node<'a>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .find_map(|child| find_kind(child, kind))
+}
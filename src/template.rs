@@ -0,0 +1,129 @@
+use serde::Serialize;
+use std::error::Error;
+use std::path::Path;
+use tera::{Context, Tera};
+
+use crate::PER_FILE_STRATEGY;
+use crate::error::ConcatError;
+use crate::writer::FileStats;
+
+const HEADER: &str = "header";
+const DOCUMENT: &str = "document";
+
+/// User-supplied Tera templates for file headings and the overall document
+#[derive(Debug, Clone, Default)]
+pub struct Templates {
+    tera: Tera,
+    has_header: bool,
+    has_document: bool,
+}
+
+/// Variables available to the header template, and to each entry of `files`
+/// in the document template
+#[derive(Serialize, Debug, Clone)]
+pub struct FileContext {
+    pub path: String,
+    pub lang: String,
+    pub tokens: usize,
+    pub lines: usize,
+    pub size: usize,
+    /// 1-based position of the file in the output
+    pub index: usize,
+    /// Total number of files in the output
+    pub count: usize,
+}
+
+impl FileContext {
+    pub fn new(stats: &FileStats, index: usize, count: usize) -> Self {
+        Self {
+            path: stats.rel_path.display().to_string(),
+            lang: stats.language.clone(),
+            tokens: PER_FILE_STRATEGY.estimate(stats.content_chars),
+            lines: stats.lines,
+            size: stats.size,
+            index: index + 1,
+            count,
+        }
+    }
+}
+
+impl Templates {
+    /// Loads the header and document templates from the given files
+    pub fn load(header: Option<&Path>, document: Option<&Path>) -> Result<Self, ConcatError> {
+        let mut tera = Tera::default();
+        // Output is Markdown, so nothing should be HTML-escaped
+        tera.autoescape_on(Vec::new());
+        for (name, path) in [(HEADER, header), (DOCUMENT, document)] {
+            if let Some(path) = path {
+                tera.add_template_file(path, Some(name))
+                    .map_err(|e| template_error(path, &e))?;
+            }
+        }
+        Ok(Self {
+            tera,
+            has_header: header.is_some(),
+            has_document: document.is_some(),
+        })
+    }
+
+    pub fn has_header(&self) -> bool {
+        self.has_header
+    }
+
+    pub fn has_document(&self) -> bool {
+        self.has_document
+    }
+
+    /// Renders the heading of a single file section
+    pub fn render_header(&self, file: &FileContext) -> Result<String, ConcatError> {
+        let context = Context::from_serialize(file).map_err(|e| render_error(HEADER, &e))?;
+        self.tera
+            .render(HEADER, &context)
+            .map_err(|e| render_error(HEADER, &e))
+    }
+
+    /// Renders the whole document around the already rendered file sections
+    pub fn render_document(
+        &self,
+        content: &str,
+        files: &[FileContext],
+        tokens: usize,
+    ) -> Result<String, ConcatError> {
+        let mut context = Context::new();
+        context.insert("content", content);
+        context.insert("files", files);
+        context.insert("file_count", &files.len());
+        context.insert("tokens", &tokens);
+        self.tera
+            .render(DOCUMENT, &context)
+            .map_err(|e| render_error(DOCUMENT, &e))
+    }
+}
+
+fn template_error(path: &Path, error: &tera::Error) -> ConcatError {
+    ConcatError::Config(format!(
+        "invalid template {}: {}",
+        path.display(),
+        describe(error)
+    ))
+}
+
+fn render_error(name: &str, error: &tera::Error) -> ConcatError {
+    ConcatError::Config(format!(
+        "failed to render {} template: {}",
+        name,
+        describe(error)
+    ))
+}
+
+/// Tera keeps the useful detail in the error's source chain
+fn describe(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
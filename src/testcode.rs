@@ -0,0 +1,99 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::path::Path;
+
+/// What to do with test code
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestMode {
+    /// Leave out test files and Rust `#[cfg(test)]` modules
+    Exclude,
+    /// Keep only test files and Rust `#[cfg(test)]` modules
+    Only,
+    /// Keep everything, marking test files below their heading
+    Tag,
+}
+
+/// Directory names that hold tests in common project layouts
+const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "specs", "testdata"];
+
+/// Whether a file is test code, judged by its path alone
+pub fn is_test_path(rel_path: &Path) -> bool {
+    let in_test_dir = rel_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|c| TEST_DIRS.contains(&c.as_os_str().to_string_lossy().as_ref()));
+    if in_test_dir {
+        return true;
+    }
+
+    let name = rel_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name.as_str(), ""));
+    match ext {
+        "go" | "rs" => stem.ends_with("_test"),
+        "py" => stem.starts_with("test_") || stem.ends_with("_test") || name == "conftest.py",
+        "rb" => stem.ends_with("_spec") || stem.ends_with("_test"),
+        "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => {
+            stem.ends_with(".test") || stem.ends_with(".spec")
+        }
+        "java" | "kt" | "scala" => stem.ends_with("Test") || stem.ends_with("Tests"),
+        "cs" => stem.ends_with("Tests") || stem.ends_with("Test"),
+        "php" => stem.ends_with("Test"),
+        "c" | "cc" | "cpp" | "cxx" => stem.ends_with("_test") || stem.starts_with("test_"),
+        _ => false,
+    }
+}
+
+/// Splits Rust source into the code outside `#[cfg(test)]` modules and the
+/// modules themselves. Braces are counted naively, which holds for the usual
+/// `mod tests { ... }` layout.
+pub fn split_rust_tests(text: &str) -> (String, String) {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut production = String::new();
+    let mut tests = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim() != "#[cfg(test)]" || !starts_module(&lines[i + 1..]) {
+            production.push_str(lines[i]);
+            i += 1;
+            continue;
+        }
+
+        let mut depth = 0usize;
+        let mut opened = false;
+        while i < lines.len() {
+            tests.push_str(lines[i]);
+            for c in lines[i].chars() {
+                match c {
+                    '{' => {
+                        depth += 1;
+                        opened = true;
+                    }
+                    '}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            i += 1;
+            if opened && depth == 0 {
+                break;
+            }
+        }
+    }
+    (production, tests)
+}
+
+/// Whether the item after an attribute (skipping further attributes) is an inline module
+fn starts_module(lines: &[&str]) -> bool {
+    lines
+        .iter()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with("#["))
+        .is_some_and(|line| {
+            let line = line.strip_prefix("pub ").unwrap_or(line);
+            line.starts_with("mod ") && line.ends_with('{')
+        })
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::Path;
+
+use crate::document;
+use crate::error::{ConcatError, output_error};
+
+/// Recreates the files of a generated document below `dest`
+pub fn unpack(input: &Path, dest: &Path, force: bool) -> Result<(), ConcatError> {
+    let text = fs::read_to_string(input).map_err(|source| ConcatError::InputFile {
+        path: input.to_path_buf(),
+        source,
+    })?;
+    let sections = document::parse(&text);
+    if sections.is_empty() {
+        return Err(ConcatError::NoFilesMatched);
+    }
+
+    let mut files = Vec::new();
+    for (rel_path, content) in document::resolve(&sections) {
+        let Some(safe_path) = document::safe_relative(&rel_path) else {
+            eprintln!(
+                "Warning: Skipping {}: path leaves the destination",
+                rel_path.display()
+            );
+            continue;
+        };
+        match content {
+            Ok(content) => files.push((dest.join(safe_path), content)),
+            Err(reason) => eprintln!("Warning: Skipping {}: {}", rel_path.display(), reason),
+        }
+    }
+
+    if !force && let Some((existing, _)) = files.iter().find(|(path, _)| path.exists()) {
+        return Err(ConcatError::OutputExists(existing.clone()));
+    }
+
+    for (path, content) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(output_error(parent))?;
+        }
+        fs::write(path, content).map_err(output_error(path))?;
+    }
+
+    status!("Unpacked {} file(s) into {}", files.len(), dest.display());
+    Ok(())
+}
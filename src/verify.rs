@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::Path;
+
+use crate::cache::sha256_hex;
+use crate::directives;
+use crate::document;
+use crate::error::ConcatError;
+
+/// Compares every section of a generated document against the current files
+/// below `root` and reports the ones that drifted
+pub fn verify(input: &Path, root: &Path) -> Result<(), ConcatError> {
+    let text = fs::read_to_string(input).map_err(|source| ConcatError::InputFile {
+        path: input.to_path_buf(),
+        source,
+    })?;
+    let sections = document::parse(&text);
+    if sections.is_empty() {
+        return Err(ConcatError::NoFilesMatched);
+    }
+
+    let mut unchanged = 0;
+    let mut drifted = 0;
+    for (rel_path, snapshot) in document::resolve(&sections) {
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(reason) => {
+                status!("unknown   {} ({})", rel_path.display(), reason);
+                continue;
+            }
+        };
+        let current = document::safe_relative(&rel_path)
+            .and_then(|path| fs::read_to_string(root.join(path)).ok());
+
+        match current {
+            // Regions left out by md_concat:begin-ignore comments are not drift
+            Some(current)
+                if document::same_content(&current, &snapshot)
+                    || document::same_content(
+                        &directives::strip_ignored(&current).0,
+                        &snapshot,
+                    ) =>
+            {
+                unchanged += 1
+            }
+            Some(current) => {
+                drifted += 1;
+                status!(
+                    "modified  {} (snapshot {}, now {})",
+                    rel_path.display(),
+                    short_hash(&snapshot),
+                    short_hash(&current)
+                );
+            }
+            None => {
+                drifted += 1;
+                status!("missing   {}", rel_path.display());
+            }
+        }
+    }
+
+    status!(
+        "{} file(s) match the snapshot, {} drifted",
+        unchanged,
+        drifted
+    );
+    if drifted > 0 {
+        return Err(ConcatError::Drift(drifted));
+    }
+    Ok(())
+}
+
+fn short_hash(text: &str) -> String {
+    sha256_hex(text.as_bytes())[..12].to_string()
+}
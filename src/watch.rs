@@ -0,0 +1,149 @@
+use notify::{Event, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::error::ConcatError;
+use crate::overrides::OVERRIDES_FILE;
+use crate::{CliArgs, run, split};
+
+/// How long the file system must stay quiet before a regeneration is triggered
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Runs once, then regenerates the output whenever a relevant file changes.
+/// `argv` is the command line, parsed again when a configuration file changes.
+pub fn watch(args: &CliArgs, argv: &[OsString]) -> Result<(), ConcatError> {
+    match run(args) {
+        Err(e) if e.is_recoverable() => eprintln!("Error: {}", e),
+        result => result?,
+    }
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+
+    for input_dir in &args.input_dirs {
+        watcher
+            .watch(input_dir, RecursiveMode::Recursive)
+            .map_err(io::Error::other)?;
+    }
+
+    // Configuration files outside the input directories are watched through
+    // their directory, since editors often replace a file instead of writing it
+    let input_dirs: Vec<PathBuf> = args
+        .input_dirs
+        .iter()
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .collect();
+    let config_files: Vec<PathBuf> = args
+        .config_sources
+        .iter()
+        .filter_map(|path| resolved(path))
+        .collect();
+    for config_file in &config_files {
+        let Some(dir) = config_file.parent() else {
+            continue;
+        };
+        if !input_dirs
+            .iter()
+            .any(|input_dir| dir.starts_with(input_dir))
+        {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(io::Error::other)?;
+        }
+    }
+
+    // Our own writes must not trigger another run
+    let own_files: Vec<PathBuf> = args
+        .written_files()
+        .iter()
+        .filter_map(|path| resolved(path))
+        .collect();
+    let split_output = args
+        .output_file
+        .as_deref()
+        .filter(|_| args.split_tokens.is_some() || args.split_by.is_some());
+
+    let mut args = args.clone();
+    status!("\nWatching for changes (press Ctrl+C to stop)...");
+
+    while let Ok(event) = rx.recv() {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Warning: Watch error: {}", e);
+                continue;
+            }
+        };
+
+        // Reading files during a run produces access events; only modifications matter
+        if event.kind.is_access() {
+            continue;
+        }
+
+        let paths: Vec<PathBuf> = event
+            .paths
+            .iter()
+            .filter_map(|path| resolved(path))
+            .collect();
+        let config_changed = paths.iter().any(|path| config_files.contains(path));
+        let relevant = config_changed
+            || paths.iter().any(|path| {
+                if own_files.contains(path)
+                    || split_output.is_some_and(|output| split::is_group_document(output, path))
+                {
+                    return false;
+                }
+                let is_rule_file = path
+                    .file_name()
+                    .is_some_and(|name| name == ".gitignore" || name == OVERRIDES_FILE);
+                let has_extension = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|ext| args.extensions.iter().any(|e| e == ext));
+                is_rule_file || has_extension
+            });
+
+        if !relevant {
+            continue;
+        }
+
+        // Wait until changes settle before regenerating
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        if config_changed {
+            status!("\nConfiguration changed, reloading...");
+            match crate::reload_args(argv, &args) {
+                Ok(reloaded) => args = reloaded,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    continue;
+                }
+            }
+        } else {
+            status!("\nChange detected, regenerating...");
+        }
+        // Later runs replace the output this session just wrote
+        let mut rerun_args = args.clone();
+        rerun_args.force = true;
+        rerun_args.backup = None;
+        if let Err(e) = run(&rerun_args) {
+            eprintln!("Error: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// `path` with its directory canonicalized, so that event paths compare equal
+/// to the files of the run whether or not those exist at the time
+fn resolved(path: &Path) -> Option<PathBuf> {
+    let dir = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Some(fs::canonicalize(dir).ok()?.join(path.file_name()?))
+}
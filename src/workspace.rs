@@ -0,0 +1,270 @@
+use globset::Glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::ConcatError;
+
+/// A package of a workspace
+#[derive(Debug)]
+pub struct Member {
+    /// Package name from the member's manifest, if it declares one
+    pub name: Option<String>,
+    /// Canonical directory of the member
+    pub dir: PathBuf,
+}
+
+impl Member {
+    /// Whether `wanted` names this member, by package name or directory name
+    fn is_named(&self, wanted: &str) -> bool {
+        self.name.as_deref() == Some(wanted)
+            || self.name.as_deref().and_then(|n| n.rsplit('/').next()) == Some(wanted)
+            || self.dir.file_name().and_then(|n| n.to_str()) == Some(wanted)
+    }
+
+    fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            self.dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// A Cargo, npm/pnpm or Go workspace found at a directory
+#[derive(Debug)]
+pub struct Workspace {
+    kind: &'static str,
+    /// Configuration files at the root that every member shares
+    pub root_files: Vec<PathBuf>,
+    pub members: Vec<Member>,
+}
+
+/// Root files kept alongside the selected members, per workspace kind
+const CARGO_ROOT_FILES: &[&str] = &["Cargo.toml", "rust-toolchain.toml", ".cargo/config.toml"];
+const NPM_ROOT_FILES: &[&str] = &[
+    "package.json",
+    "pnpm-workspace.yaml",
+    "tsconfig.json",
+    "tsconfig.base.json",
+];
+const GO_ROOT_FILES: &[&str] = &["go.work"];
+
+/// Whether `path` is a root file of any workspace kind, e.g. `.cargo/config.toml`
+pub fn is_root_file(path: &Path) -> bool {
+    [CARGO_ROOT_FILES, NPM_ROOT_FILES, GO_ROOT_FILES]
+        .concat()
+        .iter()
+        .any(|file| path.ends_with(file))
+}
+
+impl Workspace {
+    /// Looks for a workspace definition directly in `root`
+    pub fn detect(root: &Path) -> Result<Option<Self>, ConcatError> {
+        let root = fs::canonicalize(root).map_err(|source| ConcatError::InputDir {
+            path: root.to_path_buf(),
+            source,
+        })?;
+
+        let (kind, patterns, root_files) = if let Some(patterns) = cargo_members(&root)? {
+            ("Cargo", patterns, CARGO_ROOT_FILES)
+        } else if let Some(patterns) = pnpm_members(&root) {
+            ("pnpm", patterns, NPM_ROOT_FILES)
+        } else if let Some(patterns) = npm_members(&root)? {
+            ("npm", patterns, NPM_ROOT_FILES)
+        } else if let Some(patterns) = go_members(&root) {
+            ("Go", patterns, GO_ROOT_FILES)
+        } else {
+            return Ok(None);
+        };
+
+        let mut members = Vec::new();
+        for pattern in &patterns {
+            for dir in expand(&root, pattern) {
+                if members.iter().any(|m: &Member| m.dir == dir) {
+                    continue;
+                }
+                members.push(Member {
+                    name: package_name(kind, &dir),
+                    dir,
+                });
+            }
+        }
+        Ok(Some(Self {
+            kind,
+            root_files: root_files
+                .iter()
+                .map(|file| root.join(file))
+                .filter(|path| path.is_file())
+                .collect(),
+            members,
+        }))
+    }
+
+    /// The members with the given names; unknown names are an error listing the known ones
+    pub fn select(&self, names: &[String]) -> Result<Vec<&Member>, ConcatError> {
+        names
+            .iter()
+            .map(|name| {
+                self.members
+                    .iter()
+                    .find(|member| member.is_named(name))
+                    .ok_or_else(|| {
+                        let known: Vec<String> = self.members.iter().map(Member::label).collect();
+                        ConcatError::Config(format!(
+                            "no {} workspace member named '{}' (members: {})",
+                            self.kind,
+                            name,
+                            known.join(", ")
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+}
+
+/// `workspace.members` of a root Cargo.toml
+fn cargo_members(root: &Path) -> Result<Option<Vec<String>>, ConcatError> {
+    let Ok(text) = fs::read_to_string(root.join("Cargo.toml")) else {
+        return Ok(None);
+    };
+    let manifest: toml::Table = toml::from_str(&text)
+        .map_err(|e| ConcatError::Config(format!("invalid Cargo.toml: {}", e)))?;
+    Ok(manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(|members| members.as_array())
+        .map(|members| {
+            members
+                .iter()
+                .filter_map(|m| m.as_str().map(str::to_string))
+                .collect()
+        }))
+}
+
+/// `workspaces` of a root package.json, as a list or as `{ "packages": [...] }`
+fn npm_members(root: &Path) -> Result<Option<Vec<String>>, ConcatError> {
+    let Ok(text) = fs::read_to_string(root.join("package.json")) else {
+        return Ok(None);
+    };
+    let package: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| ConcatError::Config(format!("invalid package.json: {}", e)))?;
+    let workspaces = package.get("workspaces");
+    let list = workspaces
+        .and_then(|w| w.as_array())
+        .or_else(|| workspaces.and_then(|w| w.get("packages")?.as_array()));
+    Ok(list.map(|patterns| {
+        patterns
+            .iter()
+            .filter_map(|p| p.as_str().map(str::to_string))
+            .collect()
+    }))
+}
+
+/// The `packages:` list of pnpm-workspace.yaml. Only the plain list form is
+/// understood, which is what pnpm itself documents.
+fn pnpm_members(root: &Path) -> Option<Vec<String>> {
+    let text = fs::read_to_string(root.join("pnpm-workspace.yaml")).ok()?;
+    let mut in_packages = false;
+    let mut patterns = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = trimmed == "packages:";
+            continue;
+        }
+        if in_packages && let Some(item) = trimmed.strip_prefix('-') {
+            patterns.push(item.trim().trim_matches(['\'', '"']).to_string());
+        }
+    }
+    Some(patterns)
+}
+
+/// `use` directives of go.work, in single-line and block form
+fn go_members(root: &Path) -> Option<Vec<String>> {
+    let text = fs::read_to_string(root.join("go.work")).ok()?;
+    let mut in_block = false;
+    let mut dirs = Vec::new();
+    for line in text.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if in_block {
+            if line == ")" {
+                in_block = false;
+            } else if !line.is_empty() {
+                dirs.push(line.to_string());
+            }
+        } else if let Some(rest) = line.strip_prefix("use") {
+            match rest.trim() {
+                "(" => in_block = true,
+                "" => {}
+                dir => dirs.push(dir.to_string()),
+            }
+        }
+    }
+    Some(dirs)
+}
+
+fn package_name(kind: &str, dir: &Path) -> Option<String> {
+    match kind {
+        "Cargo" => {
+            let text = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+            let manifest: toml::Table = toml::from_str(&text).ok()?;
+            Some(manifest.get("package")?.get("name")?.as_str()?.to_string())
+        }
+        "Go" => {
+            let text = fs::read_to_string(dir.join("go.mod")).ok()?;
+            text.lines()
+                .find_map(|line| line.trim().strip_prefix("module "))
+                .map(|module| module.trim().to_string())
+        }
+        _ => {
+            let text = fs::read_to_string(dir.join("package.json")).ok()?;
+            let package: serde_json::Value = serde_json::from_str(&text).ok()?;
+            Some(package.get("name")?.as_str()?.to_string())
+        }
+    }
+}
+
+/// Directories below `root` matching a member pattern such as `crates/*`.
+/// Negated patterns (`!`) are ignored.
+fn expand(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    if pattern.starts_with('!') {
+        return Vec::new();
+    }
+    let mut dirs = vec![root.to_path_buf()];
+    for component in pattern.trim_start_matches("./").split('/') {
+        if component.is_empty() || component == "." {
+            continue;
+        }
+        let Ok(glob) = Glob::new(component) else {
+            return Vec::new();
+        };
+        let matcher = glob.compile_matcher();
+        dirs = dirs
+            .iter()
+            .flat_map(|dir| {
+                let mut children: Vec<PathBuf> = fs::read_dir(dir)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter(|entry| entry.path().is_dir())
+                    .filter(|entry| matcher.is_match(entry.file_name()))
+                    .map(|entry| entry.path())
+                    .collect();
+                children.sort();
+                children
+            })
+            .collect();
+    }
+    dirs.into_iter()
+        .filter(|dir| dir != root)
+        .filter_map(|dir| fs::canonicalize(dir).ok())
+        .collect()
+}
@@ -0,0 +1,959 @@
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use encoding_rs::{Encoding, WINDOWS_1252};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::assets;
+use crate::cache::sha256_hex;
+use crate::comments;
+use crate::dedupe::SimilarityIndex;
+use crate::directives;
+use crate::error::ConcatError;
+use crate::history;
+use crate::languages::LanguageMap;
+use crate::license;
+use crate::output::OutputFormat;
+use crate::overrides;
+use crate::project;
+use crate::public_api;
+use crate::redact::{RedactionCounts, Redactor};
+use crate::sanitize;
+use crate::summary::Footer;
+use crate::symbols::{self, Symbol};
+use crate::template::{FileContext, Templates};
+use crate::testcode::{self, TestMode};
+use crate::{CliArgs, PER_FILE_STRATEGY, TokenCounter};
+
+/// Size of a single file's section in the generated output
+#[derive(Debug, Clone, Default)]
+pub struct FileStats {
+    pub rel_path: PathBuf,
+    pub char_count: usize,
+    /// Size of the file's section in the output, in bytes
+    pub bytes: usize,
+    /// Whether the file could not be opened or read
+    pub unreadable: bool,
+    /// Characters of the file content alone
+    pub content_chars: usize,
+    pub lines: usize,
+    /// Size of the file on disk in bytes
+    pub size: usize,
+    /// Redactions applied to the content, per rule
+    pub redactions: RedactionCounts,
+    /// Number of instruction-like phrases neutralized by --sanitize
+    pub sanitized: usize,
+    /// Earlier file with identical content, written in place of this file's body
+    pub duplicate_of: Option<PathBuf>,
+    /// Earlier, nearly identical file this file is written as a diff against
+    pub similar_to: Option<PathBuf>,
+    /// Content characters left out by deduplication
+    pub saved_chars: usize,
+    /// Processed content, kept only if WriteOptions::keep_content is set
+    pub content: Option<String>,
+    /// Definitions found in the file, collected only if WriteOptions::symbol_index is set
+    pub symbols: Vec<Symbol>,
+    /// Code fence language, judged from the content for ambiguous extensions
+    pub language: String,
+}
+
+impl FileStats {
+    /// Estimated tokens for this section using the per-file strategy
+    pub fn tokens(&self) -> usize {
+        PER_FILE_STRATEGY.estimate(self.char_count)
+    }
+}
+
+/// What to do with a file that cannot be opened or read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnreadablePolicy {
+    /// Emit the section with an error note in place of the content
+    Placeholder,
+    /// Leave the file out of the output
+    Skip,
+    /// Abort the run
+    Fail,
+}
+
+/// How file contents are decoded into text
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InputEncoding {
+    /// Require valid UTF-8; other files are reported as unreadable
+    #[default]
+    Utf8,
+    /// Honour UTF-16 BOMs, keep valid UTF-8, and detect the encoding of anything else
+    Auto,
+    /// Decode as Latin-1 (Windows-1252)
+    Latin1,
+    /// Decode as UTF-8, replacing invalid sequences with U+FFFD
+    Lossy,
+}
+
+/// How Markdown files are written
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkdownMode {
+    /// Wrap them in a code fence like any other file
+    #[default]
+    Fence,
+    /// Write them as-is, with their headings demoted below the file heading
+    Passthrough,
+}
+
+/// Character code fences are made of
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FenceStyle {
+    /// ```
+    #[default]
+    Backtick,
+    /// ~~~
+    Tilde,
+}
+
+impl FenceStyle {
+    /// A fence longer than any run of its character inside `text`, so the text cannot close it early
+    pub fn fence_for(self, text: &str) -> String {
+        let c = match self {
+            FenceStyle::Backtick => '`',
+            FenceStyle::Tilde => '~',
+        };
+        let longest = text
+            .split(|other| other != c)
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        c.to_string().repeat(longest.max(2) + 1)
+    }
+}
+
+/// Metadata that can be listed below each file heading
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderField {
+    /// File size in bytes
+    Size,
+    /// Last modification time (UTC)
+    Mtime,
+    /// SHA-256 of the file as stored on disk
+    Sha256,
+    /// Estimated tokens of the file content
+    Tokens,
+    /// Number of lines
+    Lines,
+}
+
+/// Settings that control how file sections are rendered
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    pub unreadable: UnreadablePolicy,
+    pub encoding: InputEncoding,
+    /// Keep a leading UTF-8 byte order mark instead of stripping it
+    pub keep_bom: bool,
+    /// Prefix content lines with right-aligned line numbers
+    pub line_numbers: bool,
+    /// Remove comments from the files of the symbol index languages
+    pub strip_comments: bool,
+    /// Keep only this many lines of each file
+    pub max_lines_per_file: Option<usize>,
+    /// Input directories (canonical) whose subdirectories may hold `.mdconcat.toml` overrides
+    pub override_roots: Vec<PathBuf>,
+    /// Metadata listed below each heading, in order
+    pub header_fields: Vec<HeaderField>,
+    pub templates: Templates,
+    pub languages: LanguageMap,
+    pub redactor: Redactor,
+    /// Remove the regions marked with `md_concat:begin-ignore` / `end-ignore` comments
+    pub directives: bool,
+    /// Neutralize prompt-injection phrases in file contents
+    pub sanitize: bool,
+    /// Replace bodies identical to an earlier file's with a reference to it
+    pub dedupe_content: bool,
+    /// Similarity threshold above which files are written as a diff against an earlier file
+    pub dedupe_similar: Option<f64>,
+    /// Text written before the first file section
+    pub preamble: String,
+    /// Text written after the last file section, ending with the task if one is given
+    pub epilogue: String,
+    /// Keep each file's processed content in its FileStats, for other output formats
+    pub keep_content: bool,
+    /// Handling of test code
+    pub tests: Option<TestMode>,
+    /// Append an index of the symbols defined in each file
+    pub symbol_index: bool,
+    /// Keep only these functions and types of each file
+    pub extract_symbols: Vec<String>,
+    /// Number of recent commits listed under each file heading
+    pub file_history: Option<usize>,
+    /// Git revision the files are read at (--at-ref), for --file-history
+    pub revision: String,
+    /// Keep only the functions reachable through calls from this entry point
+    pub slice_from: Option<String>,
+    /// Reduce Rust files to their public items and signatures
+    pub public_api: bool,
+    /// How Markdown files are written
+    pub markdown: MarkdownMode,
+    /// Level of the heading above each file, and of the other sections the writer adds
+    pub heading_level: usize,
+    pub fence: FenceStyle,
+    /// Estimated tokens of each file in its heading
+    pub annotate_tokens: bool,
+    /// Files estimated above this many tokens are marked in their heading
+    pub warn_file_tokens: Option<usize>,
+    /// Table of the project's dependencies, written after the file sections (--deps-appendix)
+    pub deps_appendix: String,
+    /// Table of the binary files left out, written after the dependencies (--assets-inventory)
+    pub assets_inventory: String,
+    /// Closing summary section, if --summary-footer is set
+    pub summary_footer: Option<Footer>,
+    /// Leave out the per-file warnings and notes, for renders that only measure
+    pub quiet: bool,
+}
+
+impl WriteOptions {
+    pub fn from_args(args: &CliArgs) -> Result<Self, ConcatError> {
+        if args.deterministic && args.header_fields.contains(&HeaderField::Mtime) {
+            return Err(ConcatError::Config(
+                "--deterministic forbids the mtime header field".into(),
+            ));
+        }
+        if !(1..=6).contains(&args.heading_level) {
+            return Err(ConcatError::Config(
+                "heading_level must be between 1 and 6".into(),
+            ));
+        }
+        let unreadable = if args.strict {
+            UnreadablePolicy::Fail
+        } else if args.skip_unreadable {
+            UnreadablePolicy::Skip
+        } else {
+            UnreadablePolicy::Placeholder
+        };
+        // Input directories as read (canonical), or the root of --files-from
+        let roots: Vec<PathBuf> = match args.files_from {
+            Some(_) => vec![args.files_root.clone()],
+            None => args
+                .input_dirs
+                .iter()
+                .map(|dir| args.input_root(dir))
+                .collect(),
+        }
+        .iter()
+        .filter_map(|root| fs::canonicalize(root).ok())
+        .collect();
+        let packages = if args.project_summary || args.deps_appendix || args.license_notice {
+            project::detect(&roots)
+        } else {
+            Vec::new()
+        };
+        if (args.project_summary || args.deps_appendix) && packages.is_empty() {
+            status!(
+                "No Cargo.toml, package.json, pyproject.toml or go.mod found for --project-summary or --deps-appendix"
+            );
+        }
+        let license_notice = if args.license_notice {
+            let notice = license::notice(&roots, &packages);
+            if notice.is_none() {
+                status!("--license-notice: no license file or declared license found");
+            }
+            notice.unwrap_or_default()
+        } else {
+            String::new()
+        };
+        // A walk of the input directories; --files-from and --from-manifest name the files
+        let assets_inventory =
+            if args.assets_inventory && args.files_from.is_none() && args.from_manifest.is_none() {
+                let assets = assets::inventory(args, &roots)?;
+                status!("Assets left out: {}", assets.len());
+                if assets.is_empty() {
+                    String::new()
+                } else {
+                    assets::section(&assets, args.heading_level.into())
+                }
+            } else {
+                String::new()
+            };
+        let project_summary = if args.project_summary && !packages.is_empty() {
+            project::overview_section(&packages, args.heading_level.into())
+        } else {
+            String::new()
+        };
+        Ok(Self {
+            unreadable,
+            encoding: args.encoding,
+            keep_bom: args.keep_bom,
+            line_numbers: args.line_numbers,
+            strip_comments: args.strip_comments,
+            max_lines_per_file: args.max_lines_per_file.filter(|&lines| lines > 0),
+            override_roots: roots.clone(),
+            header_fields: args
+                .header_fields
+                .iter()
+                .copied()
+                .filter(|field| !(args.cache_stable && *field == HeaderField::Mtime))
+                .collect(),
+            templates: Templates::load(
+                args.header_template.as_deref(),
+                args.document_template.as_deref(),
+            )?,
+            languages: LanguageMap::new(&args.lang_map),
+            redactor: Redactor::new(args.redact_secrets, args.redact_rules.as_deref())?,
+            directives: args.directives,
+            sanitize: args.sanitize,
+            dedupe_content: args.dedupe_content,
+            dedupe_similar: args.dedupe_similar,
+            preamble: license_notice
+                + &surrounding_text(&args.prepend_file, &args.prepend_text)?
+                + &project_summary
+                + &match args.git_log_summary {
+                    Some(count) => history::log_section(
+                        args.input_dirs
+                            .first()
+                            .map_or(Path::new("."), PathBuf::as_path),
+                        args.revision(),
+                        count,
+                        args.heading_level.into(),
+                    )?,
+                    None => String::new(),
+                },
+            epilogue: surrounding_text(&args.append_file, &args.append_text)?
+                + &args
+                    .task
+                    .as_deref()
+                    .map(|task| task_section(task, args.heading_level.into()))
+                    .unwrap_or_default(),
+            tests: args.tests,
+            symbol_index: args.symbol_index,
+            extract_symbols: args.extract_symbols.clone(),
+            slice_from: args.slice_from.clone(),
+            file_history: args.file_history,
+            revision: args.revision().to_string(),
+            public_api: args.public_api,
+            markdown: args.md,
+            heading_level: args.heading_level.into(),
+            fence: args.fence,
+            annotate_tokens: args.annotate_tokens,
+            warn_file_tokens: args.warn_file_tokens,
+            deps_appendix: if args.deps_appendix && !packages.is_empty() {
+                project::dependencies_section(&packages, args.heading_level.into())
+            } else {
+                String::new()
+            },
+            assets_inventory,
+            summary_footer: args.summary_footer.then(|| Footer::new(args)),
+            quiet: false,
+            keep_content: args
+                .outputs
+                .iter()
+                .any(|target| target.format == OutputFormat::Xml),
+        })
+    }
+}
+
+/// Writer that feeds every byte written through it to a TokenCounter, so the
+/// estimates describe exactly the output
+pub struct CountingWriter<'a, W: Write> {
+    inner: W,
+    counter: &'a mut TokenCounter,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    pub fn new(inner: W, counter: &'a mut TokenCounter) -> Self {
+        Self { inner, counter }
+    }
+
+    /// Characters written so far, including those counted before wrapping
+    pub fn char_count(&self) -> usize {
+        self.counter.char_count
+    }
+
+    /// Bytes written so far, including those counted before wrapping
+    pub fn byte_count(&self) -> usize {
+        self.counter.byte_count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Written whole, so a character is never split between two counts
+        self.inner.write_all(buf)?;
+        self.counter.add_text(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes all file sections, wrapped in the document template if one is set and
+/// surrounded by the preamble and epilogue.
+/// Returns one entry per input file, in the same order.
+pub fn write_document<W: Write>(
+    writer: &mut W,
+    found_files: &[(PathBuf, PathBuf)],
+    token_counter: &mut TokenCounter,
+    options: &WriteOptions,
+) -> Result<Vec<FileStats>, ConcatError> {
+    let writer = &mut CountingWriter::new(writer, token_counter);
+    writer.write_all(options.preamble.as_bytes())?;
+    let stats = if options.templates.has_document() {
+        let mut body = Vec::new();
+        let mut body_counter = TokenCounter::new();
+        let stats = write_files(&mut body, found_files, &mut body_counter, options)?;
+        let contexts: Vec<FileContext> = stats
+            .iter()
+            .enumerate()
+            .map(|(i, file)| FileContext::new(file, i, stats.len()))
+            .collect();
+        let document = options.templates.render_document(
+            &String::from_utf8_lossy(&body),
+            &contexts,
+            PER_FILE_STRATEGY.estimate(body_counter.char_count),
+        )?;
+        writer.write_all(document.as_bytes())?;
+        stats
+    } else {
+        write_sections(writer, found_files, options)?
+    };
+    if options.symbol_index {
+        writer.write_all(symbol_index(&stats, options.heading_level).as_bytes())?;
+    }
+    writer.write_all(options.deps_appendix.as_bytes())?;
+    writer.write_all(options.assets_inventory.as_bytes())?;
+    if let Some(ref footer) = options.summary_footer {
+        writer.write_all(footer.render(&stats, options.heading_level).as_bytes())?;
+    }
+    writer.write_all(options.epilogue.as_bytes())?;
+    Ok(stats)
+}
+
+/// Appendix listing every definition with the file and line it is found at,
+/// in the spirit of a ctags file
+fn symbol_index(stats: &[FileStats], heading_level: usize) -> String {
+    let mut index = format!("{} Symbol Index\n\n", "#".repeat(heading_level));
+    let entries: Vec<String> = stats
+        .iter()
+        .flat_map(|file| {
+            file.symbols.iter().map(|symbol| {
+                format!(
+                    "- `{}` {} — `{}:{}`\n",
+                    symbol.name,
+                    symbol.kind,
+                    file.rel_path.display(),
+                    symbol.line
+                )
+            })
+        })
+        .collect();
+    if entries.is_empty() {
+        index.push_str("No symbols found.\n");
+    }
+    index.extend(entries);
+    index.push('\n');
+    index
+}
+
+/// Final section stating what should be done with the files above
+fn task_section(task: &str, heading_level: usize) -> String {
+    format!(
+        "---\n\n{} Task\n\n{}\n",
+        "#".repeat(heading_level),
+        task.trim()
+    )
+}
+
+/// Joins the given files and texts into a block separated from the files by a blank line
+fn surrounding_text(files: &[PathBuf], texts: &[String]) -> Result<String, ConcatError> {
+    let mut block = String::new();
+    let file_texts = files.iter().map(|path| {
+        fs::read_to_string(path)
+            .map_err(|e| ConcatError::Config(format!("cannot read {}: {}", path.display(), e)))
+    });
+    for text in file_texts.chain(texts.iter().cloned().map(Ok)) {
+        let text = text?;
+        block.push_str(text.trim_end_matches('\n'));
+        block.push_str("\n\n");
+    }
+    Ok(block)
+}
+
+/// Writes each file as a heading followed by a fenced code block.
+/// Returns one entry per input file, in the same order.
+pub fn write_files<W: Write>(
+    writer: &mut W,
+    found_files: &[(PathBuf, PathBuf)],
+    token_counter: &mut TokenCounter,
+    options: &WriteOptions,
+) -> Result<Vec<FileStats>, ConcatError> {
+    write_sections(
+        &mut CountingWriter::new(writer, token_counter),
+        found_files,
+        options,
+    )
+}
+
+/// The functions of each file reachable through calls from `entry`
+/// (--slice-from), or None if no function has that name
+pub fn slice_files(
+    found_files: &[(PathBuf, PathBuf)],
+    entry: &str,
+) -> Option<Vec<HashSet<String>>> {
+    let texts: Vec<String> = found_files
+        .iter()
+        .map(|(_, abs_path)| {
+            let bytes = fs::read(abs_path).unwrap_or_default();
+            String::from_utf8_lossy(&bytes).into_owned()
+        })
+        .collect();
+    let sources: Vec<(&Path, &str)> = found_files
+        .iter()
+        .zip(&texts)
+        .map(|((rel_path, _), text)| (rel_path.as_path(), text.as_str()))
+        .collect();
+    symbols::slice(&sources, entry)
+}
+
+fn write_sections<W: Write>(
+    writer: &mut CountingWriter<W>,
+    found_files: &[(PathBuf, PathBuf)],
+    options: &WriteOptions,
+) -> Result<Vec<FileStats>, ConcatError> {
+    let mut stats = Vec::with_capacity(found_files.len());
+    // First file seen with each content hash, for --dedupe-content
+    let mut first_by_hash: HashMap<String, PathBuf> = HashMap::new();
+    let mut similarity_index = options.dedupe_similar.map(SimilarityIndex::new);
+    // Functions reachable from --slice-from, as qualified names per file
+    let sliced = options
+        .slice_from
+        .as_ref()
+        .and_then(|entry| slice_files(found_files, entry))
+        .unwrap_or_default();
+    let histories = options
+        .file_history
+        .map(|count| history::file_histories(found_files, count, &options.revision))
+        .unwrap_or_default();
+    let overrides = overrides::for_files(found_files, &options.override_roots)?;
+
+    for (index, (rel_path, abs_path)) in found_files.iter().enumerate() {
+        // Options changed by the .mdconcat.toml files above this one
+        let file_options = overrides[index]
+            .as_ref()
+            .map(|overrides| overrides.apply(options));
+        let options = file_options.as_ref().unwrap_or(options);
+        let chars_before = writer.char_count();
+        let bytes_before = writer.byte_count();
+
+        let bytes = fs::read(abs_path).map_err(|e| format!("Could not open file: {}", e));
+        let size = bytes.as_ref().map_or(0, Vec::len);
+        let metadata = match bytes {
+            Ok(ref bytes) if !options.header_fields.is_empty() => {
+                Some(metadata_line(abs_path, bytes, &options.header_fields))
+            }
+            _ => None,
+        };
+        let mut redactions = RedactionCounts::new();
+        let mut sanitized = 0;
+        let mut file_symbols = Vec::new();
+        let content = bytes
+            .and_then(|bytes| decode_file(abs_path, bytes, options))
+            .map(|mut text| {
+                // Taken from the file as it is on disk so that line numbers match it
+                if options.symbol_index {
+                    file_symbols = symbols::symbols(rel_path, &text);
+                }
+                if options.directives {
+                    let ignored_lines;
+                    (text, ignored_lines) = directives::strip_ignored(&text);
+                    file_symbols
+                        .retain(|symbol| ignored_lines.binary_search(&symbol.line).is_err());
+                }
+                if !options.extract_symbols.is_empty() {
+                    text = symbols::extract(rel_path, &text, &options.extract_symbols)
+                        .unwrap_or_default();
+                }
+                if let Some(names) = sliced.get(index) {
+                    text = symbols::extract_qualified(rel_path, &text, names).unwrap_or_default();
+                }
+                if options.public_api && rel_path.extension().is_some_and(|ext| ext == "rs") {
+                    text = public_api::summarize(&text).unwrap_or_default();
+                }
+                if let Some(mode) = options.tests
+                    && rel_path.extension().is_some_and(|ext| ext == "rs")
+                    && !testcode::is_test_path(rel_path)
+                {
+                    let (production, tests) = testcode::split_rust_tests(&text);
+                    match mode {
+                        TestMode::Exclude => text = production,
+                        TestMode::Only => text = tests,
+                        TestMode::Tag => {}
+                    }
+                }
+                if options.strip_comments {
+                    text = comments::strip(rel_path, &text);
+                }
+                if !options.redactor.is_empty() {
+                    (text, redactions) = options.redactor.redact(rel_path, text);
+                }
+                if options.sanitize {
+                    (text, sanitized) = sanitize::sanitize(text);
+                }
+                if let Some(max_lines) = options.max_lines_per_file {
+                    text = truncate_lines(text, max_lines);
+                }
+                if options.line_numbers {
+                    number_lines(&text)
+                } else {
+                    text
+                }
+            });
+        if let Err(ref reason) = content {
+            match options.unreadable {
+                UnreadablePolicy::Fail => {
+                    return Err(ConcatError::Unreadable {
+                        path: rel_path.clone(),
+                        reason: reason.clone(),
+                    });
+                }
+                UnreadablePolicy::Skip => {
+                    if !options.quiet {
+                        eprintln!("Warning: Skipping {}: {}", abs_path.display(), reason);
+                    }
+                    stats.push(FileStats {
+                        rel_path: rel_path.clone(),
+                        char_count: 0,
+                        unreadable: true,
+                        size,
+                        ..Default::default()
+                    });
+                    continue;
+                }
+                UnreadablePolicy::Placeholder => {}
+            }
+        }
+
+        let text = content.as_deref().unwrap_or("");
+        let mut file_stats = FileStats {
+            rel_path: rel_path.clone(),
+            char_count: 0,
+            unreadable: content.is_err(),
+            content_chars: text.chars().count(),
+            lines: text.lines().count(),
+            size,
+            redactions,
+            sanitized,
+            content: content
+                .as_ref()
+                .ok()
+                .filter(|_| options.keep_content)
+                .cloned(),
+            symbols: file_symbols,
+            language: options
+                .languages
+                .language_for_content(rel_path, text)
+                .to_string(),
+            ..Default::default()
+        };
+        if options.dedupe_content && content.is_ok() {
+            let original = first_by_hash
+                .entry(sha256_hex(text.as_bytes()))
+                .or_insert_with(|| rel_path.clone());
+            if original != rel_path {
+                file_stats.duplicate_of = Some(original.clone());
+                file_stats.saved_chars = file_stats.content_chars;
+            }
+        }
+        let near_duplicate = match similarity_index {
+            Some(ref mut index) if content.is_ok() && file_stats.duplicate_of.is_none() => {
+                index.check(rel_path, text)
+            }
+            _ => None,
+        };
+
+        let header = if options.templates.has_header() {
+            let context = FileContext::new(&file_stats, index, found_files.len());
+            options.templates.render_header(&context)?
+        } else {
+            let tokens = PER_FILE_STRATEGY.estimate(file_stats.content_chars);
+            let annotation = if options.warn_file_tokens.is_some_and(|limit| tokens > limit) {
+                format!(" (⚠ ≈{} tokens)", thousands(tokens))
+            } else if options.annotate_tokens {
+                format!(" (≈{} tokens)", thousands(tokens))
+            } else {
+                String::new()
+            };
+            format!(
+                "{} {}{}\n\n",
+                "#".repeat(options.heading_level),
+                rel_path.display(),
+                annotation
+            )
+        };
+        let fence = options.fence.fence_for(text);
+
+        write!(writer, "{}", header)?;
+        if options.tests == Some(TestMode::Tag) && testcode::is_test_path(rel_path) {
+            write!(writer, "*Test file*\n\n")?;
+        }
+        if let Some(ref metadata) = metadata {
+            write!(writer, "{}", metadata)?;
+        }
+        if let Some(commits) = histories.get(index).filter(|commits| !commits.is_empty()) {
+            writeln!(writer, "History:")?;
+            for commit in commits {
+                writeln!(writer, "- {}", commit)?;
+            }
+            writeln!(writer)?;
+        }
+
+        if let Some(ref original) = file_stats.duplicate_of {
+            write!(writer, "Identical to `{}`.\n\n", original.display())?;
+            file_stats.char_count = writer.char_count() - chars_before;
+            file_stats.bytes = writer.byte_count() - bytes_before;
+            stats.push(file_stats);
+            continue;
+        }
+
+        if let Some(near) = near_duplicate {
+            let fence = options.fence.fence_for(&near.diff);
+            write!(
+                writer,
+                "Nearly identical to `{}` ({:.0}% similar); differences:\n\n{}diff\n{}{}\n\n",
+                near.original.display(),
+                near.similarity * 100.0,
+                fence,
+                near.diff,
+                fence
+            )?;
+            file_stats.saved_chars = file_stats
+                .content_chars
+                .saturating_sub(near.diff.chars().count());
+            file_stats.similar_to = Some(near.original);
+            file_stats.char_count = writer.char_count() - chars_before;
+            file_stats.bytes = writer.byte_count() - bytes_before;
+            stats.push(file_stats);
+            continue;
+        }
+
+        if options.markdown == MarkdownMode::Passthrough
+            && !options.line_numbers
+            && let Ok(ref buffer) = content
+            && file_stats.language == "markdown"
+        {
+            let mut body = demote_headings(buffer, options.heading_level);
+            if !body.ends_with('\n') {
+                body.push('\n');
+            }
+            body.push('\n');
+            write!(writer, "{}", body)?;
+            file_stats.char_count = writer.char_count() - chars_before;
+            file_stats.bytes = writer.byte_count() - bytes_before;
+            stats.push(file_stats);
+            continue;
+        }
+
+        writeln!(writer, "{}{}", fence, file_stats.language)?;
+
+        match content {
+            Ok(ref buffer) => {
+                write!(writer, "{}", buffer)?;
+                if !buffer.ends_with('\n') {
+                    writeln!(writer)?;
+                }
+            }
+            Err(ref reason) => {
+                if !options.quiet {
+                    eprintln!("Warning: {}: {}", abs_path.display(), reason);
+                }
+                writeln!(writer, "\nError: {}", reason)?;
+            }
+        }
+
+        write!(writer, "{}\n\n", fence)?;
+
+        file_stats.char_count = writer.char_count() - chars_before;
+        file_stats.bytes = writer.byte_count() - bytes_before;
+        stats.push(file_stats);
+    }
+
+    Ok(stats)
+}
+
+/// Keeps the first `max_lines` lines of `text`, followed by a note of how many
+/// were left out
+fn truncate_lines(text: String, max_lines: usize) -> String {
+    let total = text.lines().count();
+    if total <= max_lines {
+        return text;
+    }
+    let mut kept: String = text.split_inclusive('\n').take(max_lines).collect();
+    if !kept.ends_with('\n') {
+        kept.push('\n');
+    }
+    kept.push_str(&format!("... {} more line(s)\n", total - max_lines));
+    kept
+}
+
+/// Pushes every ATX heading outside code fences `levels` deeper (at most to
+/// level 6), so a Markdown file's own structure nests under its file heading
+fn demote_headings(text: &str, levels: usize) -> String {
+    let mut demoted = String::with_capacity(text.len() + levels * 8);
+    let mut fence: Option<(char, usize)> = None;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        if let Some(marker) = marker {
+            let run = trimmed.chars().take_while(|c| *c == marker).count();
+            if run >= 3 {
+                match fence {
+                    None => fence = Some((marker, run)),
+                    Some((open, len)) if open == marker && run >= len => fence = None,
+                    Some(_) => {}
+                }
+            }
+        }
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        let is_heading = fence.is_none()
+            && (1..=6).contains(&hashes)
+            && line[hashes..].starts_with([' ', '\t', '\n', '\r']);
+        if is_heading {
+            demoted.push_str(&"#".repeat((hashes + levels).min(6) - hashes));
+        }
+        demoted.push_str(line);
+    }
+    demoted
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Reports how many files were replaced by references to identical files or
+/// by diffs against nearly identical ones
+pub fn print_dedupe_report(stats: &[FileStats]) {
+    let identical = stats.iter().filter(|f| f.duplicate_of.is_some()).count();
+    let similar = stats.iter().filter(|f| f.similar_to.is_some()).count();
+    if identical + similar == 0 {
+        status!("No duplicate files found");
+        return;
+    }
+
+    let saved: usize = stats.iter().map(|file| file.saved_chars).sum();
+    status!(
+        "Deduplicated {} identical and {} near-duplicate file(s), saving ~{} tokens",
+        identical,
+        similar,
+        PER_FILE_STRATEGY.estimate(saved)
+    );
+    for file in stats {
+        if let Some(ref original) = file.similar_to {
+            status!(
+                "  {} written as a diff against {}",
+                file.rel_path.display(),
+                original.display()
+            );
+        }
+    }
+}
+
+/// Builds the metadata paragraph shown under a file heading
+fn metadata_line(abs_path: &Path, bytes: &[u8], fields: &[HeaderField]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let parts: Vec<String> = fields
+        .iter()
+        .map(|field| match field {
+            HeaderField::Size => format!("size: {} bytes", bytes.len()),
+            HeaderField::Mtime => {
+                let mtime = fs::metadata(abs_path)
+                    .and_then(|m| m.modified())
+                    .map(|t| DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Secs, true))
+                    .unwrap_or_else(|_| "unknown".to_string());
+                format!("mtime: {}", mtime)
+            }
+            HeaderField::Sha256 => format!("sha256: {}", sha256_hex(bytes)),
+            HeaderField::Tokens => format!(
+                "tokens: {}",
+                PER_FILE_STRATEGY.estimate(text.chars().count())
+            ),
+            HeaderField::Lines => format!("lines: {}", text.lines().count()),
+        })
+        .collect();
+    format!("{}\n\n", parts.join(" · "))
+}
+
+/// Decodes file contents as text, describing the failure if that is not possible
+fn decode_file(
+    abs_path: &Path,
+    mut bytes: Vec<u8>,
+    options: &WriteOptions,
+) -> Result<String, String> {
+    let not_text = || "Could not read file content (e.g., binary or non-UTF-8)".to_string();
+
+    if !options.keep_bom && bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    }
+
+    match options.encoding {
+        InputEncoding::Utf8 => String::from_utf8(bytes).map_err(|_| not_text()),
+        InputEncoding::Lossy if looks_binary(&bytes) => Err(not_text()),
+        InputEncoding::Lossy => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        InputEncoding::Latin1 if looks_binary(&bytes) => Err(not_text()),
+        InputEncoding::Latin1 => Ok(decode(WINDOWS_1252, &bytes)),
+        InputEncoding::Auto => {
+            if let Some((bom_encoding, bom_len)) = Encoding::for_bom(&bytes)
+                && bom_encoding != encoding_rs::UTF_8
+            {
+                return Ok(decode(bom_encoding, &bytes[bom_len..]));
+            }
+            if let Ok(text) = std::str::from_utf8(&bytes) {
+                return Ok(text.to_string());
+            }
+            if looks_binary(&bytes) {
+                return Err(not_text());
+            }
+
+            let mut detector = EncodingDetector::new(Iso2022JpDetection::Allow);
+            detector.feed(&bytes, true);
+            let detected = detector.guess(None, Utf8Detection::Deny);
+            if !options.quiet {
+                status!("Decoded {} as {}", abs_path.display(), detected.name());
+            }
+            Ok(decode(detected, &bytes))
+        }
+    }
+}
+
+/// 1245 -> "1,245"
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Prefixes every line with its 1-based number, right-aligned to the widest number
+fn number_lines(text: &str) -> String {
+    let width = text.lines().count().max(1).to_string().len();
+    let mut numbered = String::with_capacity(text.len() + text.lines().count() * (width + 3));
+    for (i, line) in text.lines().enumerate() {
+        numbered.push_str(&format!("{:>width$} | {}\n", i + 1, line, width = width));
+    }
+    numbered
+}
+
+fn decode(encoding: &'static Encoding, bytes: &[u8]) -> String {
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// Treats content with NUL bytes as binary; text encodings other than UTF-16 never contain them
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}